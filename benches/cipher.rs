@@ -0,0 +1,60 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use rfc::encoding::Encoding;
+use rfc::parallel::with_threads;
+use rfc::{Decryptor, Encryptor, Mode};
+
+const INPUT_SIZES: [usize; 2] = [64 * 1024, 8 * 1024 * 1024];
+
+/// Source code used as benchmark input
+const SOURCE: &[u8] = include_bytes!("../src/rfc/mod.rs");
+
+/// Encrypts `input` with `mode` on a single thread, so that only the cipher pipeline is measured.
+fn encrypt(input: &[u8], key: &[u8], mode: Mode) -> Vec<u8> {
+    with_threads(Some(1), || {
+        Encryptor::new().cipher(mode).key(key).encrypt_buf(input)
+    })
+    .expect("encryption failed")
+}
+
+/// Decrypts `input` with `mode` on a single thread.
+fn decrypt(input: &[u8], key: &[u8], mode: Mode) -> Vec<u8> {
+    with_threads(Some(1), || {
+        Decryptor::new()
+            .cipher(mode)
+            .encoding(Encoding::Plain)
+            .key(key)
+            .decrypt_buf(input)
+    })
+    .expect("decryption failed")
+}
+
+fn bench_cipher(c: &mut Criterion) {
+    let key = b"this_is_my_key".to_vec();
+
+    for mode in [Mode::AES128, Mode::AES256] {
+        let mut group = c.benchmark_group(mode.to_string());
+        group.sample_size(10);
+
+        for size in INPUT_SIZES {
+            let input = SOURCE.repeat(size.div_ceil(SOURCE.len()))[..size].to_vec();
+            let ciphertext = encrypt(&input, &key, mode);
+            group.throughput(Throughput::Bytes(size as u64));
+
+            group.bench_with_input(BenchmarkId::new("encrypt", size), &input, |b, input| {
+                b.iter(|| encrypt(input, &key, mode))
+            });
+
+            group.bench_with_input(
+                BenchmarkId::new("decrypt", size),
+                &ciphertext,
+                |b, ciphertext| b.iter(|| decrypt(ciphertext, &key, mode)),
+            );
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_cipher);
+criterion_main!(benches);
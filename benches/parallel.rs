@@ -0,0 +1,91 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use rfc::parallel::with_threads;
+use rfc::seekable::{encrypt_seekable, DEFAULT_CHUNK_SIZE};
+use rfc::{Encryptor, KdfId, Mode};
+
+const INPUT_SIZE: usize = 32 * 1024 * 1024;
+
+/// Returns compressible input, so that compression does not take a fast path
+fn input() -> Vec<u8> {
+    include_bytes!("../src/rfc/mod.rs")
+        .repeat(INPUT_SIZE / include_bytes!("../src/rfc/mod.rs").len())
+}
+
+/// Thread counts to benchmark: powers of 2 up to the number of cores
+fn thread_counts() -> Vec<usize> {
+    let cores = std::thread::available_parallelism().map_or(1, usize::from);
+    let mut counts: Vec<usize> = (0..).map(|i| 1 << i).take_while(|n| *n < cores).collect();
+    counts.push(cores);
+
+    counts
+}
+
+fn bench_parallel(c: &mut Criterion) {
+    let input = input();
+    let key = b"this_is_my_key".to_vec();
+
+    let mut group = c.benchmark_group("encrypt");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(input.len() as u64));
+
+    for threads in thread_counts() {
+        group.bench_with_input(
+            BenchmarkId::new("aes256", threads),
+            &threads,
+            |b, threads| {
+                b.iter(|| {
+                    with_threads(Some(*threads), || {
+                        Encryptor::new()
+                            .compress(false)
+                            .key(&key)
+                            .encrypt_buf(&input[..])
+                    })
+                    .expect("encryption failed")
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("aes256_lz4", threads),
+            &threads,
+            |b, threads| {
+                b.iter(|| {
+                    with_threads(Some(*threads), || {
+                        Encryptor::new()
+                            .compress(true)
+                            .key(&key)
+                            .encrypt_buf(&input[..])
+                    })
+                    .expect("encryption failed")
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("seekable", threads),
+            &threads,
+            |b, threads| {
+                b.iter(|| {
+                    let mut output = Vec::with_capacity(input.len() + input.len() / 1024);
+                    with_threads(Some(*threads), || {
+                        encrypt_seekable(
+                            &input[..],
+                            &mut output,
+                            &key,
+                            Mode::AES256,
+                            KdfId::PBKDF2,
+                            DEFAULT_CHUNK_SIZE,
+                        )
+                    })
+                    .expect("encryption failed")
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_parallel);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+/// Generates the C header `rfc.h` into `OUT_DIR` from the API in `src/lib.rs`. The
+/// committed `include/rfc.h` is checked against it by `tests/c_api.rs`.
+fn main() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set");
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR not set");
+    let header = format!("{}/rfc.h", out_dir);
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rustc-env=RFC_GENERATED_HEADER={}", header);
+
+    cbindgen::generate(&crate_dir)
+        .expect("unable to generate C header")
+        .write_to_file(header);
+}
@@ -0,0 +1,172 @@
+#ifndef RFC_H
+#define RFC_H
+
+/* Generated by cbindgen from src/lib.rs, do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Success
+ */
+#define RFC_OK 0
+
+/**
+ * Any error without a more specific code
+ */
+#define RFC_ERR_OTHER 1
+
+/**
+ * A null pointer, or an unknown cipher or encoding constant was passed
+ */
+#define RFC_ERR_INVALID_ARGUMENT 2
+
+/**
+ * The key or passphrase is wrong
+ */
+#define RFC_ERR_WRONG_KEY 3
+
+/**
+ * The input is corrupted or has been tampered with
+ */
+#define RFC_ERR_TAMPERED 4
+
+/**
+ * The input is truncated
+ */
+#define RFC_ERR_TRUNCATED 5
+
+/**
+ * The input has an unsupported format version
+ */
+#define RFC_ERR_UNSUPPORTED_VERSION 6
+
+/**
+ * The input was encrypted with an unknown cipher
+ */
+#define RFC_ERR_UNKNOWN_CIPHER 7
+
+/**
+ * An I/O error
+ */
+#define RFC_ERR_IO 8
+
+/**
+ * The input's signature is bad
+ */
+#define RFC_ERR_BAD_SIGNATURE 9
+
+/**
+ * The input's key was derived with an unknown KDF
+ */
+#define RFC_ERR_UNKNOWN_KDF 10
+
+/**
+ * The input was encrypted with another cipher than the one passed to decrypt it
+ */
+#define RFC_ERR_CIPHER_MISMATCH 13
+
+/**
+ * Reads the cipher from the input, only valid for decryption
+ */
+#define RFC_CIPHER_ANY 0
+
+/**
+ * AES-128, the ids of ciphers are those written to file headers
+ */
+#define RFC_CIPHER_AES128 1
+
+/**
+ * AES-256
+ */
+#define RFC_CIPHER_AES256 2
+
+#define RFC_ENCODING_PLAIN 0
+
+#define RFC_ENCODING_HEX 1
+
+#define RFC_ENCODING_B64 2
+
+#define RFC_ENCODING_B64_URL 3
+
+#define RFC_ENCODING_B32 4
+
+#define RFC_ENCODING_B58 5
+
+#define RFC_ENCODING_Z85 6
+
+#define RFC_ENCODING_ARMOR 7
+
+/**
+ * Detects the encoding of the input, only valid for decryption
+ */
+#define RFC_ENCODING_AUTO 8
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Encrypts `input_len` bytes of `input` with `key` and the cipher, compression and
+ * encoding given as `RFC_CIPHER_*`, 0 or 1 and `RFC_ENCODING_*` constants.
+ *
+ * On success, `*output` and `*output_len` are set to the encrypted output, which must be
+ * released with `rfc_free`. On error, `*output` is set to null.
+ *
+ * # Safety
+ *
+ * `input` and `key` must point to `input_len` and `key_len` readable bytes, or may be
+ * null if their length is 0. `output` and `output_len` must be valid for writes.
+ */
+int rfc_encrypt_buf(const uint8_t *input,
+                    size_t input_len,
+                    const uint8_t *key,
+                    size_t key_len,
+                    uint32_t cipher,
+                    int compress,
+                    uint32_t encoding,
+                    uint8_t **output,
+                    size_t *output_len);
+
+/**
+ * Decrypts `input_len` bytes of `input` with `key` and the cipher, compression and
+ * encoding the input was encrypted with. `RFC_CIPHER_ANY` reads the cipher from the input,
+ * and `RFC_ENCODING_AUTO` detects the encoding.
+ *
+ * On success, `*output` and `*output_len` are set to the plaintext, which must be
+ * released with `rfc_free`. On error, `*output` is set to null.
+ *
+ * # Safety
+ *
+ * As for `rfc_encrypt_buf`.
+ */
+int rfc_decrypt_buf(const uint8_t *input,
+                    size_t input_len,
+                    const uint8_t *key,
+                    size_t key_len,
+                    uint32_t cipher,
+                    int compress,
+                    uint32_t encoding,
+                    uint8_t **output,
+                    size_t *output_len);
+
+/**
+ * Releases a buffer returned by `rfc_encrypt_buf` or `rfc_decrypt_buf`.
+ * Does nothing if `buf` is null.
+ *
+ * # Safety
+ *
+ * `buf` and `len` must have been returned together by rfc, and not released before.
+ */
+void rfc_free(uint8_t *buf, size_t len);
+
+/**
+ * Returns a static, NUL-terminated description of an error code.
+ */
+const char *rfc_strerror(int code);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* RFC_H */
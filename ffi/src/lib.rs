@@ -0,0 +1,234 @@
+//! C API for rfc, built as a `cdylib`. The header `include/rfc.h` is generated by
+//! cbindgen from this file, and a test fails when the committed copy is out of date.
+//!
+//! All functions return `RFC_OK` or an error code, which are the exit codes of the
+//! rfc CLI. Codes 11 and 12 are only used by the CLI, for errors about output files.
+//! Output buffers are allocated by rfc and must be released with `rfc_free`.
+//!
+//! With the `python` feature, the library is also the `rfc` Python extension module.
+use std::ffi::{c_char, c_int};
+use std::panic::{self, UnwindSafe};
+use std::ptr;
+use std::slice;
+
+use rfc::{Decryptor, Encoding, Encryptor, Mode, RfcError};
+
+#[cfg(feature = "python")]
+mod python;
+
+/// Success
+pub const RFC_OK: c_int = 0;
+/// Any error without a more specific code
+pub const RFC_ERR_OTHER: c_int = 1;
+/// A null pointer, or an unknown cipher or encoding constant was passed
+pub const RFC_ERR_INVALID_ARGUMENT: c_int = 2;
+/// The key or passphrase is wrong
+pub const RFC_ERR_WRONG_KEY: c_int = 3;
+/// The input is corrupted or has been tampered with
+pub const RFC_ERR_TAMPERED: c_int = 4;
+/// The input is truncated
+pub const RFC_ERR_TRUNCATED: c_int = 5;
+/// The input has an unsupported format version
+pub const RFC_ERR_UNSUPPORTED_VERSION: c_int = 6;
+/// The input was encrypted with an unknown cipher
+pub const RFC_ERR_UNKNOWN_CIPHER: c_int = 7;
+/// An I/O error
+pub const RFC_ERR_IO: c_int = 8;
+/// The input's signature is bad
+pub const RFC_ERR_BAD_SIGNATURE: c_int = 9;
+/// The input's key was derived with an unknown KDF
+pub const RFC_ERR_UNKNOWN_KDF: c_int = 10;
+/// The input was encrypted with another cipher than the one passed to decrypt it
+pub const RFC_ERR_CIPHER_MISMATCH: c_int = 13;
+
+/// Reads the cipher from the input, only valid for decryption
+pub const RFC_CIPHER_ANY: u32 = 0;
+/// AES-128, the ids of ciphers are those written to file headers
+pub const RFC_CIPHER_AES128: u32 = 1;
+/// AES-256
+pub const RFC_CIPHER_AES256: u32 = 2;
+
+pub const RFC_ENCODING_PLAIN: u32 = 0;
+pub const RFC_ENCODING_HEX: u32 = 1;
+pub const RFC_ENCODING_B64: u32 = 2;
+pub const RFC_ENCODING_B64_URL: u32 = 3;
+pub const RFC_ENCODING_B32: u32 = 4;
+pub const RFC_ENCODING_B58: u32 = 5;
+pub const RFC_ENCODING_Z85: u32 = 6;
+pub const RFC_ENCODING_ARMOR: u32 = 7;
+/// Detects the encoding of the input, only valid for decryption
+pub const RFC_ENCODING_AUTO: u32 = 8;
+
+/// Encrypts `input_len` bytes of `input` with `key` and the cipher, compression and
+/// encoding given as `RFC_CIPHER_*`, 0 or 1 and `RFC_ENCODING_*` constants.
+///
+/// On success, `*output` and `*output_len` are set to the encrypted output, which must be
+/// released with `rfc_free`. On error, `*output` is set to null.
+///
+/// # Safety
+///
+/// `input` and `key` must point to `input_len` and `key_len` readable bytes, or may be
+/// null if their length is 0. `output` and `output_len` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rfc_encrypt_buf(
+    input: *const u8,
+    input_len: usize,
+    key: *const u8,
+    key_len: usize,
+    cipher: u32,
+    compress: c_int,
+    encoding: u32,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+) -> c_int {
+    let (Some(input), Some(key)) = (bytes(input, input_len), bytes(key, key_len)) else {
+        return RFC_ERR_INVALID_ARGUMENT;
+    };
+    let (Some(mode), Some(encoding)) = (mode(cipher), encoding_from(encoding)) else {
+        return RFC_ERR_INVALID_ARGUMENT;
+    };
+
+    let encryptor = Encryptor::new()
+        .cipher(mode)
+        .compress(compress != 0)
+        .encoding(encoding)
+        .key(key);
+
+    run(output, output_len, || {
+        let mut ciphertext = Vec::new();
+        encryptor.encrypt_slice(input, &mut ciphertext)?;
+        Ok(ciphertext)
+    })
+}
+
+/// Decrypts `input_len` bytes of `input` with `key` and the cipher, compression and
+/// encoding the input was encrypted with. `RFC_CIPHER_ANY` reads the cipher from the input,
+/// and `RFC_ENCODING_AUTO` detects the encoding.
+///
+/// On success, `*output` and `*output_len` are set to the plaintext, which must be
+/// released with `rfc_free`. On error, `*output` is set to null.
+///
+/// # Safety
+///
+/// As for `rfc_encrypt_buf`.
+#[no_mangle]
+pub unsafe extern "C" fn rfc_decrypt_buf(
+    input: *const u8,
+    input_len: usize,
+    key: *const u8,
+    key_len: usize,
+    cipher: u32,
+    compress: c_int,
+    encoding: u32,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+) -> c_int {
+    let (Some(input), Some(key)) = (bytes(input, input_len), bytes(key, key_len)) else {
+        return RFC_ERR_INVALID_ARGUMENT;
+    };
+    let Some(encoding) = encoding_from(encoding) else {
+        return RFC_ERR_INVALID_ARGUMENT;
+    };
+
+    let mut decryptor = Decryptor::new()
+        .compress(compress != 0)
+        .encoding(encoding)
+        .key(key);
+    if cipher != RFC_CIPHER_ANY {
+        let Some(mode) = mode(cipher) else {
+            return RFC_ERR_INVALID_ARGUMENT;
+        };
+        decryptor = decryptor.cipher(mode);
+    }
+
+    run(output, output_len, || decryptor.decrypt_buf(input))
+}
+
+/// Releases a buffer returned by `rfc_encrypt_buf` or `rfc_decrypt_buf`.
+/// Does nothing if `buf` is null.
+///
+/// # Safety
+///
+/// `buf` and `len` must have been returned together by rfc, and not released before.
+#[no_mangle]
+pub unsafe extern "C" fn rfc_free(buf: *mut u8, len: usize) {
+    if !buf.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buf, len)));
+    }
+}
+
+/// Returns a static, NUL-terminated description of an error code.
+#[no_mangle]
+pub extern "C" fn rfc_strerror(code: c_int) -> *const c_char {
+    let msg: &'static [u8] = match code {
+        RFC_OK => b"success\0",
+        RFC_ERR_INVALID_ARGUMENT => b"invalid argument\0",
+        RFC_ERR_WRONG_KEY => b"wrong key or passphrase\0",
+        RFC_ERR_TAMPERED => b"input is corrupted or has been tampered with\0",
+        RFC_ERR_TRUNCATED => b"input is truncated\0",
+        RFC_ERR_UNSUPPORTED_VERSION => b"unsupported format version\0",
+        RFC_ERR_UNKNOWN_CIPHER => b"unknown cipher\0",
+        RFC_ERR_IO => b"io error\0",
+        RFC_ERR_BAD_SIGNATURE => b"bad signature\0",
+        RFC_ERR_UNKNOWN_KDF => b"unknown KDF\0",
+        RFC_ERR_CIPHER_MISMATCH => b"input was encrypted with another cipher\0",
+        _ => b"error\0",
+    };
+
+    msg.as_ptr().cast()
+}
+
+/// Runs `f`, and stores its output in `output` and `output_len`.
+/// Panics are caught, since unwinding into C is undefined behavior.
+unsafe fn run<F>(output: *mut *mut u8, output_len: *mut usize, f: F) -> c_int
+where
+    F: FnOnce() -> Result<Vec<u8>, RfcError> + UnwindSafe,
+{
+    if output.is_null() || output_len.is_null() {
+        return RFC_ERR_INVALID_ARGUMENT;
+    }
+    *output = ptr::null_mut();
+    *output_len = 0;
+
+    match panic::catch_unwind(f) {
+        Ok(Ok(bytes)) => {
+            let bytes = Box::into_raw(bytes.into_boxed_slice());
+            *output_len = bytes.len();
+            *output = bytes.cast();
+            RFC_OK
+        }
+        Ok(Err(err)) => c_int::from(err.code()),
+        Err(_) => RFC_ERR_OTHER,
+    }
+}
+
+/// Returns the slice at `ptr`, which may only be null if `len` is 0.
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (ptr.is_null(), len) {
+        (true, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(slice::from_raw_parts(ptr, len)),
+    }
+}
+
+/// Returns the mode of a cipher id, which may also be that of a cipher registered in Rust.
+fn mode(cipher: u32) -> Option<Mode> {
+    u8::try_from(cipher)
+        .ok()
+        .and_then(|id| Mode::from_id(id).ok())
+}
+
+fn encoding_from(encoding: u32) -> Option<Encoding> {
+    match encoding {
+        RFC_ENCODING_PLAIN => Some(Encoding::Plain),
+        RFC_ENCODING_HEX => Some(Encoding::Hex),
+        RFC_ENCODING_B64 => Some(Encoding::B64),
+        RFC_ENCODING_B64_URL => Some(Encoding::B64Url),
+        RFC_ENCODING_B32 => Some(Encoding::B32),
+        RFC_ENCODING_B58 => Some(Encoding::B58),
+        RFC_ENCODING_Z85 => Some(Encoding::Z85),
+        RFC_ENCODING_ARMOR => Some(Encoding::Armor),
+        RFC_ENCODING_AUTO => Some(Encoding::Auto),
+        _ => None,
+    }
+}
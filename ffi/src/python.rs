@@ -0,0 +1,421 @@
+/// Python bindings, enabled with the `python` feature and built as the `rfc` extension module.
+///
+/// `encrypt` and `decrypt` take and return `bytes`, and `DecryptReader` decrypts a binary
+/// file object. Ciphers and encodings are named as in the CLI. Errors are raised as
+/// subclasses of `rfc.Error`, and I/O errors as `OSError`.
+use std::io::{self, Cursor, Read};
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyOSError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
+
+use rfc::{Decryptor, Encoding, Encryptor, Mode, RfcError};
+
+create_exception!(rfc, Error, PyException, "Base class of rfc errors.");
+create_exception!(rfc, WrongKeyError, Error, "The key or passphrase is wrong.");
+create_exception!(
+    rfc,
+    TamperedError,
+    Error,
+    "The input is corrupted or has been tampered with."
+);
+create_exception!(rfc, TruncatedError, Error, "The input is truncated.");
+create_exception!(
+    rfc,
+    UnsupportedVersionError,
+    Error,
+    "The input has an unsupported format version."
+);
+create_exception!(
+    rfc,
+    UnknownCipherError,
+    Error,
+    "The input was encrypted with an unknown cipher."
+);
+create_exception!(
+    rfc,
+    UnknownKdfError,
+    Error,
+    "The input's key was derived with an unknown KDF."
+);
+create_exception!(
+    rfc,
+    CipherMismatchError,
+    Error,
+    "The input was encrypted with another cipher than the one given."
+);
+create_exception!(
+    rfc,
+    BadSignatureError,
+    Error,
+    "The input's signature is bad."
+);
+
+/// Encrypts `data` with `key`, which may be `bytes` or a `str` passphrase.
+#[pyfunction]
+#[pyo3(signature = (data, key, cipher = "aes256", compress = false, encoding = "plain"))]
+fn encrypt<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    key: &Bound<'py, PyAny>,
+    cipher: &str,
+    compress: bool,
+    encoding: &str,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let encryptor = Encryptor::new()
+        .cipher(mode(cipher)?)
+        .compress(compress)
+        .encoding(encoding_from(encoding)?)
+        .key(key_bytes(key)?);
+
+    let ciphertext = py
+        .allow_threads(|| {
+            let mut ciphertext = Vec::new();
+            encryptor
+                .encrypt_slice(data, &mut ciphertext)
+                .map(|_| ciphertext)
+        })
+        .map_err(to_py_err)?;
+
+    Ok(PyBytes::new(py, &ciphertext))
+}
+
+/// Decrypts `data` with `key`, detecting its encoding by default. The cipher is read
+/// from `data`, and only checked against `cipher` if given.
+#[pyfunction]
+#[pyo3(signature = (data, key, cipher = None, compress = false, encoding = "auto"))]
+fn decrypt<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    key: &Bound<'py, PyAny>,
+    cipher: Option<&str>,
+    compress: bool,
+    encoding: &str,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let decryptor = decryptor(key, cipher, compress, encoding)?;
+
+    let plaintext = py
+        .allow_threads(|| decryptor.decrypt_buf(data))
+        .map_err(to_py_err)?;
+
+    Ok(PyBytes::new(py, &plaintext))
+}
+
+/// DecryptReader(file, key, cipher=None, compress=False, encoding="auto")
+///
+/// Reads the plaintext of the rfc file read from the binary file object `file`.
+/// The whole file is read and authenticated by the first read.
+#[pyclass(name = "DecryptReader", module = "rfc")]
+struct PyDecryptReader {
+    decryptor: Decryptor,
+    /// The file, until it is decrypted by the first read
+    file: Option<PyFile>,
+    plaintext: Cursor<Vec<u8>>,
+}
+
+#[pymethods]
+impl PyDecryptReader {
+    #[new]
+    #[pyo3(signature = (file, key, cipher = None, compress = false, encoding = "auto"))]
+    fn new(
+        file: PyObject,
+        key: &Bound<'_, PyAny>,
+        cipher: Option<&str>,
+        compress: bool,
+        encoding: &str,
+    ) -> PyResult<Self> {
+        Ok(Self {
+            decryptor: decryptor(key, cipher, compress, encoding)?,
+            file: Some(PyFile(file)),
+            plaintext: Cursor::new(Vec::new()),
+        })
+    }
+
+    /// Reads at most `size` bytes of plaintext, or all remaining plaintext if `size` is negative.
+    #[pyo3(signature = (size = -1))]
+    fn read<'py>(&mut self, py: Python<'py>, size: i64) -> PyResult<Bound<'py, PyBytes>> {
+        if let Some(file) = self.file.take() {
+            let plaintext = self.decryptor.decrypt_buf(file).map_err(to_py_err)?;
+            self.plaintext = Cursor::new(plaintext);
+        }
+
+        let mut plaintext = Vec::new();
+        let read = match u64::try_from(size) {
+            Ok(size) => (&mut self.plaintext).take(size).read_to_end(&mut plaintext),
+            Err(_) => self.plaintext.read_to_end(&mut plaintext),
+        };
+        read.expect("reads from memory do not fail");
+
+        Ok(PyBytes::new(py, &plaintext))
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __exit__(&mut self, _exc_type: PyObject, _exc: PyObject, _traceback: PyObject) {}
+}
+
+/// PyFile reads from a Python binary file object by calling its `read` method.
+struct PyFile(PyObject);
+
+impl Read for PyFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Python::with_gil(|py| {
+            let bytes = self
+                .0
+                .call_method1(py, "read", (buf.len(),))
+                .map_err(io::Error::other)?;
+            let bytes = bytes
+                .downcast_bound::<PyBytes>(py)
+                .map_err(|_| {
+                    io::Error::other(PyTypeError::new_err("file must be opened in binary mode"))
+                })?
+                .as_bytes();
+
+            if bytes.len() > buf.len() {
+                return Err(io::Error::other(PyValueError::new_err(
+                    "file read returned more bytes than requested",
+                )));
+            }
+            buf[..bytes.len()].copy_from_slice(bytes);
+
+            Ok(bytes.len())
+        })
+    }
+}
+
+fn decryptor(
+    key: &Bound<'_, PyAny>,
+    cipher: Option<&str>,
+    compress: bool,
+    encoding: &str,
+) -> PyResult<Decryptor> {
+    let decryptor = Decryptor::new()
+        .compress(compress)
+        .encoding(encoding_from(encoding)?)
+        .key(key_bytes(key)?);
+
+    match cipher {
+        Some(cipher) => Ok(decryptor.cipher(mode(cipher)?)),
+        None => Ok(decryptor),
+    }
+}
+
+fn key_bytes(key: &Bound<'_, PyAny>) -> PyResult<Vec<u8>> {
+    if let Ok(key) = key.downcast::<PyBytes>() {
+        return Ok(key.as_bytes().to_vec());
+    }
+    if let Ok(key) = key.downcast::<PyString>() {
+        return Ok(key.to_str()?.as_bytes().to_vec());
+    }
+
+    Err(PyTypeError::new_err("key must be bytes or str"))
+}
+
+fn mode(cipher: &str) -> PyResult<Mode> {
+    Mode::from_name(cipher)
+        .map_err(|_| PyValueError::new_err(format!("unknown cipher {:?}", cipher)))
+}
+
+fn encoding_from(encoding: &str) -> PyResult<Encoding> {
+    match encoding {
+        "plain" => Ok(Encoding::Plain),
+        "hex" => Ok(Encoding::Hex),
+        "b64" => Ok(Encoding::B64),
+        "b64-url" => Ok(Encoding::B64Url),
+        "b32" => Ok(Encoding::B32),
+        "b58" => Ok(Encoding::B58),
+        "z85" => Ok(Encoding::Z85),
+        "armor" => Ok(Encoding::Armor),
+        "auto" => Ok(Encoding::Auto),
+        _ => Err(PyValueError::new_err(format!(
+            "unknown encoding {:?}",
+            encoding
+        ))),
+    }
+}
+
+/// Maps an RfcError to the matching Python exception. Python exceptions raised by
+/// file objects pass through unchanged.
+fn to_py_err(err: RfcError) -> PyErr {
+    let msg = err.to_string();
+
+    match err {
+        RfcError::WrongKey => WrongKeyError::new_err(msg),
+        RfcError::Tampered(_) => TamperedError::new_err(msg),
+        RfcError::Truncated(_) => TruncatedError::new_err(msg),
+        RfcError::UnsupportedVersion(_) => UnsupportedVersionError::new_err(msg),
+        RfcError::UnknownCipher(_) => UnknownCipherError::new_err(msg),
+        RfcError::UnknownKdf(_) => UnknownKdfError::new_err(msg),
+        RfcError::CipherMismatch(..) => CipherMismatchError::new_err(msg),
+        RfcError::BadSignature(_) => BadSignatureError::new_err(msg),
+        RfcError::IoError(err) if err.get_ref().is_some_and(|inner| inner.is::<PyErr>()) => *err
+            .into_inner()
+            .expect("no inner error")
+            .downcast::<PyErr>()
+            .expect("inner error is not PyErr"),
+        RfcError::IoError(_) => PyOSError::new_err(msg),
+        _ => Error::new_err(msg),
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "rfc")]
+fn rfc_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+
+    m.add_function(wrap_pyfunction!(encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(decrypt, m)?)?;
+    m.add_class::<PyDecryptReader>()?;
+
+    m.add("Error", py.get_type::<Error>())?;
+    m.add("WrongKeyError", py.get_type::<WrongKeyError>())?;
+    m.add("TamperedError", py.get_type::<TamperedError>())?;
+    m.add("TruncatedError", py.get_type::<TruncatedError>())?;
+    m.add(
+        "UnsupportedVersionError",
+        py.get_type::<UnsupportedVersionError>(),
+    )?;
+    m.add("UnknownCipherError", py.get_type::<UnknownCipherError>())?;
+    m.add("UnknownKdfError", py.get_type::<UnknownKdfError>())?;
+    m.add("CipherMismatchError", py.get_type::<CipherMismatchError>())?;
+    m.add("BadSignatureError", py.get_type::<BadSignatureError>())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rfc_module;
+
+    use std::ffi::CString;
+
+    use pyo3::prelude::*;
+    use pyo3::types::{PyDict, PyModule};
+
+    /// Runs `code` with the module imported as `rfc`.
+    fn run_python(code: &str) {
+        pyo3::prepare_freethreaded_python();
+
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "rfc").unwrap();
+            rfc_module(&module).unwrap();
+
+            let globals = PyDict::new(py);
+            globals.set_item("rfc", module).unwrap();
+
+            if let Err(err) = py.run(&CString::new(code).unwrap(), Some(&globals), None) {
+                err.display(py);
+                panic!("python test failed: {}", err);
+            }
+        });
+    }
+
+    #[test]
+    fn test_python_encrypt_decrypt() {
+        run_python(
+            r#"
+plaintext = bytes(range(256)) * 100
+
+for encoding in ["plain", "hex", "b64", "b64-url", "b32", "z85", "armor"]:
+    ciphertext = rfc.encrypt(plaintext, b"key", encoding=encoding)
+    assert isinstance(ciphertext, bytes)
+    assert rfc.decrypt(ciphertext, b"key") == plaintext
+    assert rfc.decrypt(ciphertext, b"key", encoding=encoding) == plaintext
+
+ciphertext = rfc.encrypt(plaintext, "passphrase", cipher="aes128", compress=True)
+assert len(ciphertext) < len(plaintext)
+assert rfc.decrypt(ciphertext, "passphrase", cipher="aes128", compress=True) == plaintext
+assert rfc.decrypt(ciphertext, "passphrase", compress=True) == plaintext
+assert rfc.decrypt(rfc.encrypt(b"", "key"), "key") == b""
+"#,
+        );
+    }
+
+    #[test]
+    fn test_python_decrypt_reader() {
+        run_python(
+            r#"
+import io
+
+plaintext = b"hello, world\n" * 1000
+ciphertext = rfc.encrypt(plaintext, b"key", encoding="b64")
+
+with rfc.DecryptReader(io.BytesIO(ciphertext), b"key") as reader:
+    assert reader.readable()
+    assert reader.read(5) == b"hello"
+    assert reader.read(8) == b", world\n"
+    assert reader.read() == plaintext[13:]
+    assert reader.read() == b""
+
+reader = rfc.DecryptReader(io.BytesIO(ciphertext), b"wrong key")
+try:
+    reader.read()
+    raise AssertionError("wrong key not detected")
+except rfc.WrongKeyError:
+    pass
+
+# Exceptions raised by the file object pass through
+class Failing(io.RawIOBase):
+    def read(self, size=-1):
+        raise KeyError("from file")
+
+try:
+    rfc.DecryptReader(Failing(), b"key").read()
+    raise AssertionError("file error not raised")
+except KeyError as err:
+    assert "from file" in str(err)
+
+try:
+    rfc.DecryptReader(io.StringIO("text"), b"key").read()
+    raise AssertionError("text file not rejected")
+except TypeError:
+    pass
+"#,
+        );
+    }
+
+    #[test]
+    fn test_python_errors() {
+        run_python(
+            r#"
+ciphertext = rfc.encrypt(b"hello, world", b"key")
+
+def raises(exception, f, *args, **kwargs):
+    try:
+        f(*args, **kwargs)
+    except exception as err:
+        assert isinstance(err, Exception)
+        return err
+    raise AssertionError(f"{exception.__name__} not raised")
+
+err = raises(rfc.WrongKeyError, rfc.decrypt, ciphertext, b"wrong key")
+assert isinstance(err, rfc.Error)
+assert "wrong key" in str(err)
+
+raises(rfc.TruncatedError, rfc.decrypt, ciphertext[:len(ciphertext) // 2], b"key")
+
+tampered = bytearray(ciphertext)
+tampered[len(tampered) // 2] ^= 1
+raises(rfc.TamperedError, rfc.decrypt, bytes(tampered), b"key")
+
+raises(rfc.Error, rfc.decrypt, b"not an rfc file", b"key")
+raises(rfc.CipherMismatchError, rfc.decrypt, ciphertext, b"key", cipher="aes128")
+raises(rfc.Error, rfc.encrypt, b"hello", b"key", encoding="auto")
+raises(ValueError, rfc.encrypt, b"hello", b"key", cipher="des")
+raises(ValueError, rfc.encrypt, b"hello", b"key", encoding="rot13")
+raises(TypeError, rfc.encrypt, b"hello", 42)
+assert issubclass(rfc.UnsupportedVersionError, rfc.Error)
+assert issubclass(rfc.UnknownCipherError, rfc.Error)
+assert issubclass(rfc.UnknownKdfError, rfc.Error)
+assert issubclass(rfc.BadSignatureError, rfc.Error)
+"#,
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! Checks the committed header against the generated one, and compiles `tests/harness.c`
+//! against it and the rfc cdylib, and runs it.
+
+use std::fs;
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::{env, process::Command};
+
+#[test]
+fn test_header_up_to_date() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let generated = env!("RFC_GENERATED_HEADER");
+
+    let committed = fs::read_to_string(manifest_dir.join("include/rfc.h")).unwrap();
+    assert!(
+        committed == fs::read_to_string(generated).unwrap(),
+        "include/rfc.h is out of date, copy {} over it",
+        generated
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_c_harness() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // Integration tests are built in target/<profile>/deps, next to which the cdylib is
+    let lib_dir = env::current_exe()
+        .unwrap()
+        .parent()
+        .and_then(|deps| deps.parent())
+        .unwrap()
+        .to_path_buf();
+    let harness = lib_dir.join("rfc_c_harness");
+
+    let cc = env::var("CC").unwrap_or_else(|_| String::from("cc"));
+    let status = Command::new(cc)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/harness.c"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lrfc_ffi")
+        .arg("-o")
+        .arg(&harness)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "failed to compile the C harness");
+
+    let output = Command::new(&harness).output().unwrap();
+    assert!(
+        output.status.success(),
+        "C harness failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(output.stdout, b"ok\n");
+}
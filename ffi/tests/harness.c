@@ -0,0 +1,119 @@
+/* Round-trips data through the rfc C API, run by tests/c_api.rs. */
+#include <stdio.h>
+#include <string.h>
+
+#include "rfc.h"
+
+#define CHECK(cond)                                                        \
+    do {                                                                   \
+        if (!(cond)) {                                                     \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,        \
+                    __LINE__, #cond);                                      \
+            return 1;                                                      \
+        }                                                                  \
+    } while (0)
+
+static const char KEY[] = "my key";
+
+static int round_trip(uint32_t cipher, int compress, uint32_t encoding,
+                      const uint8_t *plaintext, size_t plaintext_len) {
+    uint8_t *ciphertext, *decrypted;
+    size_t ciphertext_len, decrypted_len;
+
+    CHECK(rfc_encrypt_buf(plaintext, plaintext_len, (const uint8_t *)KEY,
+                          strlen(KEY), cipher, compress, encoding,
+                          &ciphertext, &ciphertext_len) == RFC_OK);
+    CHECK(ciphertext != NULL);
+
+    CHECK(rfc_decrypt_buf(ciphertext, ciphertext_len, (const uint8_t *)KEY,
+                          strlen(KEY), cipher, compress, RFC_ENCODING_AUTO,
+                          &decrypted, &decrypted_len) == RFC_OK);
+    CHECK(decrypted_len == plaintext_len);
+    CHECK(plaintext_len == 0 || memcmp(decrypted, plaintext, plaintext_len) == 0);
+
+    rfc_free(ciphertext, ciphertext_len);
+    rfc_free(decrypted, decrypted_len);
+    return 0;
+}
+
+static int errors(void) {
+    const uint8_t plaintext[] = "hello, world";
+    uint8_t *ciphertext, *decrypted;
+    size_t ciphertext_len, decrypted_len;
+    int code;
+
+    CHECK(rfc_encrypt_buf(plaintext, sizeof(plaintext), (const uint8_t *)KEY,
+                          strlen(KEY), RFC_CIPHER_AES256, 0, RFC_ENCODING_PLAIN,
+                          &ciphertext, &ciphertext_len) == RFC_OK);
+
+    code = rfc_decrypt_buf(ciphertext, ciphertext_len, (const uint8_t *)"wrong", 5,
+                           RFC_CIPHER_AES256, 0, RFC_ENCODING_PLAIN, &decrypted,
+                           &decrypted_len);
+    CHECK(code == RFC_ERR_WRONG_KEY);
+    CHECK(decrypted == NULL);
+    CHECK(strcmp(rfc_strerror(code), "wrong key or passphrase") == 0);
+
+    code = rfc_decrypt_buf(ciphertext, ciphertext_len / 2, (const uint8_t *)KEY,
+                           strlen(KEY), RFC_CIPHER_AES256, 0, RFC_ENCODING_PLAIN,
+                           &decrypted, &decrypted_len);
+    CHECK(code == RFC_ERR_TRUNCATED);
+
+    ciphertext[ciphertext_len / 2] ^= 1;
+    code = rfc_decrypt_buf(ciphertext, ciphertext_len, (const uint8_t *)KEY,
+                           strlen(KEY), RFC_CIPHER_AES256, 0, RFC_ENCODING_PLAIN,
+                           &decrypted, &decrypted_len);
+    CHECK(code == RFC_ERR_TAMPERED);
+    ciphertext[ciphertext_len / 2] ^= 1;
+
+    code = rfc_decrypt_buf(ciphertext, ciphertext_len, (const uint8_t *)KEY,
+                           strlen(KEY), RFC_CIPHER_AES128, 0, RFC_ENCODING_PLAIN,
+                           &decrypted, &decrypted_len);
+    CHECK(code == RFC_ERR_CIPHER_MISMATCH);
+
+    CHECK(rfc_decrypt_buf(ciphertext, ciphertext_len, (const uint8_t *)KEY,
+                          strlen(KEY), RFC_CIPHER_ANY, 0, RFC_ENCODING_PLAIN,
+                          &decrypted, &decrypted_len) == RFC_OK);
+    CHECK(decrypted_len == sizeof(plaintext));
+    rfc_free(decrypted, decrypted_len);
+    rfc_free(ciphertext, ciphertext_len);
+
+    CHECK(rfc_encrypt_buf(plaintext, sizeof(plaintext), (const uint8_t *)KEY,
+                          strlen(KEY), 42, 0, RFC_ENCODING_PLAIN, &ciphertext,
+                          &ciphertext_len) == RFC_ERR_INVALID_ARGUMENT);
+    CHECK(rfc_encrypt_buf(plaintext, sizeof(plaintext), (const uint8_t *)KEY,
+                          strlen(KEY), RFC_CIPHER_AES256, 0, RFC_ENCODING_AUTO,
+                          &ciphertext, &ciphertext_len) != RFC_OK);
+    CHECK(rfc_encrypt_buf(NULL, 1, (const uint8_t *)KEY, strlen(KEY),
+                          RFC_CIPHER_AES256, 0, RFC_ENCODING_PLAIN, &ciphertext,
+                          &ciphertext_len) == RFC_ERR_INVALID_ARGUMENT);
+    CHECK(rfc_encrypt_buf(plaintext, sizeof(plaintext), (const uint8_t *)KEY,
+                          strlen(KEY), RFC_CIPHER_AES256, 0, RFC_ENCODING_PLAIN,
+                          NULL, &ciphertext_len) == RFC_ERR_INVALID_ARGUMENT);
+
+    rfc_free(NULL, 0);
+    return 0;
+}
+
+int main(void) {
+    static uint8_t plaintext[100000];
+    const uint32_t encodings[] = {RFC_ENCODING_PLAIN, RFC_ENCODING_HEX,
+                                  RFC_ENCODING_B64, RFC_ENCODING_B64_URL,
+                                  RFC_ENCODING_B32, RFC_ENCODING_Z85,
+                                  RFC_ENCODING_ARMOR};
+    size_t i;
+
+    for (i = 0; i < sizeof(plaintext); i++) {
+        plaintext[i] = (uint8_t)(i * 7 + i / 256);
+    }
+
+    for (i = 0; i < sizeof(encodings) / sizeof(encodings[0]); i++) {
+        CHECK(round_trip(RFC_CIPHER_AES256, 0, encodings[i], plaintext,
+                         sizeof(plaintext)) == 0);
+    }
+    CHECK(round_trip(RFC_CIPHER_AES128, 1, RFC_ENCODING_B58, plaintext, 1000) == 0);
+    CHECK(round_trip(RFC_CIPHER_AES128, 1, RFC_ENCODING_PLAIN, NULL, 0) == 0);
+    CHECK(errors() == 0);
+
+    printf("ok\n");
+    return 0;
+}
@@ -1,29 +1,95 @@
-use clap::{Parser, ValueEnum};
+use clap::builder::{PossibleValue, PossibleValuesParser, TypedValueParser};
+use clap::{Parser, Subcommand, ValueEnum};
 
-use crate::rfc::{encoding::Encoding, Mode};
+use std::ffi::OsStr;
+use std::num::NonZeroUsize;
+
+use rfc::{
+    buf::OverwritePolicy, cipher, encoding::Encoding, gfc::GfcCipher, openssl, padding::Padding,
+    pbkdf2::Pbkdf2Digest, Mode,
+};
+
+const EXIT_CODES: &str = "Exit codes:
+  0  success
+  1  other errors
+  2  usage error
+  3  wrong key or passphrase
+  4  input is corrupted or has been tampered with
+  5  input is truncated
+  6  unsupported format version
+  7  unknown cipher
+  8  I/O error
+  9  bad signature
+ 10  unknown KDF
+ 11  output file already exists
+ 12  input and output are the same file
+ 13  input was encrypted with another cipher than --cipher";
 
 #[derive(Debug, Parser)]
 #[clap(
     author = "github.com/artnoi43",
     version,
-    about = "A simple, stupid Rust clone of gfc - a file encryption utility"
+    about = "A simple, stupid Rust clone of gfc - a file encryption utility",
+    after_help = EXIT_CODES,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Input file
-    #[arg(value_parser = validate_filename)]
-    pub filename: Filename,
+    #[arg(value_parser = validate_filename, required = true)]
+    pub filename: Option<Filename>,
 
-    /// Encryption cipher/mode to use
-    #[arg(short, long, default_value_t = Cipher::Aes256)]
-    pub cipher: Cipher,
+    /// Cipher to encrypt with, or that input to decrypt must have been encrypted with [default: aes256 when encrypting]
+    #[arg(short, long, value_parser = CipherParser)]
+    pub cipher: Option<Mode>,
 
     #[arg(short, long, default_value_t = false)]
     /// Decrypt file
     pub decrypt: bool,
 
-    #[arg(short, long, value_parser = validate_filename)]
+    #[arg(long, default_value_t = Format::Rfc)]
+    /// File format to encrypt to or decrypt from
+    pub format: Format,
+
+    #[arg(long, default_value_t = GfcCipher::AesGcm)]
+    /// Cipher of the gfc format, only used with --format gfc
+    pub gfc_cipher: GfcCipher,
+
+    #[arg(long, default_value_t = Pbkdf2Digest::Sha256)]
+    /// PBKDF2 digest of the openssl format, like openssl enc -md, only used with --format openssl
+    pub openssl_digest: Pbkdf2Digest,
+
+    #[arg(long, default_value_t = openssl::DEFAULT_ITERATIONS)]
+    /// PBKDF2 iterations of the openssl format, like openssl enc -iter, only used with --format openssl
+    pub openssl_iter: u32,
+
+    #[arg(long, conflicts_with = "decrypt")]
+    /// Encrypt to this age1... X25519 recipient instead of a passphrase, only used with --format age
+    pub recipient: Vec<String>,
+
+    #[arg(long, value_parser = validate_filename, requires = "decrypt")]
+    /// Decrypt with the age identities in this file instead of a passphrase, only used with --format age
+    pub identity: Vec<Filename>,
+
+    #[arg(
+        short,
+        long,
+        value_parser = validate_filename,
+        required_unless_present_any = ["restore_name", "inspect"]
+    )]
     /// Output file
-    pub outfile: Filename,
+    pub outfile: Option<Filename>,
+
+    #[arg(long, default_value_t = false)]
+    /// Overwrite output file if it already exists
+    pub force: bool,
+
+    #[arg(short = 'n', long, default_value_t = false, conflicts_with = "force")]
+    /// Do not overwrite an existing output file, and exit without error
+    pub no_clobber: bool,
 
     #[arg(short, long = "key", default_value_t = KeyType::Passphrase)]
     /// Encryption key type - can be either passphrase or key file
@@ -37,29 +103,193 @@ pub struct Args {
     /// Compress using LZ4
     pub compress: bool,
 
-    #[arg(short, long, default_value_t = Encoding::Plain)]
-    /// Encoding to decode input
-    pub encoding: Encoding,
+    #[arg(short, long)]
+    /// Encoding of encrypted output, or of input to decrypt [default: plain, or auto when decrypting]
+    ///
+    /// auto detects any encoding with --format rfc, hex or base64 with gfc,
+    /// base64 with openssl and armor with age.
+    pub encoding: Option<Encoding>,
+
+    #[arg(short, long, default_value_t = Padding::None, conflicts_with = "decrypt")]
+    /// Padding to hide plaintext length
+    pub pad: Padding,
+
+    #[arg(short, long, default_value_t = false, conflicts_with = "decrypt")]
+    /// Embed original filename, size, mode bits and mtime as encrypted metadata
+    pub metadata: bool,
+
+    #[arg(long, conflicts_with = "decrypt")]
+    /// Comment to embed with the encrypted metadata, implies --metadata
+    pub comment: Option<String>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "decrypt",
+        conflicts_with = "outfile"
+    )]
+    /// Write decrypted output to the original filename from the encrypted metadata
+    pub restore_name: bool,
+
+    #[arg(long, default_value_t = false, conflicts_with_all = ["outfile", "restore_name"])]
+    /// Decrypt and print the encrypted metadata without writing any output
+    pub inspect: bool,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["decrypt", "compress", "encoding", "pad", "metadata", "comment"]
+    )]
+    /// Encrypt to the seekable format, which allows decrypting byte ranges with --range
+    pub seekable: bool,
+
+    #[arg(short, long)]
+    /// Number of worker threads for encryption and compression, defaults to one per CPU core
+    pub threads: Option<NonZeroUsize>,
+
+    #[arg(long, default_value_t = false)]
+    /// Memory-map regular input files instead of reading them into memory, pipes are still streamed
+    pub mmap: bool,
+
+    #[arg(short, long, value_parser = parse_range, requires = "decrypt")]
+    /// Decrypt only plaintext bytes START:END of a seekable file, e.g. 100:200, 100: or :200
+    pub range: Option<ByteRange>,
+
+    #[arg(long, value_parser = validate_filename, conflicts_with_all = ["decrypt", "seekable"])]
+    /// Sign the encrypted output with the Ed25519 secret key from this file
+    pub sign: Option<Filename>,
+
+    #[arg(long, value_parser = validate_filename, requires = "decrypt", conflicts_with = "range")]
+    /// Verify the input's embedded signature with the Ed25519 public key from this file
+    pub verify_with: Option<Filename>,
 }
 
-#[derive(Clone, Debug, ValueEnum)]
-pub enum Cipher {
-    Aes128,
-    Aes256,
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Generate an Ed25519 key pair for signing, written to PREFIX.key and PREFIX.pub
+    Keygen {
+        /// Filename prefix of the key files
+        #[arg(value_parser = validate_filename)]
+        prefix: Filename,
+
+        /// Overwrite existing key files
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+
+    /// Write a detached Ed25519 signature of a file
+    Sign {
+        /// File to sign
+        #[arg(value_parser = validate_filename)]
+        filename: Filename,
+
+        /// Ed25519 secret key file
+        #[arg(short, long, value_parser = validate_filename)]
+        key: Filename,
+
+        /// Signature file, defaults to FILENAME.sig
+        #[arg(short, long, value_parser = validate_filename)]
+        output: Option<Filename>,
+
+        /// Overwrite an existing signature file
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+
+    /// Encode and optionally compress a file, without encryption
+    Encode(CodecArgs),
+
+    /// Decode and optionally decompress a file written by rfc encode
+    Decode(CodecArgs),
+
+    /// Verify a detached Ed25519 signature of a file
+    VerifySig {
+        /// File to verify
+        #[arg(value_parser = validate_filename)]
+        filename: Filename,
+
+        /// Ed25519 public key file
+        #[arg(short, long, value_parser = validate_filename)]
+        key: Filename,
+
+        /// Signature file, defaults to FILENAME.sig
+        #[arg(short, long, value_parser = validate_filename)]
+        signature: Option<Filename>,
+    },
 }
-impl std::fmt::Display for Cipher {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Aes128 => write!(f, "aes128"),
-            Self::Aes256 => write!(f, "aes256"),
-        }
+
+/// Arguments of the encode and decode subcommands
+#[derive(Debug, clap::Args)]
+pub struct CodecArgs {
+    /// Input file
+    #[arg(value_parser = validate_filename)]
+    pub filename: Filename,
+
+    /// Output file
+    #[arg(short, long, value_parser = validate_filename)]
+    pub outfile: Filename,
+
+    /// Encoding of the encoded file
+    #[arg(short, long, default_value_t = Encoding::B64)]
+    pub encoding: Encoding,
+
+    /// Compress using LZ4 before encoding
+    #[arg(short = 'z', long, default_value_t = false)]
+    pub compress: bool,
+
+    /// Overwrite output file if it already exists
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+}
+
+/// Parses --cipher as the name of a registered cipher, which are listed as its values.
+#[derive(Clone)]
+struct CipherParser;
+
+impl TypedValueParser for CipherParser {
+    type Value = Mode;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> Result<Mode, clap::Error> {
+        let names = self.possible_values().expect("no registered ciphers");
+        let name = PossibleValuesParser::new(names).parse_ref(cmd, arg, value)?;
+
+        Ok(Mode::from_name(&name).expect("cipher was unregistered"))
     }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        let ciphers = cipher::registered();
+        Some(Box::new(
+            ciphers
+                .into_iter()
+                .map(|cipher| PossibleValue::new(cipher.name())),
+        ))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Format {
+    /// rfc's own format
+    Rfc,
+    /// The format of gfc, the Go utility rfc is a clone of
+    Gfc,
+    /// The age format, age-encryption.org/v1
+    Age,
+    /// Files of openssl enc -pbkdf2 -salt, in AES-CBC mode
+    Openssl,
 }
-impl Cipher {
-    pub fn rfc_mode(&self) -> Mode {
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Aes128 => Mode::Aes128,
-            Self::Aes256 => Mode::Aes256,
+            Self::Rfc => write!(f, "rfc"),
+            Self::Gfc => write!(f, "gfc"),
+            Self::Age => write!(f, "age"),
+            Self::Openssl => write!(f, "openssl"),
         }
     }
 }
@@ -78,6 +308,58 @@ impl std::fmt::Display for KeyType {
     }
 }
 
+impl Args {
+    /// Returns the --cipher value to encrypt with, defaulting to AES-256.
+    pub fn cipher(&self) -> Mode {
+        self.cipher.unwrap_or(Mode::AES256)
+    }
+
+    /// Returns the --encoding value, detecting the encoding of input to decrypt by default.
+    pub fn encoding(&self) -> Encoding {
+        match self.encoding {
+            Some(encoding) => encoding,
+            None if self.decrypt || self.inspect => Encoding::Auto,
+            None => Encoding::Plain,
+        }
+    }
+
+    /// Returns the first option given that the --format does not support, if any.
+    pub fn unsupported_option(&self) -> Option<&'static str> {
+        let foreign = self.format != Format::Rfc;
+        let not_age = self.format != Format::Age;
+
+        let options = [
+            ("--compress", foreign && self.compress),
+            ("--pad", foreign && self.pad != Padding::None),
+            (
+                "--metadata",
+                foreign && (self.metadata || self.comment.is_some()),
+            ),
+            ("--restore-name", foreign && self.restore_name),
+            ("--inspect", foreign && self.inspect),
+            ("--seekable", foreign && self.seekable),
+            ("--range", foreign && self.range.is_some()),
+            ("--sign", foreign && self.sign.is_some()),
+            ("--verify-with", foreign && self.verify_with.is_some()),
+            ("--recipient", not_age && !self.recipient.is_empty()),
+            ("--identity", not_age && !self.identity.is_empty()),
+        ];
+
+        options
+            .into_iter()
+            .find(|(_, given)| *given)
+            .map(|(option, _)| option)
+    }
+
+    pub fn overwrite_policy(&self) -> OverwritePolicy {
+        match (self.force, self.no_clobber) {
+            (true, _) => OverwritePolicy::Force,
+            (_, true) => OverwritePolicy::NoClobber,
+            _ => OverwritePolicy::Refuse,
+        }
+    }
+}
+
 type Filename = String;
 fn validate_filename(name: &str) -> Result<Filename, String> {
     if name.is_empty() {
@@ -87,8 +369,218 @@ fn validate_filename(name: &str) -> Result<Filename, String> {
     Ok(name.to_string())
 }
 
+/// Half-open byte range `start..end`, with `end` defaulting to the end of input
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: Option<u64>,
+}
+
+fn parse_range(s: &str) -> Result<ByteRange, String> {
+    let (start, end) = s
+        .split_once(':')
+        .ok_or(String::from("range must be in the form START:END"))?;
+
+    let parse = |n: &str| {
+        n.parse::<u64>()
+            .map_err(|err| format!("bad offset {}: {}", n, err))
+    };
+    let range = ByteRange {
+        start: match start {
+            "" => 0,
+            start => parse(start)?,
+        },
+        end: match end {
+            "" => None,
+            end => Some(parse(end)?),
+        },
+    };
+
+    match range.end {
+        Some(end) if end < range.start => Err(String::from("range end is before start")),
+        _ => Ok(range),
+    }
+}
+
+#[test]
+fn test_parse_range() {
+    let range = |start, end| Ok(ByteRange { start, end });
+
+    assert_eq!(parse_range("100:200"), range(100, Some(200)));
+    assert_eq!(parse_range("100:"), range(100, None));
+    assert_eq!(parse_range(":200"), range(0, Some(200)));
+    assert_eq!(parse_range(":"), range(0, None));
+    assert!(parse_range("200:100").is_err());
+    assert!(parse_range("100").is_err());
+    assert!(parse_range("a:b").is_err());
+}
+
 #[test]
 fn test_validate_filename() {
     assert!(validate_filename("").is_err());
     assert!(validate_filename("foo").is_ok());
 }
+
+#[test]
+fn test_overwrite_policy() {
+    let parse = |extra: &[&str]| {
+        let mut argv = vec!["rfc", "infile", "-o", "outfile"];
+        argv.extend_from_slice(extra);
+        Args::try_parse_from(argv).map(|args| args.overwrite_policy())
+    };
+
+    assert_eq!(parse(&[]).unwrap(), OverwritePolicy::Refuse);
+    assert_eq!(parse(&["--force"]).unwrap(), OverwritePolicy::Force);
+    assert_eq!(
+        parse(&["--no-clobber"]).unwrap(),
+        OverwritePolicy::NoClobber
+    );
+    assert!(parse(&["--force", "-n"]).is_err());
+}
+
+#[test]
+fn test_outfile_required() {
+    let parse = |argv: &[&str]| Args::try_parse_from(argv);
+
+    assert!(parse(&["rfc", "infile"]).is_err());
+    assert!(parse(&["rfc", "infile", "-d", "--restore-name"]).is_ok());
+    assert!(parse(&["rfc", "infile", "--restore-name"]).is_err());
+    assert!(parse(&["rfc", "infile", "--inspect"]).is_ok());
+    assert!(parse(&["rfc", "infile", "-o", "outfile", "--inspect"]).is_err());
+}
+
+#[test]
+fn test_cipher() {
+    let parse = |cipher: &str| Args::try_parse_from(["rfc", "in", "-o", "out", "-c", cipher]);
+
+    assert_eq!(parse("aes128").unwrap().cipher, Some(Mode::AES128));
+    assert_eq!(parse("aes256").unwrap().cipher(), Mode::AES256);
+    assert!(parse("des").is_err());
+
+    let args = Args::try_parse_from(["rfc", "in", "-o", "out"]).unwrap();
+    assert_eq!(args.cipher, None);
+    assert_eq!(args.cipher(), Mode::AES256);
+}
+
+#[test]
+fn test_format() {
+    let parse = |extra: &[&str]| {
+        let mut argv = vec!["rfc", "infile", "-o", "outfile"];
+        argv.extend_from_slice(extra);
+        Args::try_parse_from(argv).unwrap()
+    };
+
+    let args = parse(&[]);
+    assert_eq!(args.format, Format::Rfc);
+    assert_eq!(args.gfc_cipher, GfcCipher::AesGcm);
+
+    let args = parse(&["--format", "gfc", "--gfc-cipher", "chacha20", "-e", "b64"]);
+    assert_eq!(args.format, Format::Gfc);
+    assert_eq!(args.gfc_cipher, GfcCipher::Chacha20);
+    assert_eq!(args.unsupported_option(), None);
+
+    let args = parse(&["--format", "gfc", "-z"]);
+    assert_eq!(args.unsupported_option(), Some("--compress"));
+    assert_eq!(parse(&["-z"]).unsupported_option(), None);
+
+    let recipient = "age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p";
+    let args = parse(&[
+        "--format",
+        "age",
+        "--recipient",
+        recipient,
+        "--recipient",
+        "x",
+    ]);
+    assert_eq!(args.format, Format::Age);
+    assert_eq!(args.recipient.len(), 2);
+    assert_eq!(args.unsupported_option(), None);
+
+    let args = parse(&["-d", "--format", "age", "--identity", "key.txt"]);
+    assert_eq!(args.identity, vec![String::from("key.txt")]);
+    assert_eq!(
+        parse(&["--recipient", recipient]).unsupported_option(),
+        Some("--recipient")
+    );
+    assert!(Args::try_parse_from(["rfc", "in", "-o", "out", "--identity", "key.txt"]).is_err());
+
+    let args = parse(&[
+        "--format",
+        "openssl",
+        "--openssl-digest",
+        "sha1",
+        "--openssl-iter",
+        "1",
+    ]);
+    assert_eq!(args.format, Format::Openssl);
+    assert_eq!(args.openssl_digest, Pbkdf2Digest::Sha1);
+    assert_eq!(args.openssl_iter, 1);
+    assert_eq!(parse(&[]).openssl_iter, openssl::DEFAULT_ITERATIONS);
+    assert_eq!(
+        parse(&["--format", "openssl", "-m"]).unsupported_option(),
+        Some("--metadata")
+    );
+    assert!(Args::try_parse_from(["rfc", "in", "-o", "out", "--format", "go"]).is_err());
+}
+
+#[test]
+fn test_threads() {
+    let parse = |threads: &str| Args::try_parse_from(["rfc", "infile", "-o", "out", "-t", threads]);
+
+    assert_eq!(parse("4").unwrap().threads, NonZeroUsize::new(4));
+    assert!(parse("0").is_err());
+}
+
+#[test]
+fn test_subcommands() {
+    let parse = |argv: &[&str]| Args::try_parse_from(argv);
+
+    let args = parse(&["rfc", "keygen", "mykey"]).unwrap();
+    assert!(matches!(args.command, Some(Command::Keygen { .. })));
+    assert!(args.filename.is_none());
+
+    let args = parse(&["rfc", "sign", "file", "-k", "mykey.key"]).unwrap();
+    assert!(matches!(
+        args.command,
+        Some(Command::Sign { output: None, .. })
+    ));
+
+    let args = parse(&["rfc", "encode", "file", "-o", "file.b64", "-z"]).unwrap();
+    assert!(matches!(
+        args.command,
+        Some(Command::Encode(CodecArgs {
+            encoding: Encoding::B64,
+            compress: true,
+            ..
+        }))
+    ));
+    assert!(parse(&["rfc", "decode", "file.b64", "-o", "file", "-e", "hex"]).is_ok());
+    assert!(parse(&["rfc", "decode", "file.b64"]).is_err());
+
+    let args = parse(&["rfc", "verify-sig", "file", "-k", "mykey.pub"]).unwrap();
+    assert!(matches!(args.command, Some(Command::VerifySig { .. })));
+
+    assert!(parse(&["rfc", "sign", "file"]).is_err());
+    assert!(parse(&["rfc"]).is_err());
+
+    let args = parse(&["rfc", "infile", "-o", "out", "--sign", "mykey.key"]).unwrap();
+    assert!(args.command.is_none());
+    assert!(parse(&["rfc", "infile", "-o", "out", "-d", "--sign", "k"]).is_err());
+    assert!(parse(&["rfc", "infile", "-o", "out", "--verify-with", "k"]).is_err());
+    assert!(parse(&["rfc", "infile", "-o", "out", "-d", "--verify-with", "k"]).is_ok());
+}
+
+#[test]
+fn test_encoding() {
+    let parse = |extra: &[&str]| {
+        let mut argv = vec!["rfc", "infile", "-o", "outfile"];
+        argv.extend_from_slice(extra);
+        Args::try_parse_from(argv).map(|args| args.encoding())
+    };
+
+    assert_eq!(parse(&[]).unwrap(), Encoding::Plain);
+    assert_eq!(parse(&["-d"]).unwrap(), Encoding::Auto);
+    assert_eq!(parse(&["-d", "-e", "hex"]).unwrap(), Encoding::Hex);
+    assert_eq!(parse(&["-e", "b64"]).unwrap(), Encoding::B64);
+    assert!(parse(&["-s", "-e", "hex"]).is_err());
+}
@@ -0,0 +1,34 @@
+//! rfc is a simple, stupid Rust clone of gfc - a file encryption library and utility.
+//!
+//! Files are encrypted with AES using keys derived with PBKDF2, and authenticated with
+//! HMAC-SHA256. Plaintext can be compressed with LZ4 before encryption, and ciphertext
+//! can be encoded as text, e.g. with Base64 or ASCII armor.
+//!
+//! Most users only need [`Encryptor`] and [`Decryptor`]:
+//!
+//! ```
+//! use rfc::{Decryptor, Encoding, Encryptor, Mode};
+//!
+//! let ciphertext = Encryptor::new()
+//!     .cipher(Mode::AES128)
+//!     .compress(true)
+//!     .encoding(Encoding::Armor)
+//!     .key("my passphrase")
+//!     .encrypt_buf(&b"hello, world"[..])
+//!     .unwrap();
+//!
+//! let plaintext = Decryptor::new()
+//!     .cipher(Mode::AES128)
+//!     .compress(true)
+//!     .key("my passphrase")
+//!     .decrypt_buf(&ciphertext[..])
+//!     .unwrap();
+//!
+//! assert_eq!(plaintext, b"hello, world");
+//! ```
+//!
+//! The lower level building blocks are available in their own modules.
+
+mod rfc;
+
+pub use self::rfc::*;
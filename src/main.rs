@@ -1,50 +1,440 @@
-#![feature(array_chunks)]
-
 mod cli;
-mod rfc;
 
 use clap::Parser;
 use rpassword::read_password;
 
-use rfc::buf::{open_file, read_file};
+use std::process::ExitCode;
+
+use rfc::buf::{
+    check_same_file, open_input, open_output, read_file, write_bytes, Output, OverwritePolicy,
+    OUTPUT_MODE, SECRET_MODE,
+};
+use rfc::encoding::Encoding;
 use rfc::error::RfcError;
+use rfc::metadata::Metadata;
+use rfc::mmap::map_file;
+use rfc::openssl::{core_openssl, OpensslOptions};
+use rfc::seekable::{decrypt_range, encrypt_seekable, is_seekable, DEFAULT_CHUNK_SIZE};
+use rfc::sign;
+use rfc::{age, gfc};
+use rfc::{Decryptor, Encryptor, KdfId, Mode};
 
-fn main() -> Result<(), RfcError> {
+fn main() -> ExitCode {
     let args = cli::Args::parse();
+    let threads = args.threads.map(usize::from);
+
+    match rfc::parallel::with_threads(threads, || run(args)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err @ RfcError::OutputExists(_)) => {
+            eprintln!("rfc: {}, use --force to overwrite", err);
+            ExitCode::from(err.code())
+        }
+        Err(err) => {
+            eprintln!("rfc: {}", err);
+            ExitCode::from(err.code())
+        }
+    }
+}
+
+fn run(args: cli::Args) -> Result<(), RfcError> {
+    if let Some(command) = args.command {
+        return run_command(command);
+    }
+    if let Some(option) = args.unsupported_option() {
+        return Err(RfcError::NotImplemented(format!(
+            "{} with --format {}",
+            option, args.format
+        )));
+    }
+    if args.inspect || args.restore_name {
+        return decrypt_with_metadata(args);
+    }
+
+    let filename = args.filename.as_deref().expect("missing input filename");
+    let outfile_name = args.outfile.as_deref().expect("missing output filename");
+
+    // Refuse to clobber input with output, even with --force
+    check_same_file(filename, outfile_name)?;
+
+    let codec = args.encoding();
+    if codec == Encoding::Auto && !args.decrypt {
+        return Err(RfcError::Encoding(String::from(
+            "auto encoding detection is only supported for decryption",
+        )));
+    }
+
+    // Load signing keys before any output is created
+    let signing_key = args.sign.as_deref().map(read_secret_key).transpose()?;
+    let verifying_key = args
+        .verify_with
+        .as_deref()
+        .map(read_public_key)
+        .transpose()?;
+
+    let infile = open_input(filename)?;
+    let mut outfile = match open_output(outfile_name, args.overwrite_policy(), OUTPUT_MODE)? {
+        Some(outfile) => outfile,
+        None => {
+            eprintln!("not overwriting existing file {}", outfile_name);
+            return Ok(());
+        }
+    };
+
+    // The output is only moved into place if everything succeeded
+    crypt_file(args, infile, &mut outfile, signing_key, verifying_key)?;
+    outfile.persist()
+}
+
+/// Encrypts or decrypts `infile` into `outfile`, according to `args`.
+fn crypt_file(
+    args: cli::Args,
+    infile: std::fs::File,
+    outfile: &mut Output,
+    signing_key: Option<sign::SigningKey>,
+    verifying_key: Option<sign::VerifyingKey>,
+) -> Result<(), RfcError> {
+    let filename = args.filename.as_deref().expect("missing input filename");
+    let codec = args.encoding();
+    let cipher = args.cipher();
+
+    let metadata = match args.metadata || args.comment.is_some() {
+        true => Some(Metadata::from_path(filename, args.comment.clone())?),
+        false => None,
+    };
+
+    if args.format == cli::Format::Age {
+        let (recipients, identities) = age_keys(&args)?;
+        age::core_age(
+            args.decrypt,
+            &recipients,
+            &identities,
+            &infile,
+            None,
+            &mut *outfile,
+            codec,
+        )?;
+        return Ok(());
+    }
+
     // Prepare key
     let key = get_key(args.key_type, args.key_file)?;
 
-    // Read bytes from infile
-    let infile = open_file(args.filename, true)?;
-
-    let infile_len = Some(
-        infile
-            .metadata()
-            .map_err(|err| RfcError::IoError(err))?
-            .len() as usize,
-    );
-
-    let _ = rfc::core(
-        args.decrypt,
-        key,
-        args.cipher.rfc_mode(),
-        &infile,
-        infile_len,
-        &mut open_file(args.outfile, true)?,
-        args.encoding,
-        args.compress,
-    )?;
+    if args.format == cli::Format::Gfc {
+        let cipher = args.gfc_cipher;
+        gfc::core_gfc(
+            args.decrypt,
+            key,
+            cipher,
+            &infile,
+            None,
+            &mut *outfile,
+            codec,
+        )?;
+        return Ok(());
+    }
+
+    if args.format == cli::Format::Openssl {
+        let options = OpensslOptions {
+            cipher,
+            digest: args.openssl_digest,
+            iterations: args.openssl_iter,
+        };
+        core_openssl(
+            args.decrypt,
+            key,
+            options,
+            &infile,
+            None,
+            &mut *outfile,
+            codec,
+        )?;
+        return Ok(());
+    }
+
+    if args.seekable {
+        encrypt_seekable(
+            &infile,
+            &mut *outfile,
+            key,
+            cipher,
+            KdfId::default(),
+            DEFAULT_CHUNK_SIZE,
+        )?;
+        return Ok(());
+    }
+
+    // Compressed streams are seekable files too, which the Decryptor decompresses
+    if args.decrypt && (args.range.is_some() || (!args.compress && is_seekable(&infile)?)) {
+        if verifying_key.is_some() {
+            return Err(RfcError::BadSignature(String::from(
+                "seekable files are not signed",
+            )));
+        }
+
+        let range = args.range.unwrap_or(cli::ByteRange {
+            start: 0,
+            end: None,
+        });
+
+        decrypt_range(&infile, &mut *outfile, key, range.start, range.end)?;
+        return Ok(());
+    }
+
+    let map = match args.mmap {
+        true => map_file(&infile)?,
+        false => None,
+    };
+
+    if args.decrypt {
+        let mut decryptor = Decryptor::new()
+            .compress(args.compress)
+            .encoding(codec)
+            .key(key);
+
+        if let Some(cipher) = args.cipher {
+            decryptor = decryptor.cipher(cipher);
+        }
+        if let Some(verifying_key) = verifying_key {
+            decryptor = decryptor.verify(verifying_key);
+        }
+
+        let _ = match map {
+            Some(map) => decryptor.decrypt_slice(&map, &mut *outfile),
+            None => decryptor.decrypt(&infile, &mut *outfile),
+        }?;
+
+        return Ok(());
+    }
+
+    let mut encryptor = Encryptor::new()
+        .cipher(cipher)
+        .compress(args.compress)
+        .encoding(codec)
+        .padding(args.pad)
+        .key(key);
+
+    if let Some(metadata) = metadata {
+        encryptor = encryptor.metadata(metadata);
+    }
+    if let Some(signing_key) = signing_key {
+        encryptor = encryptor.sign(signing_key);
+    }
+
+    let _ = match map {
+        Some(map) => encryptor.encrypt_slice(&map, &mut *outfile),
+        None => encryptor.encrypt(&infile, &mut *outfile),
+    }?;
 
     Ok(())
 }
 
-fn get_passphrase<'a>() -> Result<Vec<u8>, RfcError> {
+/// Runs an rfc subcommand.
+fn run_command(command: cli::Command) -> Result<(), RfcError> {
+    match command {
+        cli::Command::Keygen { prefix, force } => {
+            let key = sign::generate_key();
+            let secret_name = format!("{}.key", prefix);
+            let public_name = format!("{}.pub", prefix);
+
+            write_new_file(&secret_name, sign::encode_secret_key(&key), force, true)?;
+            write_new_file(
+                &public_name,
+                sign::encode_public_key(&key.verifying_key()),
+                force,
+                false,
+            )?;
+
+            eprintln!(
+                "wrote secret key to {} and public key to {}",
+                secret_name, public_name
+            );
+            Ok(())
+        }
+
+        cli::Command::Sign {
+            filename,
+            key,
+            output,
+            force,
+        } => {
+            let key = read_secret_key(&key)?;
+            let signature = sign::sign(&read_file(&filename)?, &key);
+            let output = output.unwrap_or(format!("{}.sig", filename));
+
+            write_new_file(&output, sign::encode_signature(&signature), force, false)
+        }
+
+        cli::Command::Encode(args) => transform(false, args),
+        cli::Command::Decode(args) => transform(true, args),
+
+        cli::Command::VerifySig {
+            filename,
+            key,
+            signature,
+        } => {
+            let key = read_public_key(&key)?;
+            let signature = signature.unwrap_or(format!("{}.sig", filename));
+            let signature = sign::decode_signature(&read_file(signature)?)?;
+
+            sign::verify(&read_file(&filename)?, &signature, &key)?;
+            eprintln!("good signature for {}", filename);
+            Ok(())
+        }
+    }
+}
+
+/// Encodes and compresses, or decodes and decompresses, a file without encryption.
+fn transform(decode: bool, args: cli::CodecArgs) -> Result<(), RfcError> {
+    if args.encoding == Encoding::Auto {
+        return Err(RfcError::Encoding(String::from(
+            "auto encoding detection is only supported for decryption",
+        )));
+    }
+
+    check_same_file(&args.filename, &args.outfile)?;
+
+    let infile = open_input(&args.filename)?;
+    let mut outfile = open_output(&args.outfile, force_policy(args.force), OUTPUT_MODE)?
+        .expect("refused output without an error");
+
+    let _ = match decode {
+        false => Encryptor::new()
+            .cipher(Mode::NONE)
+            .compress(args.compress)
+            .encoding(args.encoding)
+            .encrypt(&infile, &mut outfile),
+        true => Decryptor::new()
+            .cipher(Mode::NONE)
+            .compress(args.compress)
+            .encoding(args.encoding)
+            .decrypt(&infile, &mut outfile),
+    }?;
+
+    outfile.persist()
+}
+
+fn force_policy(force: bool) -> OverwritePolicy {
+    match force {
+        true => OverwritePolicy::Force,
+        false => OverwritePolicy::Refuse,
+    }
+}
+
+/// Writes `contents` to a new file, refusing to overwrite existing files unless `force` is set.
+/// `secret` files are only readable by their owner.
+fn write_new_file(
+    filename: &str,
+    contents: String,
+    force: bool,
+    secret: bool,
+) -> Result<(), RfcError> {
+    let mode = match secret {
+        true => SECRET_MODE,
+        false => OUTPUT_MODE,
+    };
+    let mut file =
+        open_output(filename, force_policy(force), mode)?.expect("refused output without an error");
+
+    write_bytes(&mut file, contents.into_bytes())?;
+    file.persist()
+}
+
+/// Loads the Ed25519 secret key to sign with from `filename`.
+fn read_secret_key(filename: &str) -> Result<sign::SigningKey, RfcError> {
+    sign::decode_secret_key(&read_file(filename)?)
+}
+
+/// Loads the Ed25519 public key to verify signatures with from `filename`.
+fn read_public_key(filename: &str) -> Result<sign::VerifyingKey, RfcError> {
+    sign::decode_public_key(&read_file(filename)?)
+}
+
+/// Decrypts input to memory, and either prints the embedded metadata (--inspect)
+/// or writes the plaintext to the original filename (--restore-name).
+fn decrypt_with_metadata(args: cli::Args) -> Result<(), RfcError> {
+    let filename = args.filename.as_deref().expect("missing input filename");
+    let verifying_key = args
+        .verify_with
+        .as_deref()
+        .map(read_public_key)
+        .transpose()?;
+
+    let infile = open_input(filename)?;
+    let key = get_key(args.key_type.clone(), args.key_file.clone())?;
+
+    let mut decryptor = Decryptor::new()
+        .compress(args.compress)
+        .encoding(args.encoding())
+        .key(key);
+
+    if let Some(cipher) = args.cipher {
+        decryptor = decryptor.cipher(cipher);
+    }
+    if let Some(verifying_key) = verifying_key {
+        decryptor = decryptor.verify(verifying_key);
+    }
+
+    let (plaintext, metadata) = decryptor.decrypt_with_metadata(&infile)?;
+
+    let metadata = metadata.ok_or(RfcError::Metadata(String::from(
+        "input has no encrypted metadata",
+    )))?;
+
+    if args.inspect {
+        println!("{}", metadata);
+        return Ok(());
+    }
+
+    let outfile_name = metadata.safe_filename()?;
+    check_same_file(filename, outfile_name)?;
+
+    let mut outfile = match open_output(outfile_name, args.overwrite_policy(), OUTPUT_MODE)? {
+        Some(outfile) => outfile,
+        None => {
+            eprintln!("not overwriting existing file {}", outfile_name);
+            return Ok(());
+        }
+    };
+
+    write_bytes(&mut outfile, plaintext)?;
+    metadata.apply(outfile.as_file())?;
+    outfile.persist()
+}
+
+fn get_passphrase() -> Result<Vec<u8>, RfcError> {
     println!("Enter your passphrase (will not echo):");
-    let passphrase = read_password().map_err(|err| RfcError::IoError(err))?;
+    let passphrase = read_password().map_err(RfcError::IoError)?;
 
     Ok(passphrase.as_bytes().to_vec())
 }
 
+/// Returns the age recipients and identities of --recipient and --identity, or the
+/// passphrase or key file as a scrypt recipient or identity if neither is given.
+fn age_keys(args: &cli::Args) -> Result<(Vec<age::Recipient>, Vec<age::Identity>), RfcError> {
+    if !args.recipient.is_empty() || !args.identity.is_empty() {
+        let recipients = args
+            .recipient
+            .iter()
+            .map(|recipient| recipient.parse())
+            .collect::<Result<_, _>>()?;
+
+        let mut identities = Vec::new();
+        for filename in &args.identity {
+            let text = String::from_utf8(read_file(filename)?)
+                .map_err(|_| RfcError::InvalidKey(format!("{} is not UTF-8", filename)))?;
+            identities.extend(age::read_identities(&text)?);
+        }
+
+        return Ok((recipients, identities));
+    }
+
+    let key = get_key(args.key_type.clone(), args.key_file.clone())?;
+    Ok((
+        vec![age::Recipient::Scrypt(key.clone(), age::SCRYPT_WORK_FACTOR)],
+        vec![age::Identity::Scrypt(key)],
+    ))
+}
+
 fn get_key<P>(key_type: cli::KeyType, key_file: Option<P>) -> Result<Vec<u8>, RfcError>
 where
     P: AsRef<std::path::Path>,
@@ -9,7 +9,6 @@
     Debug,
 )]
 #[archive(check_bytes)]
-
 pub(crate) struct HeaderAes(pub usize);
 
 #[cfg(test)]
@@ -42,7 +41,7 @@ mod tests {
 
         let header = HeaderAes(extra);
 
-        return WrapperBytes(header, content);
+        WrapperBytes(header, content)
     }
 
     #[test]
@@ -74,7 +73,7 @@ mod tests {
         print_size(
             format!("rkyv {}", filename),
             file,
-            file.encode().expect("failed to encode rkyv"),
+            file.encode().expect("failed to encode rkyv").to_vec(),
         );
 
         print_size(
@@ -103,7 +102,7 @@ mod tests {
             "size_of_data: {}\nmem_size_of_padding: {}\nmem_size_of_header: {}\nheader_bytes: {}\nfile_bytes_length: {}\n",
             data.len(),
             std::mem::size_of::<usize>(),
-            std::mem::size_of_val(&header),
+            std::mem::size_of_val(header),
             header_size,
             f_bytes.len(),
         );
@@ -1,132 +1,158 @@
 mod header;
 
 /// Basic AES cipher, with implementation from "aes" extern crate.
-/// This crate wraps the extern crate with extra logic, i.e. padding
-/// bytes as well as removing the padding during decryption.
+/// Blocks are encrypted in place, and rfc records the plaintext length.
 ///
-/// Since we need padding information to remove padding when decrypting,
-/// the encryption output bytes are serialized from `RfcFile<AesHeader>`.
-use aes::cipher::{generic_array::GenericArray, typenum::U16};
+/// Legacy files, whose plaintext length is not recorded by rfc, hold the
+/// ciphertext serialized with the length of its last block in `WrapperBytes<HeaderAes>`.
+use aes::cipher::{generic_array::GenericArray, inout::InOutBuf, typenum::U16};
 use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
 use aes::{Aes128, Aes256};
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use rayon::prelude::*;
 
 use std::io::Write;
 
-use crate::rfc::buf::bytes_chunks;
+use crate::rfc::cipher::{AeadChunks, ChunkAead, Cipher};
 use crate::rfc::error::RfcError;
 use crate::rfc::wrapper::WrapperBytes;
-use crate::rfc::Cipher;
 use header::HeaderAes;
 
 const AES_BLOCKSIZE: usize = 16;
 
-type BlockAes = GenericArray<u8, U16>;
+/// Number of AES blocks (64 KiB) processed by each parallel task
+const PAR_BLOCKS: usize = 4096;
 
 /// Raw AES 128 cipher. It uses the implementation from crate "aes".
-/// It handles padding removal when decrypting a padded ciphertext.
+/// It only encrypts full blocks, which rfc zero-fills the plaintext to.
 pub struct CipherAes128 {}
 /// Raw AES 256 cipher. It uses the implementation from crate "aes"
-/// It handles padding removal when decrypting a padded ciphertext.
+/// It only encrypts full blocks, which rfc zero-fills the plaintext to.
 pub struct CipherAes256 {}
 
 impl Cipher for CipherAes128 {
-    const KEY_SIZE: usize = 16;
-
-    fn encrypt<T, K>(bytes: T, key: K) -> Result<Vec<u8>, RfcError>
-    where
-        T: AsRef<[u8]>,
-        K: AsRef<[u8]>,
-    {
-        let (mut blocks, extra) = aes_blocks(bytes);
-        Aes128::new(&GenericArray::from(aes_key(key))).encrypt_blocks(&mut blocks);
-
-        let ciphertext = aes_blocks_to_bytes(blocks);
-        encode_encryption_output(ciphertext, extra)
-    }
-
-    fn decrypt<T, K>(bytes: T, key: K) -> Result<Vec<u8>, RfcError>
-    where
-        T: AsRef<[u8]>,
-        K: AsRef<[u8]>,
-    {
-        let infile = WrapperBytes::<HeaderAes>::decode_archived(bytes.as_ref())?;
-        let ciphertext = &infile.1;
-
-        let (mut blocks, ciphertext_extra) = aes_blocks(ciphertext);
-        if ciphertext_extra != 0 {
-            return Err(RfcError::Decryption(format!(
-                "input not full AES blocks: got {} extra trailing",
-                ciphertext_extra
-            )));
-        }
-
-        Aes128::new(&GenericArray::from(aes_key(key))).decrypt_blocks(&mut blocks);
-        let plaintext = aes_blocks_to_bytes(blocks);
-
-        let extra = (&infile.0).0 as usize;
-        Ok(truncate_padding(plaintext, extra))
+    fn id(&self) -> u8 {
+        1
+    }
+
+    fn name(&self) -> &'static str {
+        "aes128"
+    }
+
+    fn key_size(&self) -> usize {
+        16
+    }
+
+    fn block_size(&self) -> usize {
+        AES_BLOCKSIZE
+    }
+
+    fn encrypt(&self, buf: &mut [u8], _offset: u64, key: &[u8]) -> Result<(), RfcError> {
+        encrypt_par(
+            &Aes128::new(&GenericArray::from(aes_key(key))),
+            full_blocks(buf)?,
+        );
+        Ok(())
+    }
+
+    fn decrypt(&self, buf: &mut [u8], _offset: u64, key: &[u8]) -> Result<(), RfcError> {
+        decrypt_par(
+            &Aes128::new(&GenericArray::from(aes_key(key))),
+            full_blocks(buf)?,
+        );
+        Ok(())
+    }
+
+    fn chunk_aead(&self, key: &[u8]) -> Option<Box<dyn ChunkAead>> {
+        Some(Box::new(AeadChunks(Aes128Gcm::new(&GenericArray::from(
+            aes_key(key),
+        )))))
     }
 }
 
 impl Cipher for CipherAes256 {
-    const KEY_SIZE: usize = 32;
-
-    fn encrypt<T, K>(bytes: T, key: K) -> Result<Vec<u8>, RfcError>
-    where
-        T: AsRef<[u8]>,
-        K: AsRef<[u8]>,
-    {
-        let (mut blocks, extra) = aes_blocks(bytes);
-        Aes256::new(&GenericArray::from(aes_key(key))).encrypt_blocks(&mut blocks);
-
-        let blocks = aes_blocks_to_bytes(blocks);
-        encode_encryption_output(blocks, extra)
-    }
-
-    fn decrypt<T, K>(bytes: T, key: K) -> Result<Vec<u8>, RfcError>
-    where
-        T: AsRef<[u8]>,
-        K: AsRef<[u8]>,
-    {
-        let infile = WrapperBytes::<HeaderAes>::decode_archived(bytes.as_ref())?;
-        let ciphertext = &infile.1;
-
-        let (mut blocks, ciphertext_extra) = aes_blocks(ciphertext);
-        if ciphertext_extra != 0 {
-            return Err(RfcError::Decryption(format!(
-                "input not full AES blocks: got {} extra trailing",
-                ciphertext_extra
-            )));
-        }
-
-        Aes256::new(&GenericArray::from(aes_key(key))).decrypt_blocks(&mut blocks);
-        let plaintext = aes_blocks_to_bytes(blocks);
-
-        let extra = (&infile.0).0 as usize;
-        Ok(truncate_padding(plaintext, extra))
+    fn id(&self) -> u8 {
+        2
+    }
+
+    fn name(&self) -> &'static str {
+        "aes256"
+    }
+
+    fn key_size(&self) -> usize {
+        32
+    }
+
+    fn block_size(&self) -> usize {
+        AES_BLOCKSIZE
+    }
+
+    fn encrypt(&self, buf: &mut [u8], _offset: u64, key: &[u8]) -> Result<(), RfcError> {
+        encrypt_par(
+            &Aes256::new(&GenericArray::from(aes_key(key))),
+            full_blocks(buf)?,
+        );
+        Ok(())
+    }
+
+    fn decrypt(&self, buf: &mut [u8], _offset: u64, key: &[u8]) -> Result<(), RfcError> {
+        decrypt_par(
+            &Aes256::new(&GenericArray::from(aes_key(key))),
+            full_blocks(buf)?,
+        );
+        Ok(())
+    }
+
+    fn chunk_aead(&self, key: &[u8]) -> Option<Box<dyn ChunkAead>> {
+        Some(Box::new(AeadChunks(Aes256Gcm::new(&GenericArray::from(
+            aes_key(key),
+        )))))
     }
 }
 
-fn aes_blocks<T>(bytes: T) -> (Vec<BlockAes>, usize)
+/// Encrypts `buf` in place, spreading the work across the current rayon thread pool.
+/// `buf` must be a multiple of AES_BLOCKSIZE long.
+fn encrypt_par<C>(cipher: &C, buf: &mut [u8])
 where
-    T: AsRef<[u8]>,
+    C: BlockEncrypt<BlockSize = U16> + Sync,
 {
-    let (chunks, extra) = bytes_chunks::<AES_BLOCKSIZE, T>(bytes);
-    let mut blocks: Vec<BlockAes> = Vec::with_capacity(chunks.len());
+    buf.par_chunks_mut(PAR_BLOCKS * AES_BLOCKSIZE)
+        .for_each(|chunk| cipher.encrypt_blocks_inout(InOutBuf::from(chunk).into_chunks().0));
+}
 
-    for chunk in chunks {
-        blocks.push(GenericArray::from(chunk));
-    }
+/// Decrypts `buf` in place, spreading the work across the current rayon thread pool.
+/// `buf` must be a multiple of AES_BLOCKSIZE long.
+fn decrypt_par<C>(cipher: &C, buf: &mut [u8])
+where
+    C: BlockDecrypt<BlockSize = U16> + Sync,
+{
+    buf.par_chunks_mut(PAR_BLOCKS * AES_BLOCKSIZE)
+        .for_each(|chunk| cipher.decrypt_blocks_inout(InOutBuf::from(chunk).into_chunks().0));
+}
 
-    (blocks, extra)
+/// Returns `buf` if it is full AES blocks, which is all the AES ciphers encrypt.
+fn full_blocks(buf: &mut [u8]) -> Result<&mut [u8], RfcError> {
+    match buf.len() % AES_BLOCKSIZE {
+        0 => Ok(buf),
+        extra => Err(RfcError::Decryption(format!(
+            "input not full AES blocks: got {} extra trailing",
+            extra
+        ))),
+    }
 }
 
-fn aes_blocks_to_bytes(blocks: Vec<BlockAes>) -> Vec<u8> {
-    blocks
-        .into_iter()
-        .map(|block| block.as_slice().to_owned())
-        .flat_map(|slice| slice.into_iter().map(|byte| byte.to_owned()))
-        .collect()
+/// Decrypts the AES output of legacy files with `cipher`, the registered AES cipher.
+/// It is the ciphertext archived with the length of its last, zero-filled block.
+pub(crate) fn decrypt_legacy(
+    cipher: &dyn Cipher,
+    bytes: &[u8],
+    key: &[u8],
+) -> Result<Vec<u8>, RfcError> {
+    let infile = WrapperBytes::<HeaderAes>::decode_archived(bytes)?;
+    let mut buf = infile.1.to_vec();
+    cipher.decrypt(&mut buf, 0, key)?;
+
+    Ok(truncate_padding(buf, infile.0 .0 as usize))
 }
 
 fn aes_key<const KEY_SIZE: usize, K>(key: K) -> [u8; KEY_SIZE]
@@ -137,24 +163,23 @@ where
     let mut buf = &mut bytes[..];
 
     buf.write_all(key.as_ref())
-        .expect(format!("failed to create AES-{} key", 32 * 8).as_str());
+        .unwrap_or_else(|_| panic!("failed to create AES-{} key", KEY_SIZE * 8));
 
     bytes
 }
 
 fn truncate_padding(mut plaintext: Vec<u8>, extra: usize) -> Vec<u8> {
-    let final_len = plaintext.len().checked_sub(AES_BLOCKSIZE).unwrap_or(0) + extra;
+    // No padding block was appended if the plaintext was already block-aligned
+    if extra == 0 {
+        return plaintext;
+    }
+
+    let final_len = plaintext.len().saturating_sub(AES_BLOCKSIZE) + extra;
     plaintext.truncate(final_len);
 
     plaintext
 }
 
-fn encode_encryption_output(ciphertext: Vec<u8>, extra: usize) -> Result<Vec<u8>, RfcError> {
-    let output: WrapperBytes<HeaderAes> = WrapperBytes::<HeaderAes>(HeaderAes(extra), ciphertext);
-
-    output.encode()
-}
-
 #[cfg(test)]
 pub mod tests {
     use super::{CipherAes128, CipherAes256};
@@ -162,7 +187,7 @@ pub mod tests {
 
     #[test]
     fn test_wrapped_aes() {
-        test_cipher::<CipherAes256>();
-        test_cipher::<CipherAes128>();
+        test_cipher(&CipherAes256 {});
+        test_cipher(&CipherAes128 {});
     }
 }
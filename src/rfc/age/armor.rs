@@ -0,0 +1,84 @@
+//! The ASCII armor of age files, strict PEM-like Base64 between BEGIN and END lines.
+//!
+//! Decoding accepts LF and CRLF line endings and whitespace around the armor, but nothing
+//! else: there are no armor headers, body lines are 64 columns except the last one, and the
+//! Base64 must be padded and canonical.
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+
+use crate::rfc::error::RfcError;
+
+const BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const END: &str = "-----END AGE ENCRYPTED FILE-----";
+
+/// Columns of armor lines
+const COLUMNS: usize = 64;
+
+/// Returns the armored `bytes`.
+pub(super) fn armor(bytes: &[u8]) -> Vec<u8> {
+    let encoded = STANDARD.encode(bytes);
+
+    let mut armored = format!("{}\n", BEGIN);
+    for line in encoded.as_bytes().chunks(COLUMNS) {
+        armored.push_str(std::str::from_utf8(line).expect("Base64 is not UTF-8"));
+        armored.push('\n');
+    }
+    armored.push_str(END);
+    armored.push('\n');
+
+    armored.into_bytes()
+}
+
+/// Decodes armored `bytes`.
+pub(super) fn dearmor(bytes: &[u8]) -> Result<Vec<u8>, RfcError> {
+    let text = std::str::from_utf8(bytes)
+        .map_err(|_| armor_error("not UTF-8"))?
+        .trim_matches(|c| matches!(c, ' ' | '\t' | '\r' | '\n'));
+
+    let lines: Vec<&str> = text
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+
+    let [BEGIN, body @ .., END] = lines.as_slice() else {
+        return Err(armor_error("missing BEGIN or END line"));
+    };
+    let Some((last, full)) = body.split_last() else {
+        return Err(armor_error("no data"));
+    };
+
+    if full.iter().any(|line| line.len() != COLUMNS) {
+        return Err(armor_error("lines must be 64 columns"));
+    }
+    if last.is_empty() || last.len() > COLUMNS {
+        return Err(armor_error("bad last line"));
+    }
+
+    STANDARD
+        .decode(body.concat())
+        .map_err(|err| armor_error(&format!("bad Base64: {}", err)))
+}
+
+fn armor_error(msg: &str) -> RfcError {
+    RfcError::Encoding(format!("age armor: {}", msg))
+}
+
+#[test]
+fn test_armor() {
+    for len in [1, 47, 48, 49, 1000] {
+        let bytes: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        let armored = armor(&bytes);
+        assert_eq!(dearmor(&armored).unwrap(), bytes);
+
+        let crlf = String::from_utf8(armored).unwrap().replace('\n', "\r\n");
+        assert_eq!(
+            dearmor(format!("\n {}\t\n", crlf).as_bytes()).unwrap(),
+            bytes
+        );
+    }
+
+    assert!(dearmor(format!("{}\n{}\n", BEGIN, END).as_bytes()).is_err());
+    assert!(dearmor(format!("{}\nAAAA\n", BEGIN).as_bytes()).is_err());
+    assert!(dearmor(format!("{}\nAAA\n{}", BEGIN, END).as_bytes()).is_err());
+    assert!(dearmor(format!("{}\nAAA=\n{}", BEGIN, END).as_bytes()).is_ok());
+}
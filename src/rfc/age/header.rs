@@ -0,0 +1,244 @@
+//! The textual age header: the version line, recipient stanzas and the header MAC.
+//!
+//! Parsing is strict, as the age spec requires: stanza bodies are wrapped at 64 columns,
+//! and Base64 must be unpadded and canonical, so that each header has one encoding.
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine as _;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::rfc::error::RfcError;
+
+/// First line of age files, the version
+pub(super) const VERSION_LINE: &str = "age-encryption.org/v1";
+
+/// Prefix of the version line of all age versions
+pub(super) const VERSION_PREFIX: &[u8] = b"age-encryption.org/";
+
+/// Columns of stanza body lines
+const COLUMNS: usize = 64;
+
+/// Size of the HMAC-SHA256 header MAC
+const MAC_SIZE: usize = 32;
+
+/// Unpadded, canonical standard Base64, as used in headers
+pub(super) const B64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::RequireNone),
+);
+
+/// Stanza of one recipient, `-> tag args...` followed by its body
+#[derive(Debug, PartialEq)]
+pub(super) struct Stanza {
+    pub tag: String,
+    pub args: Vec<String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub(super) struct Header {
+    pub stanzas: Vec<Stanza>,
+    pub mac: Vec<u8>,
+    /// Length of the header bytes covered by the MAC, up to and including `---`
+    pub mac_len: usize,
+    /// Length of the whole header, including the final line break
+    pub len: usize,
+}
+
+impl Header {
+    /// Parses the header at the start of `bytes`.
+    pub fn parse(bytes: &[u8]) -> Result<Self, RfcError> {
+        let mut lines = Lines { bytes, pos: 0 };
+
+        match lines.next()? {
+            version if version == VERSION_LINE.as_bytes() => {}
+            version if version.starts_with(VERSION_PREFIX) => {
+                return Err(header_error(&format!(
+                    "unsupported version {}",
+                    String::from_utf8_lossy(version)
+                )))
+            }
+            _ => return Err(header_error("missing version line")),
+        }
+
+        let mut stanzas = Vec::new();
+        loop {
+            let start = lines.pos;
+            let line = lines.next()?;
+
+            if let Some(args) = line.strip_prefix(b"-> ") {
+                stanzas.push(parse_stanza(args, &mut lines)?);
+                continue;
+            }
+
+            let Some(mac) = line.strip_prefix(b"--- ") else {
+                return Err(header_error("expected a stanza or the header MAC"));
+            };
+            let mac = decode(mac)?;
+            if mac.len() != MAC_SIZE {
+                return Err(header_error("bad MAC length"));
+            }
+
+            return Ok(Self {
+                stanzas,
+                mac,
+                mac_len: start + 3,
+                len: lines.pos,
+            });
+        }
+    }
+}
+
+/// Returns the encoded header for `stanzas`, authenticated with `file_key`.
+pub(super) fn write_header(stanzas: &[Stanza], file_key: &[u8]) -> Vec<u8> {
+    let mut header = format!("{}\n", VERSION_LINE);
+
+    for stanza in stanzas {
+        header.push_str("->");
+        for arg in std::iter::once(&stanza.tag).chain(&stanza.args) {
+            header.push(' ');
+            header.push_str(arg);
+        }
+        header.push('\n');
+
+        // A body that fills its last line is followed by an empty line
+        let body = B64.encode(&stanza.body);
+        for line in body.as_bytes().chunks(COLUMNS) {
+            header.push_str(std::str::from_utf8(line).expect("Base64 is not UTF-8"));
+            header.push('\n');
+        }
+        if body.len() % COLUMNS == 0 {
+            header.push('\n');
+        }
+    }
+
+    header.push_str("---");
+    let mac = header_mac(file_key, header.as_bytes());
+
+    let mut header = header.into_bytes();
+    header.push(b' ');
+    header.extend_from_slice(B64.encode(mac).as_bytes());
+    header.push(b'\n');
+
+    header
+}
+
+/// Computes the MAC of the header bytes up to and including `---`.
+pub(super) fn header_mac(file_key: &[u8], header: &[u8]) -> [u8; MAC_SIZE] {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, file_key)
+        .expand(b"header", &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&key).expect("HMAC takes keys of any size");
+    mac.update(header);
+
+    mac.finalize().into_bytes().into()
+}
+
+/// Decodes unpadded, canonical Base64.
+pub(super) fn decode(encoded: &[u8]) -> Result<Vec<u8>, RfcError> {
+    B64.decode(encoded)
+        .map_err(|err| header_error(&format!("bad Base64: {}", err)))
+}
+
+pub(super) fn header_error(msg: &str) -> RfcError {
+    RfcError::Deserialize(format!("age header: {}", msg))
+}
+
+fn parse_stanza(line: &[u8], lines: &mut Lines) -> Result<Stanza, RfcError> {
+    let mut args = Vec::new();
+    for arg in line.split(|&b| b == b' ') {
+        // Arguments are non-empty strings of printable ASCII
+        if arg.is_empty() || !arg.iter().all(|b| (33..=126).contains(b)) {
+            return Err(header_error("bad stanza argument"));
+        }
+        args.push(String::from_utf8(arg.to_vec()).expect("ASCII is not UTF-8"));
+    }
+    let tag = args.remove(0);
+
+    let mut body = Vec::new();
+    loop {
+        let line = lines.next()?;
+        if line.len() > COLUMNS {
+            return Err(header_error("stanza body line too long"));
+        }
+
+        body.extend_from_slice(line);
+        if line.len() < COLUMNS {
+            break;
+        }
+    }
+
+    Ok(Stanza {
+        tag,
+        args,
+        body: decode(&body)?,
+    })
+}
+
+/// Lines yields the lines of a header, which must end with LF.
+struct Lines<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lines<'a> {
+    fn next(&mut self) -> Result<&'a [u8], RfcError> {
+        let rest = &self.bytes[self.pos..];
+        let len = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or(header_error("unexpected end of header"))?;
+
+        let line = &rest[..len];
+        if line.contains(&b'\r') {
+            return Err(header_error("header lines must end with LF"));
+        }
+
+        self.pos += len + 1;
+        Ok(line)
+    }
+}
+
+#[test]
+fn test_header() {
+    let file_key = [7u8; 16];
+    let stanzas = vec![
+        Stanza {
+            tag: String::from("X25519"),
+            args: vec![String::from("share")],
+            body: vec![1u8; 32],
+        },
+        Stanza {
+            tag: String::from("grease"),
+            args: vec![],
+            body: vec![2u8; 48],
+        },
+        Stanza {
+            tag: String::from("empty"),
+            args: vec![String::from("a"), String::from("b")],
+            body: vec![],
+        },
+    ];
+
+    let mut bytes = write_header(&stanzas, &file_key);
+    let len = bytes.len();
+    bytes.extend_from_slice(b"payload");
+
+    let header = Header::parse(&bytes).unwrap();
+    assert_eq!(header.stanzas, stanzas);
+    assert_eq!(header.len, len);
+    assert_eq!(
+        header.mac,
+        header_mac(&file_key, &bytes[..header.mac_len]).to_vec()
+    );
+    assert!(bytes[..header.mac_len].ends_with(b"\n---"));
+
+    assert!(Header::parse(&bytes[..len - 1]).is_err());
+    assert!(Header::parse(b"age-encryption.org/v2\n--- x\n").is_err());
+}
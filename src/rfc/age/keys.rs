@@ -0,0 +1,312 @@
+//! age recipients and identities: scrypt passphrases and X25519 keys.
+//!
+//! Each recipient wraps the file key into a stanza of the header, which the matching
+//! identity unwraps. X25519 keys are encoded with Bech32, as `age1...` recipients
+//! and `AGE-SECRET-KEY-1...` identities.
+use bech32::{FromBase32, ToBase32, Variant};
+use hkdf::Hkdf;
+use rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use std::str::FromStr;
+
+use super::header::{decode, header_error, Stanza, B64};
+use super::{chacha20_poly1305, FILE_KEY_SIZE};
+use crate::rfc::error::RfcError;
+use crate::rfc::kdf::KdfId;
+use crate::rfc::scrypt::MAX_SCRYPT_WORK_FACTOR;
+
+use base64::Engine as _;
+
+/// scrypt work factor of passphrase recipients, as log2(N)
+pub use crate::rfc::scrypt::SCRYPT_WORK_FACTOR;
+
+const SCRYPT_TAG: &str = "scrypt";
+const SCRYPT_SALT_LABEL: &[u8] = b"age-encryption.org/v1/scrypt";
+const SCRYPT_SALT_SIZE: usize = 16;
+
+const X25519_TAG: &str = "X25519";
+const X25519_INFO: &[u8] = b"age-encryption.org/v1/X25519";
+
+const RECIPIENT_HRP: &str = "age";
+const IDENTITY_HRP: &str = "age-secret-key-";
+
+/// Size of stanza bodies, the wrapped file key and its tag
+const WRAPPED_KEY_SIZE: usize = FILE_KEY_SIZE + 16;
+
+/// Recipient that files are encrypted to
+#[derive(Clone)]
+pub enum Recipient {
+    /// Passphrase, with the scrypt work factor as log2(N). Must be the only recipient.
+    Scrypt(Vec<u8>, u8),
+    /// X25519 public key, parsed from an `age1...` string
+    X25519(PublicKey),
+}
+
+/// Identity that decrypts files encrypted to the matching recipient
+#[derive(Clone)]
+pub enum Identity {
+    /// Passphrase
+    Scrypt(Vec<u8>),
+    /// X25519 secret key, parsed from an `AGE-SECRET-KEY-1...` string
+    X25519(StaticSecret),
+}
+
+impl Recipient {
+    /// Returns the stanza wrapping `file_key` for the recipient.
+    pub(super) fn wrap(&self, file_key: &[u8]) -> Result<Stanza, RfcError> {
+        match self {
+            Self::Scrypt(passphrase, work_factor) => {
+                let mut salt = [0u8; SCRYPT_SALT_SIZE];
+                OsRng.fill_bytes(&mut salt);
+
+                let key = scrypt_key(passphrase, &salt, *work_factor)?;
+                Ok(Stanza {
+                    tag: String::from(SCRYPT_TAG),
+                    args: vec![B64.encode(salt), work_factor.to_string()],
+                    body: wrap_key(&key, file_key)?,
+                })
+            }
+
+            Self::X25519(recipient) => {
+                let ephemeral = StaticSecret::random_from_rng(OsRng);
+                let share = PublicKey::from(&ephemeral);
+
+                let shared = ephemeral.diffie_hellman(recipient);
+                let key = x25519_key(shared.as_bytes(), &share, recipient);
+                Ok(Stanza {
+                    tag: String::from(X25519_TAG),
+                    args: vec![B64.encode(share.as_bytes())],
+                    body: wrap_key(&key, file_key)?,
+                })
+            }
+        }
+    }
+
+    fn is_scrypt(&self) -> bool {
+        matches!(self, Self::Scrypt(..))
+    }
+}
+
+/// Checks that passphrase recipients are not mixed with others, as age requires.
+pub(super) fn check_recipients(recipients: &[Recipient]) -> Result<(), RfcError> {
+    match recipients {
+        [] => Err(RfcError::Encryption(String::from("no age recipients"))),
+        [_] => Ok(()),
+        _ if recipients.iter().any(Recipient::is_scrypt) => Err(RfcError::Encryption(
+            String::from("an age passphrase must be the only recipient"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+impl FromStr for Recipient {
+    type Err = RfcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key: [u8; 32] = decode_bech32(s, RECIPIENT_HRP)?;
+        Ok(Self::X25519(PublicKey::from(key)))
+    }
+}
+
+impl std::fmt::Display for Recipient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Scrypt(..) => write!(f, "passphrase"),
+            Self::X25519(key) => write!(f, "{}", encode_bech32(RECIPIENT_HRP, key.as_bytes())),
+        }
+    }
+}
+
+impl Identity {
+    /// Generates a random X25519 identity.
+    pub fn generate() -> Self {
+        Self::X25519(StaticSecret::random_from_rng(OsRng))
+    }
+
+    /// Returns the recipient of the identity, which is `None` for passphrases.
+    pub fn to_public(&self) -> Option<Recipient> {
+        match self {
+            Self::Scrypt(_) => None,
+            Self::X25519(secret) => Some(Recipient::X25519(PublicKey::from(secret))),
+        }
+    }
+
+    /// Unwraps the file key from `stanza`. Returns `None` if the stanza is for another
+    /// identity, and an error if it is malformed.
+    pub(super) fn unwrap(&self, stanza: &Stanza) -> Result<Option<Vec<u8>>, RfcError> {
+        match (self, stanza.tag.as_str()) {
+            (Self::Scrypt(passphrase), SCRYPT_TAG) => {
+                let [salt, work_factor] = stanza.args.as_slice() else {
+                    return Err(header_error("scrypt stanza takes 2 arguments"));
+                };
+
+                let salt = decode(salt.as_bytes())?;
+                if salt.len() != SCRYPT_SALT_SIZE {
+                    return Err(header_error("bad scrypt salt length"));
+                }
+                let work_factor = parse_work_factor(work_factor)?;
+                check_body(stanza)?;
+
+                let key = scrypt_key(passphrase, &salt, work_factor)?;
+                unwrap_key(&key, &stanza.body)
+            }
+
+            (Self::X25519(secret), X25519_TAG) => {
+                let [share] = stanza.args.as_slice() else {
+                    return Err(header_error("X25519 stanza takes 1 argument"));
+                };
+
+                let share: [u8; 32] = decode(share.as_bytes())?
+                    .try_into()
+                    .map_err(|_| header_error("bad X25519 share length"))?;
+                check_body(stanza)?;
+
+                let share = PublicKey::from(share);
+                let shared = secret.diffie_hellman(&share);
+                if !shared.was_contributory() {
+                    return Err(header_error("low order X25519 share"));
+                }
+
+                let key = x25519_key(shared.as_bytes(), &share, &PublicKey::from(secret));
+                unwrap_key(&key, &stanza.body)
+            }
+
+            _ => Ok(None),
+        }
+    }
+}
+
+impl FromStr for Identity {
+    type Err = RfcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key: [u8; 32] = decode_bech32(s, IDENTITY_HRP)?;
+        Ok(Self::X25519(StaticSecret::from(key)))
+    }
+}
+
+impl std::fmt::Display for Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Scrypt(_) => write!(f, "passphrase"),
+            Self::X25519(secret) => write!(
+                f,
+                "{}",
+                encode_bech32(IDENTITY_HRP, secret.as_bytes()).to_uppercase()
+            ),
+        }
+    }
+}
+
+/// Parses the X25519 identities of an identity file, as written by age-keygen.
+/// Empty lines and `#` comments are ignored.
+pub fn read_identities(text: &str) -> Result<Vec<Identity>, RfcError> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(Identity::from_str)
+        .collect()
+}
+
+/// Parses a scrypt work factor, a decimal without sign or leading zeros.
+fn parse_work_factor(arg: &str) -> Result<u8, RfcError> {
+    let canonical = arg.bytes().all(|b| b.is_ascii_digit()) && !arg.starts_with('0');
+
+    match arg.parse::<u8>() {
+        Ok(work_factor) if canonical && work_factor <= MAX_SCRYPT_WORK_FACTOR => Ok(work_factor),
+        _ => Err(header_error(&format!("bad scrypt work factor {:?}", arg))),
+    }
+}
+
+fn check_body(stanza: &Stanza) -> Result<(), RfcError> {
+    match stanza.body.len() {
+        WRAPPED_KEY_SIZE => Ok(()),
+        _ => Err(header_error(&format!(
+            "bad {} stanza body length",
+            stanza.tag
+        ))),
+    }
+}
+
+/// Derives the wrapping key with the registered scrypt, whose parameters are the work
+/// factor and the salt, which age prefixes with its label.
+fn scrypt_key(passphrase: &[u8], salt: &[u8], work_factor: u8) -> Result<Vec<u8>, RfcError> {
+    let params = [&[work_factor], SCRYPT_SALT_LABEL, salt].concat();
+
+    KdfId::SCRYPT.kdf()?.derive(passphrase, &params, 32)
+}
+
+fn x25519_key(shared: &[u8], share: &PublicKey, recipient: &PublicKey) -> [u8; 32] {
+    let salt = [share.as_bytes().as_slice(), recipient.as_bytes()].concat();
+
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(X25519_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+
+    key
+}
+
+/// Wraps the file key with ChaCha20-Poly1305 and a zero nonce, since each key is used once.
+fn wrap_key(key: &[u8], file_key: &[u8]) -> Result<Vec<u8>, RfcError> {
+    chacha20_poly1305(key)?.seal(&[0u8; 12], &[], file_key)
+}
+
+/// Returns the file key, or `None` if the stanza was wrapped with another key.
+fn unwrap_key(key: &[u8], body: &[u8]) -> Result<Option<Vec<u8>>, RfcError> {
+    Ok(chacha20_poly1305(key)?.open(&[0u8; 12], &[], body).ok())
+}
+
+fn encode_bech32(hrp: &str, key: &[u8]) -> String {
+    bech32::encode(hrp, key.to_base32(), Variant::Bech32).expect("bad Bech32 prefix")
+}
+
+fn decode_bech32(s: &str, hrp: &str) -> Result<[u8; 32], RfcError> {
+    let invalid = |msg: &str| RfcError::InvalidKey(format!("{}: {}", msg, s));
+
+    let (decoded_hrp, data, variant) = bech32::decode(s).map_err(|_| invalid("bad age key"))?;
+    if decoded_hrp != hrp || variant != Variant::Bech32 {
+        return Err(invalid("unexpected age key type"));
+    }
+
+    Vec::<u8>::from_base32(&data)
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or(invalid("bad age key length"))
+}
+
+#[test]
+fn test_keys() {
+    let identity = Identity::generate();
+    let recipient = identity.to_public().unwrap();
+
+    let encoded = identity.to_string();
+    assert!(encoded.starts_with("AGE-SECRET-KEY-1"));
+    assert_eq!(Identity::from_str(&encoded).unwrap().to_string(), encoded);
+
+    let encoded = recipient.to_string();
+    assert!(encoded.starts_with("age1"));
+    assert_eq!(Recipient::from_str(&encoded).unwrap().to_string(), encoded);
+
+    // Keys of age-keygen, from the age README
+    let recipient =
+        Recipient::from_str("age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p")
+            .unwrap();
+    assert!(matches!(recipient, Recipient::X25519(_)));
+    assert!(Identity::from_str(&recipient.to_string()).is_err());
+
+    let identities = read_identities(&format!(
+        "# created: 2026-01-01\n# public key: {}\n{}\n\n",
+        identity.to_public().unwrap(),
+        identity
+    ))
+    .unwrap();
+    assert_eq!(identities.len(), 1);
+
+    assert!(parse_work_factor("18").is_ok());
+    for bad in ["0", "018", "+18", "-1", "0x12", "23", "256", "18 ", ""] {
+        assert!(parse_work_factor(bad).is_err(), "{:?}", bad);
+    }
+}
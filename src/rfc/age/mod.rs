@@ -0,0 +1,294 @@
+//! Compatibility with the age file format, age-encryption.org/v1.
+//!
+//! age files start with a textual header of recipient stanzas, each wrapping a random
+//! file key, and authenticated with an HMAC keyed by the file key. The payload that follows
+//! is a random nonce and the plaintext in ChaCha20-Poly1305 STREAM chunks. Files may be
+//! wrapped in an ASCII armor.
+//!
+//! Passphrases (scrypt stanzas) and X25519 keys are supported. Stanzas of other recipient
+//! types are skipped, as the spec requires.
+//!
+//! ChaCha20-Poly1305 and scrypt are those of the cipher and KDF registries. HKDF and
+//! HMAC, which only age uses with its labels, are used directly.
+use rand_core::{OsRng, RngCore};
+use subtle::ConstantTimeEq;
+
+use std::io::{Read, Write};
+
+use self::header::{header_error, header_mac, write_header, Header, VERSION_PREFIX};
+use self::stream::{decrypt_payload, encrypt_payload, NONCE_SIZE};
+use super::buf;
+use super::cipher::ChunkAead;
+use super::encoding::Encoding;
+use super::error::RfcError;
+use super::Mode;
+
+mod armor;
+mod header;
+mod keys;
+mod stream;
+
+pub use self::keys::{read_identities, Identity, Recipient, SCRYPT_WORK_FACTOR};
+
+/// Size of the random key of each file
+const FILE_KEY_SIZE: usize = 16;
+
+/// Returns ChaCha20-Poly1305 keyed with `key`, the AEAD of the registered ChaCha20 cipher.
+fn chacha20_poly1305(key: &[u8]) -> Result<Box<dyn ChunkAead>, RfcError> {
+    Mode::CHACHA20
+        .cipher()?
+        .chunk_aead(key)
+        .ok_or_else(|| RfcError::Encryption(String::from("ChaCha20 has no AEAD")))
+}
+
+/// Encrypts or decrypts an age file. Files are encrypted to `recipients` and decrypted
+/// with `identities`. The codec is `Encoding::Plain` for binary files or
+/// `Encoding::Armor` for armored ones; `Encoding::Auto` detects the armor when decrypting.
+pub fn core_age<R, W>(
+    decrypt: bool,
+    recipients: &[Recipient],
+    identities: &[Identity],
+    input: R,
+    input_len: Option<usize>,
+    output: W,
+    codec: Encoding,
+) -> Result<usize, RfcError>
+where
+    R: Read,
+    W: Write,
+{
+    match (decrypt, codec) {
+        (_, Encoding::Plain | Encoding::Armor) | (true, Encoding::Auto) => {}
+        (_, codec) => {
+            return Err(RfcError::Encoding(format!(
+                "age files cannot be encoded as {}",
+                codec
+            )))
+        }
+    }
+
+    let bytes = buf::read_bytes(input, input_len)?;
+    let bytes = match decrypt {
+        true => decrypt_age(&bytes, identities)?,
+        false => encrypt_age(&bytes, recipients, codec == Encoding::Armor)?,
+    };
+
+    buf::write_bytes(output, bytes)
+}
+
+/// Encrypts `plaintext` to `recipients`, optionally armored.
+pub fn encrypt_age(
+    plaintext: &[u8],
+    recipients: &[Recipient],
+    armored: bool,
+) -> Result<Vec<u8>, RfcError> {
+    keys::check_recipients(recipients)?;
+
+    let mut file_key = [0u8; FILE_KEY_SIZE];
+    OsRng.fill_bytes(&mut file_key);
+    let mut nonce = [0u8; NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+
+    let stanzas = recipients
+        .iter()
+        .map(|r| r.wrap(&file_key))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut bytes = write_header(&stanzas, &file_key);
+    bytes.extend_from_slice(&nonce);
+    bytes.extend_from_slice(&encrypt_payload(&file_key, &nonce, plaintext)?);
+
+    match armored {
+        true => Ok(armor::armor(&bytes)),
+        false => Ok(bytes),
+    }
+}
+
+/// Decrypts a binary or armored age file with the first of `identities` that matches
+/// a stanza.
+pub fn decrypt_age(bytes: &[u8], identities: &[Identity]) -> Result<Vec<u8>, RfcError> {
+    let dearmored;
+    let bytes = match bytes.starts_with(VERSION_PREFIX) {
+        true => bytes,
+        false => {
+            dearmored = armor::dearmor(bytes)?;
+            dearmored.as_slice()
+        }
+    };
+
+    let header = Header::parse(bytes)?;
+    if header.stanzas.len() > 1 && header.stanzas.iter().any(|s| s.tag == "scrypt") {
+        return Err(header_error("scrypt stanza must be the only stanza"));
+    }
+
+    let file_key = unwrap_file_key(&header, identities)?.ok_or(RfcError::WrongKey)?;
+    let mac = header_mac(&file_key, &bytes[..header.mac_len]);
+    if !bool::from(mac.ct_eq(&header.mac)) {
+        return Err(RfcError::Tampered(String::from("age header MAC mismatch")));
+    }
+
+    let Some((nonce, payload)) = bytes[header.len..].split_at_checked(NONCE_SIZE) else {
+        return Err(header_error("missing payload nonce"));
+    };
+
+    decrypt_payload(&file_key, nonce, payload)
+}
+
+fn unwrap_file_key(header: &Header, identities: &[Identity]) -> Result<Option<Vec<u8>>, RfcError> {
+    for identity in identities {
+        for stanza in &header.stanzas {
+            if let Some(file_key) = identity.unwrap(stanza)? {
+                return Ok(Some(file_key));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{core_age, decrypt_age, encrypt_age, Identity, Recipient};
+    use crate::rfc::encoding::Encoding;
+    use crate::rfc::error::RfcError;
+
+    use sha2::{Digest, Sha256};
+
+    use std::path::Path;
+
+    /// Runs the age test vectors of tests/fixtures/age/testkit
+    #[test]
+    fn test_age_testkit() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/age/testkit");
+        let mut failures = Vec::new();
+
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let contents = std::fs::read(&path).unwrap();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+
+            let split = contents.windows(2).position(|w| w == b"\n\n").unwrap();
+            let (meta, file) = (&contents[..split], &contents[split + 2..]);
+
+            let mut expect = "";
+            let mut payload = "";
+            let mut identities = Vec::new();
+            for line in std::str::from_utf8(meta).unwrap().lines() {
+                let (key, value) = line.split_once(": ").unwrap();
+                match key {
+                    "expect" => expect = value,
+                    "payload" => payload = value,
+                    "identity" => identities.push(value.parse::<Identity>().unwrap()),
+                    "passphrase" => identities.push(Identity::Scrypt(value.as_bytes().to_vec())),
+                    _ => {}
+                }
+            }
+
+            let result = decrypt_age(file, &identities);
+            let ok = match (expect, &result) {
+                ("success", Ok(plaintext)) => hex::encode(Sha256::digest(plaintext)) == payload,
+                ("no match", Err(RfcError::WrongKey)) => true,
+                ("HMAC failure", Err(RfcError::Tampered(_))) => true,
+                ("header failure", Err(RfcError::Deserialize(_))) => true,
+                ("payload failure", Err(RfcError::Tampered(_) | RfcError::Truncated(_))) => true,
+                ("armor failure", Err(RfcError::Encoding(_))) => true,
+                _ => false,
+            };
+
+            if !ok {
+                failures.push(format!(
+                    "{}: expected {}, got {:?}",
+                    name,
+                    expect,
+                    result.err()
+                ));
+            }
+        }
+
+        assert!(failures.is_empty(), "{:#?}", failures);
+    }
+
+    #[test]
+    fn test_age_round_trip() {
+        let plaintext: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+
+        let alice = Identity::generate();
+        let bob = Identity::generate();
+        let recipients = [alice.to_public().unwrap(), bob.to_public().unwrap()];
+
+        for armored in [false, true] {
+            let encrypted = encrypt_age(&plaintext, &recipients, armored).unwrap();
+            assert_eq!(encrypted.starts_with(b"-----BEGIN"), armored);
+
+            for identity in [&alice, &bob] {
+                let decrypted = decrypt_age(&encrypted, std::slice::from_ref(identity)).unwrap();
+                assert_eq!(decrypted, plaintext);
+            }
+
+            assert!(matches!(
+                decrypt_age(&encrypted, &[Identity::generate()]),
+                Err(RfcError::WrongKey)
+            ));
+        }
+
+        // Passphrases, with a low work factor to keep the test fast
+        let recipient = Recipient::Scrypt(b"passphrase".to_vec(), 10);
+        let mut encrypted = Vec::new();
+        core_age(
+            false,
+            &[recipient],
+            &[],
+            &b"age"[..],
+            None,
+            &mut encrypted,
+            Encoding::Armor,
+        )
+        .unwrap();
+
+        let mut decrypted = Vec::new();
+        core_age(
+            true,
+            &[],
+            &[alice, Identity::Scrypt(b"passphrase".to_vec())],
+            encrypted.as_slice(),
+            None,
+            &mut decrypted,
+            Encoding::Auto,
+        )
+        .unwrap();
+        assert_eq!(decrypted, b"age");
+    }
+
+    #[test]
+    fn test_age_errors() {
+        let identities = [Identity::generate()];
+        let recipients = [identities[0].to_public().unwrap()];
+        let passphrase = Recipient::Scrypt(b"passphrase".to_vec(), 10);
+
+        assert!(encrypt_age(b"age", &[], false).is_err());
+        assert!(encrypt_age(b"age", &[recipients[0].clone(), passphrase], false).is_err());
+
+        let encrypted = encrypt_age(b"age", &recipients, false).unwrap();
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            decrypt_age(&tampered, &identities),
+            Err(RfcError::Tampered(_))
+        ));
+
+        assert!(matches!(
+            decrypt_age(&encrypted[..encrypted.len() - 5], &identities),
+            Err(RfcError::Truncated(_))
+        ));
+
+        assert!(core_age(
+            false,
+            &recipients,
+            &[],
+            &b""[..],
+            None,
+            Vec::new(),
+            Encoding::Hex
+        )
+        .is_err());
+    }
+}
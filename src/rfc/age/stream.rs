@@ -0,0 +1,157 @@
+//! The age payload: ChaCha20-Poly1305 in the STREAM construction, with 64 KiB chunks.
+//!
+//! Chunk nonces are an 11-byte big-endian counter followed by a flag byte, which is 1 for
+//! the last chunk only, so that truncating the payload at a chunk boundary is detected.
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use super::chacha20_poly1305;
+use crate::rfc::cipher::ChunkAead;
+use crate::rfc::error::RfcError;
+
+/// Size of plaintext chunks
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Size of the Poly1305 tag of each chunk
+const TAG_SIZE: usize = 16;
+
+/// Size of the random nonce the payload key is derived with
+pub(super) const NONCE_SIZE: usize = 16;
+
+/// Encrypts `plaintext` with the payload key derived from `file_key` and `nonce`.
+pub(super) fn encrypt_payload(
+    file_key: &[u8],
+    nonce: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, RfcError> {
+    let cipher = payload_cipher(file_key, nonce)?;
+
+    // Empty plaintext is a single, empty last chunk
+    let chunks: Vec<&[u8]> = match plaintext.is_empty() {
+        true => vec![&[]],
+        false => plaintext.chunks(CHUNK_SIZE).collect(),
+    };
+
+    let mut payload = Vec::with_capacity(plaintext.len() + chunks.len() * TAG_SIZE);
+    for (counter, chunk) in chunks.iter().enumerate() {
+        let last = counter + 1 == chunks.len();
+        let sealed = cipher.seal(&chunk_nonce(counter, last), &[], chunk)?;
+
+        payload.extend_from_slice(&sealed);
+    }
+
+    Ok(payload)
+}
+
+/// Decrypts and authenticates `payload`, which must end with the last chunk.
+pub(super) fn decrypt_payload(
+    file_key: &[u8],
+    nonce: &[u8],
+    payload: &[u8],
+) -> Result<Vec<u8>, RfcError> {
+    if payload.is_empty() {
+        return Err(RfcError::Truncated(String::from(
+            "age payload has no chunks",
+        )));
+    }
+
+    let cipher = payload_cipher(file_key, nonce)?;
+    let chunks: Vec<&[u8]> = payload.chunks(CHUNK_SIZE + TAG_SIZE).collect();
+
+    let mut plaintext = Vec::with_capacity(payload.len());
+    for (counter, chunk) in chunks.iter().enumerate() {
+        let last = counter + 1 == chunks.len();
+
+        if chunk.len() < TAG_SIZE {
+            return Err(RfcError::Truncated(format!(
+                "age payload chunk {} is shorter than its tag",
+                counter
+            )));
+        }
+
+        let decrypted = match cipher.open(&chunk_nonce(counter, last), &[], chunk) {
+            Ok(decrypted) => decrypted,
+            // A full chunk that is not flagged as last was followed by a missing chunk
+            Err(_)
+                if last
+                    && cipher
+                        .open(&chunk_nonce(counter, false), &[], chunk)
+                        .is_ok() =>
+            {
+                return Err(RfcError::Truncated(String::from(
+                    "age payload is missing its last chunk",
+                )))
+            }
+            Err(_) => {
+                return Err(RfcError::Tampered(format!(
+                    "age payload chunk {} failed authentication",
+                    counter
+                )))
+            }
+        };
+
+        // Only empty payloads may end with an empty chunk
+        if last && counter > 0 && decrypted.is_empty() {
+            return Err(RfcError::Tampered(String::from(
+                "age payload ends with an empty chunk",
+            )));
+        }
+
+        plaintext.extend_from_slice(&decrypted);
+    }
+
+    Ok(plaintext)
+}
+
+fn payload_cipher(file_key: &[u8], nonce: &[u8]) -> Result<Box<dyn ChunkAead>, RfcError> {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(nonce), file_key)
+        .expand(b"payload", &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+
+    chacha20_poly1305(&key)
+}
+
+fn chunk_nonce(counter: usize, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[3..11].copy_from_slice(&(counter as u64).to_be_bytes());
+    nonce[11] = u8::from(last);
+
+    nonce
+}
+
+#[test]
+fn test_stream() {
+    let file_key = [1u8; 16];
+    let nonce = [2u8; NONCE_SIZE];
+
+    for len in [
+        0,
+        1,
+        CHUNK_SIZE - 1,
+        CHUNK_SIZE,
+        CHUNK_SIZE + 1,
+        2 * CHUNK_SIZE,
+    ] {
+        let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        let payload = encrypt_payload(&file_key, &nonce, &plaintext).unwrap();
+        assert_eq!(
+            payload.len(),
+            len + len.div_ceil(CHUNK_SIZE).max(1) * TAG_SIZE
+        );
+        assert_eq!(
+            decrypt_payload(&file_key, &nonce, &payload).unwrap(),
+            plaintext
+        );
+
+        if len > CHUNK_SIZE {
+            // Dropping the last chunk
+            assert!(matches!(
+                decrypt_payload(&file_key, &nonce, &payload[..CHUNK_SIZE + TAG_SIZE]),
+                Err(RfcError::Truncated(_))
+            ));
+        }
+    }
+
+    assert!(decrypt_payload(&file_key, &nonce, &[]).is_err());
+}
@@ -0,0 +1,493 @@
+//! tokio `AsyncWrite`/`AsyncRead` counterparts of `EncryptWriter` and `DecryptReader`,
+//! enabled with the `tokio` feature.
+//!
+//! The adapters write and read the same seekable chunk format, one chunk at a time. Key
+//! derivation and the sealing and opening of chunks run on tokio's blocking pool with
+//! `spawn_blocking`, so the adapters must be polled within a tokio runtime.
+use std::future::Future;
+use std::io::{self, Cursor, Read};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::task::{spawn_blocking, JoinError, JoinHandle};
+
+use super::builder::{Decryptor, Encryptor};
+use super::error::RfcError;
+use super::seekable::{header_size, Chunks, DEFAULT_CHUNK_SIZE, FIXED_HEADER_SIZE};
+use super::stream::check_options;
+use super::{KdfId, Mode};
+
+enum WriteState {
+    /// Nothing has been written yet, and the key is yet to be derived
+    Start,
+    /// The cipher key is being derived
+    Deriving(JoinHandle<Result<Chunks, RfcError>>),
+    /// Plaintext is being written into the chunk
+    Filling,
+    /// A chunk is being sealed, the last one if set
+    Sealing(JoinHandle<Result<Vec<u8>, RfcError>>, bool),
+    /// The header or a sealed chunk is being written to the inner writer, from the
+    /// position. It is the last chunk if set.
+    Writing(Vec<u8>, usize, bool),
+    /// The last chunk has been written
+    Done,
+}
+
+/// AsyncEncryptWriter encrypts everything written to it into the seekable format, and
+/// writes sealed chunks to the inner writer as they fill up.
+///
+/// Like `EncryptWriter`, the last chunk is only written on `shutdown`. A writer dropped
+/// before `shutdown` writes nothing more, and leaves truncated output that fails to decrypt.
+pub struct AsyncEncryptWriter<W> {
+    inner: W,
+    key: Vec<u8>,
+    mode: Mode,
+    kdf: KdfId,
+    chunks: Option<Arc<Chunks>>,
+    /// Index of the chunk being filled
+    index: u64,
+    /// Plaintext of the chunk being filled
+    chunk: Vec<u8>,
+    state: WriteState,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncEncryptWriter<W> {
+    /// Returns a writer encrypting with `key` and the default `Encryptor` options.
+    pub fn new<K: AsRef<[u8]>>(inner: W, key: K) -> Result<Self, RfcError> {
+        Self::with_encryptor(inner, Encryptor::new().key(key))
+    }
+
+    /// Returns a writer encrypting with the cipher, KDF and key of `encryptor`. Other
+    /// options, including compression and encodings, are rejected.
+    pub fn with_encryptor(inner: W, encryptor: Encryptor) -> Result<Self, RfcError> {
+        encryptor.check()?;
+        let options = encryptor.options()?;
+        check_options(&options)?;
+
+        Ok(Self {
+            inner,
+            key: options.key,
+            mode: options.mode.expect("encryptors always have a cipher"),
+            kdf: options.kdf,
+            chunks: None,
+            index: 0,
+            chunk: Vec::new(),
+            state: WriteState::Start,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn seal(&mut self, last: bool) {
+        let chunks = Arc::clone(self.chunks.as_ref().expect("no chunks"));
+        let chunk = std::mem::take(&mut self.chunk);
+        let index = self.index;
+
+        self.index += 1;
+        self.state = WriteState::Sealing(
+            spawn_blocking(move || chunks.seal(index, last, &chunk)),
+            last,
+        );
+    }
+
+    /// Drives pending work, until more plaintext can be written or the last chunk is.
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            match &mut self.state {
+                WriteState::Start => {
+                    let (key, mode, kdf) = (std::mem::take(&mut self.key), self.mode, self.kdf);
+                    self.state = WriteState::Deriving(spawn_blocking(move || {
+                        Chunks::create(&key, mode, kdf, DEFAULT_CHUNK_SIZE)
+                    }));
+                }
+
+                WriteState::Deriving(handle) => {
+                    let chunks = ready!(poll_join(handle, cx)?).map_err(io::Error::other)?;
+
+                    self.state = WriteState::Writing(chunks.header().to_vec(), 0, false);
+                    self.chunks = Some(Arc::new(chunks));
+                }
+
+                WriteState::Sealing(handle, last) => {
+                    let last = *last;
+                    let sealed = ready!(poll_join(handle, cx)?).map_err(io::Error::other)?;
+
+                    self.state = WriteState::Writing(sealed, 0, last);
+                }
+
+                WriteState::Writing(bytes, pos, last) => {
+                    while *pos < bytes.len() {
+                        let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &bytes[*pos..]))?;
+                        if n == 0 {
+                            return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                        }
+                        *pos += n;
+                    }
+
+                    self.state = match last {
+                        true => WriteState::Done,
+                        false => WriteState::Filling,
+                    };
+                }
+
+                WriteState::Filling | WriteState::Done => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncEncryptWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bytes: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+
+        if matches!(this.state, WriteState::Done) {
+            return Poll::Ready(Err(io::Error::other(RfcError::Encryption(String::from(
+                "write after shutdown",
+            )))));
+        }
+        if bytes.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        // A full chunk is only sealed once more plaintext is written, since the last
+        // chunk is sealed differently
+        let chunk_size = this.chunks.as_ref().expect("no chunks").chunk_size();
+        if this.chunk.len() == chunk_size {
+            this.seal(false);
+            ready!(this.poll_pending(cx))?;
+        }
+
+        let n = std::cmp::min(bytes.len(), chunk_size - this.chunk.len());
+        this.chunk.extend_from_slice(&bytes[..n]);
+
+        Poll::Ready(Ok(n))
+    }
+
+    /// Writes sealed chunks and flushes the inner writer. The chunk being filled is not
+    /// written before shutdown.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    /// Seals and writes the last chunk, and shuts the inner writer down.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+
+        if matches!(this.state, WriteState::Filling) {
+            this.seal(true);
+            ready!(this.poll_pending(cx))?;
+        }
+
+        ready!(Pin::new(&mut this.inner).poll_flush(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+enum ReadState {
+    /// The header is being read
+    Header,
+    /// The cipher key is being derived
+    Deriving(JoinHandle<Result<Chunks, RfcError>>),
+    /// The next sealed chunk is being read
+    Filling,
+    /// A chunk is being opened, the last one if set
+    Opening(JoinHandle<Result<Vec<u8>, RfcError>>, bool),
+    /// Plaintext of an opened chunk is being yielded, the last one if set
+    Yielding(Cursor<Vec<u8>>, bool),
+}
+
+/// AsyncDecryptReader yields the plaintext of a seekable rfc file read from the inner
+/// reader, one authenticated chunk at a time.
+///
+/// Errors are returned as `io::Error`s wrapping the `RfcError`, see `RfcError::from_io`.
+pub struct AsyncDecryptReader<R> {
+    inner: R,
+    key: Vec<u8>,
+    /// Cipher the input must have been encrypted with, if set
+    mode: Option<Mode>,
+    chunks: Option<Arc<Chunks>>,
+    /// Index of the next chunk to open
+    index: u64,
+    /// Bytes read from the inner reader and not yet opened
+    buf: Vec<u8>,
+    eof: bool,
+    state: ReadState,
+}
+
+impl<R: AsyncRead + Unpin> AsyncDecryptReader<R> {
+    /// Returns a reader decrypting with `key` and the default `Decryptor` options.
+    pub fn new<K: AsRef<[u8]>>(inner: R, key: K) -> Result<Self, RfcError> {
+        Self::with_decryptor(inner, Decryptor::new().key(key))
+    }
+
+    /// Returns a reader decrypting with the key of `decryptor`, checking the cipher if it
+    /// is set. Other options, including decompression and encodings, are rejected.
+    pub fn with_decryptor(inner: R, decryptor: Decryptor) -> Result<Self, RfcError> {
+        decryptor.check()?;
+        let options = decryptor.options()?;
+        check_options(&options)?;
+
+        Ok(Self {
+            inner,
+            key: options.key,
+            mode: options.mode,
+            chunks: None,
+            index: 0,
+            buf: Vec::new(),
+            eof: false,
+            state: ReadState::Header,
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads from the inner reader until `buf` holds `len` bytes or EOF is reached.
+    fn poll_fill(&mut self, cx: &mut Context<'_>, len: usize) -> Poll<io::Result<()>> {
+        while self.buf.len() < len && !self.eof {
+            let filled = self.buf.len();
+            self.buf.resize(len, 0);
+
+            let mut read_buf = ReadBuf::new(&mut self.buf[filled..]);
+            let polled = Pin::new(&mut self.inner).poll_read(cx, &mut read_buf);
+            let n = read_buf.filled().len();
+            self.buf.truncate(filled + n);
+
+            ready!(polled)?;
+            self.eof = n == 0;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncDecryptReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            match &mut this.state {
+                ReadState::Header => {
+                    ready!(this.poll_fill(cx, FIXED_HEADER_SIZE))?;
+                    // Short input is reported as truncated by Chunks::open
+                    let size = match this.buf.first_chunk::<FIXED_HEADER_SIZE>() {
+                        Some(fixed) => header_size(fixed).map_err(io::Error::other)?,
+                        None => this.buf.len(),
+                    };
+                    ready!(this.poll_fill(cx, size))?;
+
+                    let header: Vec<u8> = this.buf.drain(..size.min(this.buf.len())).collect();
+                    let key = std::mem::take(&mut this.key);
+                    this.state = ReadState::Deriving(spawn_blocking(move || {
+                        Chunks::open(&header[..], &key)
+                    }));
+                }
+
+                ReadState::Deriving(handle) => {
+                    let chunks = ready!(poll_join(handle, cx)?).map_err(io::Error::other)?;
+                    match this.mode {
+                        Some(mode) if mode != chunks.mode() => {
+                            return Poll::Ready(Err(io::Error::other(RfcError::CipherMismatch(
+                                chunks.mode().to_string(),
+                                mode.to_string(),
+                            ))))
+                        }
+                        _ => {}
+                    }
+
+                    this.chunks = Some(Arc::new(chunks));
+                    this.state = ReadState::Filling;
+                }
+
+                ReadState::Filling => {
+                    // Reading a byte past the chunk tells whether it is the last one
+                    let chunks = Arc::clone(this.chunks.as_ref().expect("no chunks"));
+                    let sealed_chunk_size = chunks.sealed_chunk_size();
+                    ready!(this.poll_fill(cx, sealed_chunk_size + 1))?;
+
+                    let last = this.buf.len() <= sealed_chunk_size;
+                    let sealed: Vec<u8> = match last {
+                        true => std::mem::take(&mut this.buf),
+                        false => this.buf.drain(..sealed_chunk_size).collect(),
+                    };
+                    let index = this.index;
+
+                    this.index += 1;
+                    this.state = ReadState::Opening(
+                        spawn_blocking(move || chunks.open_chunk(index, last, &sealed)),
+                        last,
+                    );
+                }
+
+                ReadState::Opening(handle, last) => {
+                    let last = *last;
+                    let chunk = ready!(poll_join(handle, cx)?).map_err(io::Error::other)?;
+
+                    this.state = ReadState::Yielding(Cursor::new(chunk), last);
+                }
+
+                ReadState::Yielding(plaintext, last) => {
+                    let n = plaintext.read(buf.initialize_unfilled())?;
+                    buf.advance(n);
+
+                    if n > 0 || buf.remaining() == 0 || *last {
+                        return Poll::Ready(Ok(()));
+                    }
+                    this.state = ReadState::Filling;
+                }
+            }
+        }
+    }
+}
+
+/// Polls a blocking task, resuming its panic if it panicked.
+fn poll_join<T>(handle: &mut JoinHandle<T>, cx: &mut Context<'_>) -> io::Result<Poll<T>> {
+    match Pin::new(handle).poll(cx) {
+        Poll::Ready(Ok(value)) => Ok(Poll::Ready(value)),
+        Poll::Ready(Err(err)) => Err(join_error(err)),
+        Poll::Pending => Ok(Poll::Pending),
+    }
+}
+
+fn join_error(err: JoinError) -> io::Error {
+    match err.try_into_panic() {
+        Ok(panic) => std::panic::resume_unwind(panic),
+        Err(err) => io::Error::other(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AsyncDecryptReader, AsyncEncryptWriter};
+    use crate::rfc::seekable::DEFAULT_CHUNK_SIZE;
+    use crate::rfc::{DecryptReader, Decryptor, Encryptor, Mode, RfcError};
+
+    use std::io::Read;
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_async_stream() {
+        let source = include_bytes!("./async_stream.rs");
+        let plaintext = source.repeat(DEFAULT_CHUNK_SIZE / source.len() * 3);
+
+        // The duplex buffer is smaller than a chunk, so both ends must make progress
+        let (client, server) = duplex(1024);
+
+        let encryptor = Encryptor::new().cipher(Mode::AES128).key("key");
+        let decryptor = Decryptor::new().cipher(Mode::AES128).key("key");
+
+        let write = async {
+            let mut writer = AsyncEncryptWriter::with_encryptor(client, encryptor).unwrap();
+            for chunk in plaintext.chunks(1000) {
+                writer.write_all(chunk).await.unwrap();
+            }
+            writer.shutdown().await.unwrap();
+        };
+
+        let read = async {
+            let mut reader = AsyncDecryptReader::with_decryptor(server, decryptor).unwrap();
+            let mut decrypted = Vec::new();
+            reader.read_to_end(&mut decrypted).await.unwrap();
+            decrypted
+        };
+
+        let (_, decrypted) = tokio::join!(write, read);
+        assert_eq!(plaintext, decrypted);
+    }
+
+    #[tokio::test]
+    async fn test_async_stream_format() {
+        // The async adapters write and read the format of the blocking ones
+        for len in [0, 1, DEFAULT_CHUNK_SIZE, 2 * DEFAULT_CHUNK_SIZE + 1] {
+            let plaintext = vec![7u8; len];
+
+            let mut writer = AsyncEncryptWriter::new(Vec::new(), "key").unwrap();
+            writer.write_all(&plaintext).await.unwrap();
+            writer.shutdown().await.unwrap();
+            let ciphertext = writer.into_inner();
+
+            let mut decrypted = Vec::new();
+            DecryptReader::new(&ciphertext[..], "key")
+                .unwrap()
+                .read_to_end(&mut decrypted)
+                .unwrap();
+            assert_eq!(plaintext, decrypted);
+
+            let mut decrypted = Vec::new();
+            AsyncDecryptReader::new(&ciphertext[..], "key")
+                .unwrap()
+                .read_to_end(&mut decrypted)
+                .await
+                .unwrap();
+            assert_eq!(plaintext, decrypted);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_stream_errors() {
+        let plaintext = vec![7u8; 3 * DEFAULT_CHUNK_SIZE / 2];
+
+        let mut writer = AsyncEncryptWriter::new(Vec::new(), "key").unwrap();
+        writer.write_all(&plaintext).await.unwrap();
+        writer.shutdown().await.unwrap();
+        assert!(writer.write_all(b"more").await.is_err());
+        let ciphertext = writer.into_inner();
+
+        let decrypt = |ciphertext: Vec<u8>, key: &'static str| async move {
+            let mut reader = AsyncDecryptReader::new(&ciphertext[..], key).unwrap();
+            let read = reader.read_to_end(&mut Vec::new()).await;
+            read.map_err(RfcError::from_io)
+        };
+
+        assert!(matches!(
+            decrypt(ciphertext.clone(), "wrong key").await,
+            Err(RfcError::WrongKey)
+        ));
+        assert!(matches!(
+            decrypt(ciphertext[..ciphertext.len() - 1].to_vec(), "key").await,
+            Err(RfcError::Tampered(_))
+        ));
+        assert!(matches!(
+            decrypt(ciphertext[..10].to_vec(), "key").await,
+            Err(RfcError::Truncated(_))
+        ));
+        assert!(matches!(
+            decrypt(b"this is not a seekable rfc file".to_vec(), "key").await,
+            Err(RfcError::Deserialize(_))
+        ));
+
+        // Without shutdown, the last chunk is never written
+        let mut writer = AsyncEncryptWriter::new(Vec::new(), "key").unwrap();
+        writer.write_all(&plaintext).await.unwrap();
+        writer.flush().await.unwrap();
+        assert!(decrypt(writer.into_inner(), "key").await.is_err());
+
+        let (client, _) = duplex(64);
+        assert!(AsyncEncryptWriter::with_encryptor(client, Encryptor::new()).is_err());
+        assert!(matches!(
+            AsyncDecryptReader::with_decryptor(
+                &b""[..],
+                Decryptor::new().compress(true).key("key")
+            ),
+            Err(RfcError::NotImplemented(_))
+        ));
+    }
+}
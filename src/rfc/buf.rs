@@ -20,8 +20,7 @@ where
 
     let mut chunks: Vec<[u8; BLOCK_SIZE]> = Vec::with_capacity(chunks);
 
-    let arrays = bytes.as_ref().array_chunks::<BLOCK_SIZE>();
-    let remainder = arrays.remainder();
+    let (arrays, remainder) = bytes.as_ref().as_chunks::<BLOCK_SIZE>();
 
     for chunk in arrays {
         chunks.push(*chunk)
@@ -43,9 +42,7 @@ where
     R: Read,
 {
     let mut buf = Vec::with_capacity(prealloc.unwrap_or(0));
-    let _written = reader
-        .read_to_end(&mut buf)
-        .map_err(|err| RfcError::IoError(err))?;
+    let _written = reader.read_to_end(&mut buf).map_err(RfcError::IoError)?;
 
     buf.truncate(buf.len());
     Ok(buf)
@@ -66,51 +63,296 @@ where
     W: Write,
     T: AsRef<[u8]>,
 {
-    let _ = writer
-        .write_all(data.as_ref())
-        .map_err(|err| RfcError::IoError(err));
-
-    Ok(())
+    writer.write_all(data.as_ref()).map_err(RfcError::IoError)
 }
 
-pub fn write_bytes<W, T>(mut w: W, data: T) -> Result<usize, RfcError>
+/// Writes all of `data` to `w`, also when `w` only accepts part of it per write,
+/// and returns its length.
+pub fn write_bytes<W, T>(w: W, data: T) -> Result<usize, RfcError>
 where
     W: Write,
     T: AsRef<[u8]>,
 {
-    w.write(data.as_ref()).map_err(|err| RfcError::IoError(err))
+    write_to_writer(w, data.as_ref())?;
+    Ok(data.as_ref().len())
+}
+
+#[test]
+fn test_write_bytes() {
+    /// Accepts at most 3 bytes per write, like a pipe or a write over Linux's limit
+    struct Short(Vec<u8>);
+
+    impl Write for Short {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let n = std::cmp::min(buf.len(), 3);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut short = Short(Vec::new());
+    assert_eq!(write_bytes(&mut short, b"hello, world").unwrap(), 12);
+    assert_eq!(short.0, b"hello, world");
 }
 
 // Fills buf with bytes
 pub fn fill(mut buf: &mut [u8], bytes: &[u8]) {
-    buf.write(bytes).expect("filling bytes failed");
+    buf.write_all(bytes).expect("filling bytes failed");
 }
 
 pub fn read_file<P>(filename: P) -> Result<Vec<u8>, RfcError>
 where
     P: AsRef<std::path::Path>,
 {
-    std::fs::read(filename).map_err(|err| RfcError::IoError(err))
+    std::fs::read(filename).map_err(RfcError::IoError)
+}
+
+/// Policy for handling an output file that already exists
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverwritePolicy {
+    /// Refuse to touch an existing output, returning `RfcError::OutputExists`
+    Refuse,
+    /// Truncate and overwrite an existing output
+    Force,
+    /// Leave an existing output untouched without returning an error
+    NoClobber,
 }
 
-pub fn open_file<P>(filename: P, write: bool) -> Result<std::fs::File, RfcError>
+/// Opens `filename` read-only, so that a mistyped input filename never creates a new file.
+pub fn open_input<P>(filename: P) -> Result<std::fs::File, RfcError>
 where
     P: AsRef<std::path::Path>,
 {
-    std::fs::OpenOptions::new()
-        .create(write)
-        .write(write)
-        .read(true)
-        .open(filename)
-        .map_err(|err| RfcError::IoError(err))
+    std::fs::File::open(filename).map_err(RfcError::IoError)
+}
+
+/// Output file, written to a temporary file in the same directory and only moved into place
+/// by `persist`. Outputs dropped without `persist`, e.g. on a wrong key or tampered input,
+/// leave nothing behind.
+pub struct Output {
+    file: tempfile::NamedTempFile,
+    path: std::path::PathBuf,
+    policy: OverwritePolicy,
+}
+
+impl Output {
+    /// Returns the temporary file, e.g. to set its permissions before `persist`.
+    pub fn as_file(&self) -> &std::fs::File {
+        self.file.as_file()
+    }
+
+    /// Moves the output into place according to its policy.
+    pub fn persist(self) -> Result<(), RfcError> {
+        let result = match self.policy {
+            OverwritePolicy::Force => self.file.persist(&self.path).map(|_| ()),
+            // persist_noclobber checks for existence and moves the file atomically
+            OverwritePolicy::Refuse | OverwritePolicy::NoClobber => {
+                self.file.persist_noclobber(&self.path).map(|_| ())
+            }
+        };
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(err) if err.error.kind() == std::io::ErrorKind::AlreadyExists => {
+                match self.policy {
+                    OverwritePolicy::NoClobber => Ok(()),
+                    _ => Err(RfcError::OutputExists(self.path.display().to_string())),
+                }
+            }
+            Err(err) => Err(RfcError::IoError(err.error)),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Permissions of new output files, before the umask is applied
+pub const OUTPUT_MODE: u32 = 0o666;
+
+/// Permissions of output files only readable by their owner, e.g. secret keys
+pub const SECRET_MODE: u32 = 0o600;
+
+/// Opens `filename` for writing according to `policy`, see `Output`. On Unix, the file is
+/// created with permissions `mode`, e.g. `OUTPUT_MODE`, so that it is never readable by others
+/// if `mode` does not allow it. Returns `None` if the file exists and `policy` is
+/// `OverwritePolicy::NoClobber`.
+pub fn open_output<P>(
+    filename: P,
+    policy: OverwritePolicy,
+    mode: u32,
+) -> Result<Option<Output>, RfcError>
+where
+    P: AsRef<std::path::Path>,
+{
+    let path = filename.as_ref();
+    if policy != OverwritePolicy::Force && path.exists() {
+        return match policy {
+            OverwritePolicy::NoClobber => Ok(None),
+            _ => Err(RfcError::OutputExists(path.display().to_string())),
+        };
+    }
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => std::path::Path::new("."),
+    };
+    let prefix = format!(
+        ".{}.",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    );
+
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(&prefix).suffix(".tmp");
+    // Outputs get the permissions asked for, not the owner-only ones of temp files
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(std::fs::Permissions::from_mode(mode));
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let file = builder.tempfile_in(dir).map_err(RfcError::IoError)?;
+    Ok(Some(Output {
+        file,
+        path: path.to_path_buf(),
+        policy,
+    }))
+}
+
+/// Returns `RfcError::SameFile` if `input` and `output` are the same file, also through
+/// hard links. A non-existent output can never be the input, so it is always accepted.
+pub fn check_same_file<P, Q>(input: P, output: Q) -> Result<(), RfcError>
+where
+    P: AsRef<std::path::Path>,
+    Q: AsRef<std::path::Path>,
+{
+    let output_metadata = match std::fs::metadata(&output) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(RfcError::IoError(err)),
+    };
+    let input_metadata = std::fs::metadata(&input).map_err(RfcError::IoError)?;
+
+    // Files are the same if they have the same device and inode, whatever their paths
+    #[cfg(unix)]
+    let same = {
+        use std::os::unix::fs::MetadataExt;
+        (input_metadata.dev(), input_metadata.ino())
+            == (output_metadata.dev(), output_metadata.ino())
+    };
+    #[cfg(not(unix))]
+    let same = {
+        let _ = (input_metadata, output_metadata);
+        std::fs::canonicalize(&input).map_err(RfcError::IoError)?
+            == std::fs::canonicalize(&output).map_err(RfcError::IoError)?
+    };
+
+    match same {
+        true => Err(RfcError::SameFile(output.as_ref().display().to_string())),
+        false => Ok(()),
+    }
 }
 
 #[test]
-fn test_open_file() {
+fn test_open_input() {
     vec!["./Cargo.toml", "./Cargo.lock"]
         .into_iter()
-        .for_each(|filename| {
-            assert!(open_file(filename, true).is_ok());
-            assert!(open_file(filename, false).is_ok());
-        })
+        .for_each(|filename| assert!(open_input(filename).is_ok()));
+
+    let missing = "./no_such_input";
+    assert!(open_input(missing).is_err());
+    assert!(!std::path::Path::new(missing).exists());
+}
+
+#[test]
+fn test_open_output() {
+    let filename = "./Cargo.toml";
+    assert!(matches!(
+        open_output(filename, OverwritePolicy::Refuse, OUTPUT_MODE),
+        Err(RfcError::OutputExists(_))
+    ));
+    assert!(matches!(
+        open_output(filename, OverwritePolicy::NoClobber, OUTPUT_MODE),
+        Ok(None)
+    ));
+
+    // Nothing is written until the output is persisted
+    let tmp_filename = "./tmp_open_output";
+    let mut outfile = open_output(tmp_filename, OverwritePolicy::Refuse, OUTPUT_MODE)
+        .unwrap()
+        .unwrap();
+    outfile.write_all(b"new content").unwrap();
+    assert!(!std::path::Path::new(tmp_filename).exists());
+    drop(outfile);
+    assert!(!std::path::Path::new(tmp_filename).exists());
+
+    std::fs::write(tmp_filename, b"some old content").expect("failed to write tmp file");
+    let mut outfile = open_output(tmp_filename, OverwritePolicy::Force, OUTPUT_MODE)
+        .unwrap()
+        .unwrap();
+    outfile.write_all(b"new content").unwrap();
+    let old = std::fs::read(tmp_filename);
+    let persisted = outfile.persist();
+    let new = std::fs::read(tmp_filename);
+    std::fs::remove_file(tmp_filename).expect("failed to remove tmp file");
+
+    assert_eq!(old.unwrap(), b"some old content");
+    assert!(persisted.is_ok());
+    assert_eq!(new.unwrap(), b"new content");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_open_output_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp_filename = "./tmp_open_output_mode";
+    let outfile = open_output(tmp_filename, OverwritePolicy::Refuse, SECRET_MODE)
+        .unwrap()
+        .unwrap();
+    // The file is never readable by others, not even before it is persisted
+    let tmp_mode = outfile.as_file().metadata().unwrap().permissions().mode();
+    outfile.persist().unwrap();
+    let mode = std::fs::metadata(tmp_filename)
+        .unwrap()
+        .permissions()
+        .mode();
+    std::fs::remove_file(tmp_filename).expect("failed to remove tmp file");
+
+    assert_eq!(tmp_mode & 0o777, SECRET_MODE);
+    assert_eq!(mode & 0o777, SECRET_MODE);
+}
+
+#[test]
+fn test_check_same_file() {
+    assert!(check_same_file("./Cargo.toml", "./Cargo.lock").is_ok());
+    assert!(check_same_file("./Cargo.toml", "./no_such_output").is_ok());
+    assert!(matches!(
+        check_same_file("./Cargo.toml", "./src/../Cargo.toml"),
+        Err(RfcError::SameFile(_))
+    ));
+
+    let tmp_filename = "./tmp_check_same_file";
+    let link_filename = "./tmp_check_same_file_link";
+    std::fs::write(tmp_filename, b"content").expect("failed to write tmp file");
+    let linked = std::fs::hard_link(tmp_filename, link_filename);
+    let checked = check_same_file(tmp_filename, link_filename);
+    std::fs::remove_file(tmp_filename).expect("failed to remove tmp file");
+    let _ = std::fs::remove_file(link_filename);
+
+    linked.expect("failed to link tmp file");
+    assert!(matches!(checked, Err(RfcError::SameFile(_))));
 }
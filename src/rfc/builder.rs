@@ -0,0 +1,341 @@
+use std::io::{Read, Write};
+
+use ed25519_dalek::{SigningKey, VerifyingKey};
+
+use super::encoding::Encoding;
+use super::error::RfcError;
+use super::kdf::KdfId;
+use super::metadata::Metadata;
+use super::padding::Padding;
+use super::sign::Signing;
+use super::stream::{DecryptReader, EncryptWriter};
+use super::{Mode, Options};
+
+/// Encryptor encrypts files with the options set by its builder methods.
+///
+/// ```
+/// use rfc::{Encoding, Encryptor, Mode};
+///
+/// let encryptor = Encryptor::new()
+///     .cipher(Mode::AES256)
+///     .compress(true)
+///     .encoding(Encoding::B64)
+///     .key(b"my secret key");
+///
+/// let ciphertext = encryptor.encrypt_buf(&b"hello, world"[..]).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Encryptor {
+    mode: Mode,
+    kdf: KdfId,
+    compress: bool,
+    encoding: Encoding,
+    padding: Padding,
+    metadata: Option<Metadata>,
+    signing_key: Option<SigningKey>,
+    key: Option<Vec<u8>>,
+}
+
+impl Default for Encryptor {
+    fn default() -> Self {
+        Self {
+            mode: Mode::AES256,
+            kdf: KdfId::default(),
+            compress: false,
+            encoding: Encoding::Plain,
+            padding: Padding::None,
+            metadata: None,
+            signing_key: None,
+            key: None,
+        }
+    }
+}
+
+impl Encryptor {
+    /// Returns an encryptor using AES-256 and PBKDF2, without compression, encoding or padding.
+    /// A key must be set with `key` before encrypting.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the cipher, defaults to `Mode::AES256`.
+    pub fn cipher(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the key derivation function, defaults to `KdfId::PBKDF2`.
+    pub fn kdf(mut self, kdf: KdfId) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
+    /// Compresses plaintext with LZ4 before encrypting.
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Sets the encoding of the encrypted output, defaults to `Encoding::Plain`.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Pads plaintext to hide its length, defaults to `Padding::None`.
+    pub fn padding(mut self, padding: Padding) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Embeds `metadata` in the encrypted output.
+    pub fn metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Signs the encrypted output with `key`.
+    pub fn sign(mut self, key: SigningKey) -> Self {
+        self.signing_key = Some(key);
+        self
+    }
+
+    /// Sets the key or passphrase to derive the cipher key from.
+    pub fn key<K: AsRef<[u8]>>(mut self, key: K) -> Self {
+        self.key = Some(key.as_ref().to_vec());
+        self
+    }
+
+    /// Encrypts `input` and writes the result to `output`.
+    pub fn encrypt<R, W>(&self, input: R, output: W) -> Result<usize, RfcError>
+    where
+        R: Read,
+        W: Write,
+    {
+        super::core(self.options()?, input, None, output)
+    }
+
+    /// Encrypts `input` and returns the result.
+    pub fn encrypt_buf<R: Read>(&self, input: R) -> Result<Vec<u8>, RfcError> {
+        super::core_buf(self.options()?, input, None).map(|(ciphertext, _)| ciphertext)
+    }
+
+    /// Encrypts `input` without copying it first, e.g. from a memory-mapped file.
+    pub fn encrypt_slice<W: Write>(&self, input: &[u8], output: W) -> Result<usize, RfcError> {
+        super::core_slice(self.options()?, input, output)
+    }
+
+    /// Returns a writer encrypting everything written to it into `inner` in the seekable
+    /// format, see `EncryptWriter`.
+    pub fn writer<W: Write>(self, inner: W) -> Result<EncryptWriter<W>, RfcError> {
+        EncryptWriter::with_encryptor(inner, self)
+    }
+
+    /// Checks that the options are complete, so that errors are reported before encrypting.
+    pub(super) fn check(&self) -> Result<(), RfcError> {
+        if self.encoding == Encoding::Auto {
+            return Err(super::auto_encoding_error());
+        }
+
+        self.get_key().map(|_| ())
+    }
+
+    fn get_key(&self) -> Result<Vec<u8>, RfcError> {
+        get_key(&self.key, Some(self.mode))
+            .ok_or(RfcError::Encryption(String::from("no key to encrypt with")))
+    }
+
+    pub(super) fn options(&self) -> Result<Options, RfcError> {
+        Ok(Options {
+            decrypt: false,
+            key: self.get_key()?,
+            mode: Some(self.mode),
+            kdf: self.kdf,
+            codec: self.encoding,
+            compress: self.compress,
+            padding: self.padding,
+            metadata: self.metadata.clone(),
+            signing: self.signing_key.clone().map(Signing::Sign),
+        })
+    }
+}
+
+/// Decryptor decrypts files with the options set by its builder methods.
+///
+/// ```
+/// use rfc::{Decryptor, Encryptor};
+///
+/// let ciphertext = Encryptor::new().key("passphrase").encrypt_buf(&b"hello"[..]).unwrap();
+/// let plaintext = Decryptor::new().key("passphrase").decrypt_buf(&ciphertext[..]).unwrap();
+///
+/// assert_eq!(plaintext, b"hello");
+/// ```
+#[derive(Clone)]
+pub struct Decryptor {
+    mode: Option<Mode>,
+    compress: bool,
+    encoding: Encoding,
+    verifying_key: Option<VerifyingKey>,
+    key: Option<Vec<u8>>,
+}
+
+impl Default for Decryptor {
+    fn default() -> Self {
+        Self {
+            mode: None,
+            compress: false,
+            encoding: Encoding::Auto,
+            verifying_key: None,
+            key: None,
+        }
+    }
+}
+
+impl Decryptor {
+    /// Returns a decryptor without decompression, detecting the encoding of its input.
+    /// The cipher and KDF are read from the input's header. A key must be set with `key`
+    /// before decrypting.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks that the input was encrypted with `mode`, failing with
+    /// `RfcError::CipherMismatch` otherwise. `Mode::NONE` passes the input through as is.
+    pub fn cipher(mut self, mode: Mode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Decompresses plaintext after decrypting, for input encrypted with compression.
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Sets the encoding of the input, defaults to `Encoding::Auto`.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Verifies the input's embedded signature with `key` before decrypting.
+    pub fn verify(mut self, key: VerifyingKey) -> Self {
+        self.verifying_key = Some(key);
+        self
+    }
+
+    /// Sets the key or passphrase to derive the cipher key from.
+    pub fn key<K: AsRef<[u8]>>(mut self, key: K) -> Self {
+        self.key = Some(key.as_ref().to_vec());
+        self
+    }
+
+    /// Decrypts `input` and writes the plaintext to `output`.
+    pub fn decrypt<R, W>(&self, input: R, output: W) -> Result<usize, RfcError>
+    where
+        R: Read,
+        W: Write,
+    {
+        super::core(self.options()?, input, None, output)
+    }
+
+    /// Decrypts `input` and returns the plaintext.
+    pub fn decrypt_buf<R: Read>(&self, input: R) -> Result<Vec<u8>, RfcError> {
+        self.decrypt_with_metadata(input)
+            .map(|(plaintext, _)| plaintext)
+    }
+
+    /// Decrypts `input` and returns the plaintext, as well as the embedded metadata, if any.
+    pub fn decrypt_with_metadata<R: Read>(
+        &self,
+        input: R,
+    ) -> Result<(Vec<u8>, Option<Metadata>), RfcError> {
+        super::core_buf(self.options()?, input, None)
+    }
+
+    /// Decrypts `input` without copying it first, e.g. from a memory-mapped file.
+    pub fn decrypt_slice<W: Write>(&self, input: &[u8], output: W) -> Result<usize, RfcError> {
+        super::core_slice(self.options()?, input, output)
+    }
+
+    /// Returns a reader yielding the decrypted plaintext of the seekable file `inner`,
+    /// see `DecryptReader`.
+    pub fn reader<R: Read>(self, inner: R) -> Result<DecryptReader<R>, RfcError> {
+        DecryptReader::with_decryptor(inner, self)
+    }
+
+    /// Checks that the options are complete, so that errors are reported before decrypting.
+    pub(super) fn check(&self) -> Result<(), RfcError> {
+        self.get_key().map(|_| ())
+    }
+
+    fn get_key(&self) -> Result<Vec<u8>, RfcError> {
+        get_key(&self.key, self.mode)
+            .ok_or(RfcError::Decryption(String::from("no key to decrypt with")))
+    }
+
+    pub(super) fn options(&self) -> Result<Options, RfcError> {
+        Ok(Options {
+            decrypt: true,
+            key: self.get_key()?,
+            mode: self.mode,
+            codec: self.encoding,
+            compress: self.compress,
+            signing: self.verifying_key.map(Signing::Verify),
+            ..Options::default()
+        })
+    }
+}
+
+/// Returns the key to derive the cipher key from. `Mode::NONE` needs no key.
+fn get_key(key: &Option<Vec<u8>>, mode: Option<Mode>) -> Option<Vec<u8>> {
+    match mode {
+        Some(Mode::NONE) => Some(key.clone().unwrap_or_default()),
+        _ => key.clone(),
+    }
+}
+
+#[test]
+fn test_builder() {
+    let plaintext = include_bytes!("./builder.rs");
+    let signing_key = super::sign::generate_key();
+
+    let encryptor = Encryptor::new()
+        .cipher(Mode::AES128)
+        .kdf(KdfId::PBKDF2)
+        .compress(true)
+        .encoding(Encoding::Hex)
+        .padding(Padding::Padme)
+        .metadata(Metadata::from_path("./Cargo.toml", None).unwrap())
+        .sign(signing_key.clone())
+        .key("my key");
+
+    let decryptor = Decryptor::new()
+        .cipher(Mode::AES128)
+        .compress(true)
+        .verify(signing_key.verifying_key())
+        .key("my key");
+
+    let ciphertext = encryptor.encrypt_buf(&plaintext[..]).unwrap();
+    let (decrypted, metadata) = decryptor.decrypt_with_metadata(&ciphertext[..]).unwrap();
+    assert_eq!(plaintext.as_slice(), decrypted);
+    assert_eq!(metadata.unwrap().filename.as_deref(), Some("Cargo.toml"));
+
+    let mut streamed = Vec::new();
+    encryptor.encrypt(&plaintext[..], &mut streamed).unwrap();
+    let mut decrypted = Vec::new();
+    decryptor.decrypt_slice(&streamed, &mut decrypted).unwrap();
+    assert_eq!(plaintext.as_slice(), decrypted);
+
+    assert!(matches!(
+        decryptor
+            .clone()
+            .key("wrong key")
+            .decrypt_buf(&ciphertext[..]),
+        Err(RfcError::WrongKey)
+    ));
+    assert!(matches!(
+        Encryptor::new().encrypt_buf(&plaintext[..]),
+        Err(RfcError::Encryption(_))
+    ));
+}
@@ -0,0 +1,109 @@
+//! ChaCha20 cipher of rfc's own format, and ChaCha20-Poly1305 for seekable files, streams
+//! and age files.
+//!
+//! The key of each file is derived with a fresh salt and encrypts a single plaintext, so
+//! the nonce of the raw keystream is zero. Its offset is a position in the keystream, so
+//! the plaintext may be encrypted in parts of any length.
+use chacha20::cipher::{KeyIvInit, StreamCipher, StreamCipherSeek};
+use chacha20::ChaCha20;
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit};
+use rayon::prelude::*;
+
+use std::io::Write;
+
+use crate::rfc::cipher::{AeadChunks, ChunkAead, Cipher};
+use crate::rfc::error::RfcError;
+
+const KEY_SIZE: usize = 32;
+
+/// Number of bytes (64 KiB) of keystream applied by each parallel task
+const PAR_BYTES: usize = 64 * 1024;
+
+/// ChaCha20 cipher, with ChaCha20-Poly1305 as its chunk AEAD. It encrypts any length,
+/// so rfc does not zero-fill the plaintext.
+pub struct CipherChacha20 {}
+
+impl Cipher for CipherChacha20 {
+    fn id(&self) -> u8 {
+        3
+    }
+
+    fn name(&self) -> &'static str {
+        "chacha20"
+    }
+
+    fn key_size(&self) -> usize {
+        KEY_SIZE
+    }
+
+    fn block_size(&self) -> usize {
+        1
+    }
+
+    fn encrypt(&self, buf: &mut [u8], offset: u64, key: &[u8]) -> Result<(), RfcError> {
+        apply_par(&chacha_key(key)?, buf, offset);
+        Ok(())
+    }
+
+    fn decrypt(&self, buf: &mut [u8], offset: u64, key: &[u8]) -> Result<(), RfcError> {
+        apply_par(&chacha_key(key)?, buf, offset);
+        Ok(())
+    }
+
+    fn chunk_aead(&self, key: &[u8]) -> Option<Box<dyn ChunkAead>> {
+        let key = chacha_key(key).ok()?;
+        Some(Box::new(AeadChunks(ChaCha20Poly1305::new(&key.into()))))
+    }
+}
+
+/// XORs `buf` with the keystream from `offset`, spreading the work across the current
+/// rayon thread pool.
+fn apply_par(key: &[u8; KEY_SIZE], buf: &mut [u8], offset: u64) {
+    buf.par_chunks_mut(PAR_BYTES)
+        .enumerate()
+        .for_each(|(i, chunk)| {
+            let mut cipher = ChaCha20::new(key.into(), &[0u8; 12].into());
+            cipher.seek(offset + (i * PAR_BYTES) as u64);
+            cipher.apply_keystream(chunk);
+        });
+}
+
+/// Returns `key` zero-filled to the ChaCha20 key size, like the keys of the AES ciphers.
+fn chacha_key(key: &[u8]) -> Result<[u8; KEY_SIZE], RfcError> {
+    let mut bytes = [0u8; KEY_SIZE];
+    let mut buf = &mut bytes[..];
+
+    buf.write_all(key).map_err(|_| {
+        RfcError::Encryption(format!("ChaCha20 key is longer than {} bytes", KEY_SIZE))
+    })?;
+
+    Ok(bytes)
+}
+
+#[test]
+fn test_chacha20() {
+    crate::rfc::tests::test_cipher(&CipherChacha20 {});
+
+    // RFC 8439 section 2.4.2, whose keystream starts at block 1 of the nonce below
+    let key: Vec<u8> = (0..32).collect();
+    let nonce = [0, 0, 0, 0, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+    let mut buf = b"Ladies and Gentlemen of the class of '99".to_vec();
+    let mut cipher = ChaCha20::new(key.as_slice().into(), &nonce.into());
+    cipher.seek(64u64);
+    cipher.apply_keystream(&mut buf);
+    assert_eq!(hex::encode(&buf[..16]), "6e2e359a2568f98041ba0728dd0d6981");
+
+    // Parts across a parallel task boundary decrypt like the whole
+    let plaintext: Vec<u8> = (0..3 * PAR_BYTES + 5).map(|i| i as u8).collect();
+    let mut whole = plaintext.clone();
+    CipherChacha20 {}.encrypt(&mut whole, 0, &key).unwrap();
+    let (first, second) = whole.split_at_mut(PAR_BYTES + 3);
+    CipherChacha20 {}.decrypt(first, 0, &key).unwrap();
+    CipherChacha20 {}
+        .decrypt(second, (PAR_BYTES + 3) as u64, &key)
+        .unwrap();
+    assert_eq!(whole, plaintext);
+
+    assert!(CipherChacha20 {}.chunk_aead(&key).is_some());
+    assert!(CipherChacha20 {}.encrypt(&mut buf, 0, &[0u8; 33]).is_err());
+}
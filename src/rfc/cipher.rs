@@ -1,27 +1,204 @@
+//! Ciphers of rfc's own format, and the registry of ciphers by header id.
+//!
+//! The AES and ChaCha20 ciphers are registered by default. Downstream crates can
+//! implement `Cipher` and `register` their own, which are then usable as a `Mode` by id
+//! or name and are listed by the CLI's --cipher.
+use std::sync::{Arc, LazyLock};
+
+use aes_gcm::aead::consts::U12;
+use aes_gcm::aead::{Aead, AeadCore, Payload};
+
+use crate::rfc::aes::{CipherAes128, CipherAes256};
+use crate::rfc::chacha20::CipherChacha20;
 use crate::rfc::error::RfcError;
+use crate::rfc::registry::{Registry, RegistryEntry};
+
+/// Cipher of rfc's own format. Keys are derived by the file's KDF to `key_size` bytes,
+/// and the ciphertext is authenticated by rfc, so ciphers need not be AEADs.
+pub trait Cipher: Send + Sync {
+    /// Id of the cipher in file headers, which must be unique and not 0
+    fn id(&self) -> u8;
+
+    /// Name of the cipher, e.g. for the CLI's --cipher
+    fn name(&self) -> &'static str;
+
+    /// Size of the keys derived for the cipher
+    fn key_size(&self) -> usize;
+
+    /// Size of the blocks the cipher encrypts. rfc zero-fills the plaintext to a multiple of it.
+    fn block_size(&self) -> usize;
+
+    /// Encrypts `buf` in place. `buf` is a multiple of `block_size` long, and starts at byte
+    /// `offset` of the plaintext, which may be encrypted in several parts.
+    fn encrypt(&self, buf: &mut [u8], offset: u64, key: &[u8]) -> Result<(), RfcError>;
+
+    /// Decrypts `buf` in place, which starts at byte `offset` of the ciphertext.
+    fn decrypt(&self, buf: &mut [u8], offset: u64, key: &[u8]) -> Result<(), RfcError>;
+
+    /// Returns the AEAD keyed with `key` that seals the chunks of seekable files and streams,
+    /// if the cipher has one. Ciphers without an AEAD are only usable in rfc's own format.
+    fn chunk_aead(&self, _key: &[u8]) -> Option<Box<dyn ChunkAead>> {
+        None
+    }
+}
+
+/// AEAD sealing the chunks of seekable files and streams. Nonces are 12 bytes long,
+/// and sealed chunks are 16 bytes longer than their plaintext.
+pub trait ChunkAead: Send + Sync {
+    fn seal(&self, nonce: &[u8; 12], aad: &[u8], chunk: &[u8]) -> Result<Vec<u8>, RfcError>;
+
+    /// Opens a sealed chunk, failing with `RfcError::Tampered` if it does not authenticate.
+    fn open(&self, nonce: &[u8; 12], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, RfcError>;
+}
+
+/// `ChunkAead` of an AEAD with 12-byte nonces, e.g. AES-GCM or ChaCha20-Poly1305.
+pub(crate) struct AeadChunks<C>(pub(crate) C);
+
+impl<C> ChunkAead for AeadChunks<C>
+where
+    C: Aead + AeadCore<NonceSize = U12> + Send + Sync,
+{
+    fn seal(&self, nonce: &[u8; 12], aad: &[u8], chunk: &[u8]) -> Result<Vec<u8>, RfcError> {
+        self.0
+            .encrypt(nonce.into(), Payload { msg: chunk, aad })
+            .map_err(|err| RfcError::Encryption(format!("failed to seal chunk: {}", err)))
+    }
+
+    fn open(&self, nonce: &[u8; 12], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, RfcError> {
+        self.0
+            .decrypt(nonce.into(), Payload { msg: sealed, aad })
+            .map_err(|_| RfcError::Tampered(String::from("chunk authentication failed")))
+    }
+}
+
+impl RegistryEntry<u8> for dyn Cipher {
+    fn entry_id(&self) -> u8 {
+        self.id()
+    }
+
+    fn entry_name(&self) -> &'static str {
+        self.name()
+    }
+}
+
+static REGISTRY: LazyLock<Registry<u8, dyn Cipher>> = LazyLock::new(|| {
+    let builtins: [Arc<dyn Cipher>; 3] = [
+        Arc::new(CipherAes128 {}),
+        Arc::new(CipherAes256 {}),
+        Arc::new(CipherChacha20 {}),
+    ];
 
-pub trait Cipher {
-    const KEY_SIZE: usize;
+    Registry::new("cipher", builtins)
+});
 
-    fn crypt<T, U>(bytes: T, key: U, decrypt: bool) -> Result<Vec<u8>, RfcError>
-    where
-        T: AsRef<[u8]>,
-        U: AsRef<[u8]>,
-    {
-        if decrypt {
-            return Self::decrypt(bytes, key);
+/// Registers `cipher` under its id. Ids and names already registered cannot be replaced.
+pub fn register<C>(cipher: C) -> Result<(), RfcError>
+where
+    C: Cipher + 'static,
+{
+    if cipher.id() == 0 {
+        return Err(RfcError::Registry(String::from(
+            "cipher id 0 is reserved for no encryption",
+        )));
+    }
+
+    REGISTRY.register(Arc::new(cipher))
+}
+
+/// Returns the cipher registered under `id`.
+pub fn lookup(id: u8) -> Result<Arc<dyn Cipher>, RfcError> {
+    REGISTRY.get(id).ok_or(RfcError::UnknownCipher(id))
+}
+
+/// Returns the cipher registered as `name`.
+pub fn lookup_name(name: &str) -> Option<Arc<dyn Cipher>> {
+    REGISTRY.get_name(name)
+}
+
+/// Returns all registered ciphers, ordered by id.
+pub fn registered() -> Vec<Arc<dyn Cipher>> {
+    REGISTRY.entries()
+}
+
+#[test]
+fn test_registry() {
+    use crate::rfc::{Decryptor, Encryptor, Mode};
+
+    /// XORs bytes with the key, to test the registry only
+    struct Xor;
+
+    impl Cipher for Xor {
+        fn id(&self) -> u8 {
+            200
+        }
+
+        fn name(&self) -> &'static str {
+            "xor-test"
+        }
+
+        fn key_size(&self) -> usize {
+            8
         }
 
-        Self::encrypt(bytes, key)
+        fn block_size(&self) -> usize {
+            1
+        }
+
+        fn encrypt(&self, buf: &mut [u8], offset: u64, key: &[u8]) -> Result<(), RfcError> {
+            let key = key.iter().cycle().skip(offset as usize % key.len());
+            buf.iter_mut().zip(key).for_each(|(b, k)| *b ^= k);
+
+            Ok(())
+        }
+
+        fn decrypt(&self, buf: &mut [u8], offset: u64, key: &[u8]) -> Result<(), RfcError> {
+            self.encrypt(buf, offset, key)
+        }
     }
 
-    fn encrypt<T, U>(bytes: T, key: U) -> Result<Vec<u8>, RfcError>
-    where
-        T: AsRef<[u8]>,
-        U: AsRef<[u8]>;
+    assert_eq!(lookup(1).unwrap().name(), "aes128");
+    assert_eq!(lookup_name("aes256").unwrap().id(), 2);
+    assert!(matches!(lookup(200), Err(RfcError::UnknownCipher(200))));
+
+    register(Xor).unwrap();
+    assert!(register(Xor).is_err());
+    assert_eq!(lookup(200).unwrap().name(), "xor-test");
+    assert!(registered()
+        .iter()
+        .any(|cipher| cipher.name() == "xor-test"));
+
+    crate::rfc::tests::test_cipher(lookup_name("xor-test").unwrap().as_ref());
+
+    // rfc's own format uses registered ciphers by id
+    let mode = Mode::from_name("xor-test").unwrap();
+    assert_eq!(mode, Mode::from_id(200).unwrap());
+    assert_eq!(mode.id(), 200);
+    assert_eq!(mode.to_string(), "xor-test");
+    // Built-in ciphers are looked up the same way, so modes compare by id only
+    assert_eq!(Mode::from_name("aes128").unwrap(), Mode::AES128);
+    assert_eq!(Mode::from_id(2).unwrap(), Mode::AES256);
+
+    let encrypted = Encryptor::new()
+        .cipher(mode)
+        .key("key")
+        .encrypt_buf(&b"plaintext"[..])
+        .unwrap();
+    let decryptor = Decryptor::new().cipher(mode).key("key");
+    assert_eq!(decryptor.decrypt_buf(&encrypted[..]).unwrap(), b"plaintext");
 
-    fn decrypt<T, U>(bytes: T, key: U) -> Result<Vec<u8>, RfcError>
-    where
-        T: AsRef<[u8]>,
-        U: AsRef<[u8]>;
+    // The cipher is read from the header, and only checked if given
+    assert_eq!(
+        Decryptor::new()
+            .key("key")
+            .decrypt_buf(&encrypted[..])
+            .unwrap(),
+        b"plaintext"
+    );
+    assert!(matches!(
+        Decryptor::new()
+            .cipher(Mode::AES256)
+            .key("key")
+            .decrypt_buf(&encrypted[..]),
+        Err(RfcError::CipherMismatch(..))
+    ));
 }
@@ -0,0 +1,208 @@
+use base64::{self, engine::general_purpose::STANDARD as b64_engine, Engine as _};
+
+use crate::rfc::error::RfcError;
+
+/// First line of ASCII-armored rfc files
+pub const ARMOR_BEGIN: &str = "-----BEGIN RFC ENCRYPTED FILE-----";
+/// Last line of ASCII-armored rfc files
+pub const ARMOR_END: &str = "-----END RFC ENCRYPTED FILE-----";
+
+/// Number of Base64 characters per armor line
+const ARMOR_COLUMNS: usize = 64;
+
+const CRC24_INIT: u32 = 0xB704CE;
+const CRC24_POLY: u32 = 0x1864CFB;
+
+/// Returns the OpenPGP CRC-24 checksum (RFC 4880, section 6.1) of `bytes`.
+pub fn crc24(bytes: &[u8]) -> u32 {
+    let mut crc = CRC24_INIT;
+    for byte in bytes {
+        crc ^= (*byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= CRC24_POLY;
+            }
+        }
+    }
+
+    crc & 0xFFFFFF
+}
+
+/// Encodes `bytes` into PEM/OpenPGP-style ASCII armor, with optional `headers` lines
+/// like `Comment: ...`, Base64 wrapped at 64 columns, and a CRC-24 checksum line.
+pub fn encode_armor(bytes: &[u8], headers: &[(&str, &str)]) -> Vec<u8> {
+    let encoded = b64_engine.encode(bytes);
+    let mut armor = String::with_capacity(encoded.len() + encoded.len() / ARMOR_COLUMNS + 128);
+
+    armor.push_str(ARMOR_BEGIN);
+    armor.push('\n');
+    for (name, value) in headers {
+        armor.push_str(&format!("{}: {}\n", name, value));
+    }
+    if !headers.is_empty() {
+        armor.push('\n');
+    }
+
+    for line in encoded.as_bytes().chunks(ARMOR_COLUMNS) {
+        armor.push_str(std::str::from_utf8(line).expect("Base64 is not UTF-8"));
+        armor.push('\n');
+    }
+
+    let crc = crc24(bytes).to_be_bytes();
+    armor.push('=');
+    armor.push_str(&b64_engine.encode(&crc[1..]));
+    armor.push('\n');
+    armor.push_str(ARMOR_END);
+    armor.push('\n');
+
+    armor.into_bytes()
+}
+
+/// Armor is the decoded content of an ASCII-armored file.
+#[derive(Debug, PartialEq)]
+pub struct Armor {
+    pub headers: Vec<(String, String)>,
+    pub bytes: Vec<u8>,
+}
+
+/// Decodes ASCII armor produced by `encode_armor`. Surrounding text, indentation,
+/// blank lines and CRLF line endings are tolerated, so armor pasted into emails,
+/// tickets or YAML files can be decoded as is. The checksum is verified if present.
+pub fn decode_armor(armor: &[u8]) -> Result<Armor, RfcError> {
+    let text = std::str::from_utf8(armor)
+        .map_err(|_| RfcError::Encoding(String::from("armor is not valid UTF-8")))?;
+
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| *line != ARMOR_BEGIN);
+
+    if lines.next().is_none() {
+        return Err(RfcError::Encoding(format!("missing {}", ARMOR_BEGIN)));
+    }
+
+    let mut headers = Vec::new();
+    let mut body = String::new();
+    let mut checksum = None;
+    let mut in_headers = true;
+    let mut ended = false;
+
+    for line in lines {
+        if line == ARMOR_END {
+            ended = true;
+            break;
+        }
+
+        match line.split_once(": ") {
+            // Header lines come first, and Base64 never contains ": "
+            Some((name, value)) if in_headers => {
+                headers.push((name.to_string(), value.to_string()));
+                continue;
+            }
+            _ => in_headers = false,
+        }
+
+        match line.strip_prefix('=') {
+            // Base64 lines never start with padding, so this is the checksum line
+            Some(crc) => checksum = Some(crc),
+            _ => body.extend(line.chars().filter(|c| !c.is_whitespace())),
+        }
+    }
+
+    if !ended {
+        return Err(RfcError::Truncated(format!("missing {}", ARMOR_END)));
+    }
+
+    let bytes = b64_engine
+        .decode(&body)
+        .map_err(|err| RfcError::Encoding(format!("bad armor Base64: {}", err)))?;
+
+    if let Some(checksum) = checksum {
+        let expected = b64_engine
+            .decode(checksum)
+            .ok()
+            .filter(|crc| crc.len() == 3)
+            .ok_or(RfcError::Encoding(format!(
+                "bad armor checksum {}",
+                checksum
+            )))?;
+
+        if expected != crc24(&bytes).to_be_bytes()[1..] {
+            return Err(RfcError::Tampered(String::from("armor checksum mismatch")));
+        }
+    }
+
+    Ok(Armor { headers, bytes })
+}
+
+#[test]
+fn test_crc24() {
+    // Check value for CRC-24/OPENPGP
+    assert_eq!(crc24(b"123456789"), 0x21CF02);
+    assert_eq!(crc24(b""), CRC24_INIT);
+}
+
+#[test]
+fn test_armor() {
+    let bytes = include_bytes!("./armor.rs");
+
+    for headers in [vec![], vec![("Comment", "hello: world"), ("Version", "1")]] {
+        let armor = encode_armor(bytes, &headers);
+        let text = std::str::from_utf8(&armor).unwrap();
+
+        assert!(text.starts_with(ARMOR_BEGIN));
+        assert!(text.trim_end().ends_with(ARMOR_END));
+        assert!(text.lines().all(|line| line.len() <= ARMOR_COLUMNS));
+
+        let decoded = decode_armor(&armor).expect("failed to decode armor");
+        assert_eq!(bytes, &decoded.bytes[..]);
+        assert_eq!(
+            headers,
+            decoded
+                .headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn test_armor_whitespace() {
+    let bytes = include_bytes!("./armor.rs");
+    let armor = String::from_utf8(encode_armor(bytes, &[])).unwrap();
+
+    // Indented in YAML, with CRLF line endings and surrounding text
+    let pasted = format!(
+        "secret: |\r\n{}\r\nother: value\r\n",
+        armor
+            .lines()
+            .map(|line| format!("    {} ", line))
+            .collect::<Vec<_>>()
+            .join("\r\n\r\n")
+    );
+
+    let decoded = decode_armor(pasted.as_bytes()).expect("failed to decode pasted armor");
+    assert_eq!(bytes, &decoded.bytes[..]);
+}
+
+#[test]
+fn test_armor_errors() {
+    let armor = String::from_utf8(encode_armor(b"some bytes to armor", &[])).unwrap();
+
+    assert!(decode_armor(b"no armor here").is_err());
+    assert!(matches!(
+        decode_armor(armor.replace(ARMOR_END, "").as_bytes()),
+        Err(RfcError::Truncated(_))
+    ));
+
+    // Corrupt the first Base64 character, keeping the Base64 valid
+    let mut lines: Vec<String> = armor.lines().map(String::from).collect();
+    let first = lines[1].remove(0);
+    lines[1].insert(0, if first == 'A' { 'B' } else { 'A' });
+    assert!(matches!(
+        decode_armor(lines.join("\n").as_bytes()),
+        Err(RfcError::Tampered(_))
+    ));
+}
@@ -0,0 +1,68 @@
+use base32::{self, Alphabet};
+
+use std::io::{Read, Write};
+
+use super::blocks::{decode_blocks, encode_blocks};
+use crate::rfc::error::RfcError;
+
+/// RFC 4648 Base32 alphabet, with `=` padding
+const B32_ALPHABET: Alphabet = Alphabet::Rfc4648 { padding: true };
+
+/// Bytes per Base32 block, which encode to 8 characters without padding
+const B32_BLOCK: usize = 5;
+const B32_ENCODED_BLOCK: usize = 8;
+
+pub fn encode_b32<S, D>(src: &mut S, dst: &mut D) -> Result<usize, RfcError>
+where
+    S: Read,
+    D: Write,
+{
+    encode_blocks(src, dst, B32_BLOCK, |chunk| {
+        base32::encode(B32_ALPHABET, chunk)
+    })
+}
+
+pub fn decode_b32<S, D>(src: &mut S, dst: &mut D) -> Result<(), RfcError>
+where
+    S: Read,
+    D: Write,
+{
+    decode_blocks(src, dst, B32_ENCODED_BLOCK, |chunk| {
+        std::str::from_utf8(chunk)
+            .ok()
+            .and_then(|chunk| base32::decode(B32_ALPHABET, chunk))
+            .ok_or(RfcError::Encoding(String::from("bad Base32 input")))
+    })
+}
+
+#[test]
+fn test_b32() {
+    // RFC 4648 test vectors
+    for (plain, encoded) in [
+        ("", ""),
+        ("f", "MY======"),
+        ("fo", "MZXQ===="),
+        ("foo", "MZXW6==="),
+        ("foob", "MZXW6YQ="),
+        ("fooba", "MZXW6YTB"),
+        ("foobar", "MZXW6YTBOI======"),
+    ] {
+        let mut buf = Vec::new();
+        encode_b32(&mut plain.as_bytes(), &mut buf).unwrap();
+        assert_eq!(encoded.as_bytes(), buf);
+
+        let mut decoded = Vec::new();
+        decode_b32(&mut encoded.as_bytes(), &mut decoded).unwrap();
+        assert_eq!(plain.as_bytes(), decoded);
+    }
+
+    let original_bytes = std::fs::read("./Cargo.toml").unwrap();
+    let mut encoded = Vec::new();
+    let mut decoded = Vec::new();
+
+    encode_b32(&mut original_bytes.as_slice(), &mut encoded).unwrap();
+    decode_b32(&mut encoded.as_slice(), &mut decoded).unwrap();
+    assert_eq!(original_bytes, decoded);
+
+    assert!(decode_b32(&mut b"MZXW6YT!".as_slice(), &mut Vec::new()).is_err());
+}
@@ -0,0 +1,63 @@
+use bs58;
+
+use std::io::{Read, Write};
+
+use crate::rfc::error::RfcError;
+
+/// Encodes `src` with the Bitcoin Base58 alphabet. Base58 treats its input as a single
+/// big number, so the whole input is buffered, and encoding time grows quadratically
+/// with input size. It is meant for small files such as keys.
+pub fn encode_b58<S, D>(src: &mut S, dst: &mut D) -> Result<usize, RfcError>
+where
+    S: Read,
+    D: Write,
+{
+    let mut bytes = Vec::new();
+    src.read_to_end(&mut bytes).map_err(RfcError::IoError)?;
+
+    dst.write_all(bs58::encode(&bytes).into_string().as_bytes())
+        .map_err(RfcError::IoError)?;
+
+    Ok(bytes.len())
+}
+
+/// Decodes Base58 from `src`, ignoring surrounding whitespace. Like `encode_b58`,
+/// the whole input is buffered.
+pub fn decode_b58<S, D>(src: &mut S, dst: &mut D) -> Result<(), RfcError>
+where
+    S: Read,
+    D: Write,
+{
+    let mut encoded = Vec::new();
+    src.read_to_end(&mut encoded).map_err(RfcError::IoError)?;
+
+    let bytes = bs58::decode(encoded.trim_ascii())
+        .into_vec()
+        .map_err(|err| RfcError::Encoding(err.to_string()))?;
+
+    dst.write_all(&bytes).map_err(RfcError::IoError)
+}
+
+#[test]
+fn test_b58() {
+    let mut encoded = Vec::new();
+    encode_b58(&mut b"Hello World!".as_slice(), &mut encoded).unwrap();
+    assert_eq!(b"2NEpo7TZRRrLZSi2U", encoded.as_slice());
+
+    // Leading zero bytes are encoded as leading '1's
+    let mut encoded = Vec::new();
+    encode_b58(&mut [0, 0, 1].as_slice(), &mut encoded).unwrap();
+    assert_eq!(b"112", encoded.as_slice());
+
+    let original_bytes = std::fs::read("./Cargo.toml").unwrap();
+    let mut encoded = Vec::new();
+    let mut decoded = Vec::new();
+
+    encode_b58(&mut original_bytes.as_slice(), &mut encoded).unwrap();
+    encoded.push(b'\n');
+    decode_b58(&mut encoded.as_slice(), &mut decoded).unwrap();
+    assert_eq!(original_bytes, decoded);
+
+    // '0', 'O', 'I' and 'l' are not in the alphabet
+    assert!(decode_b58(&mut b"2NEpo7TZ0".as_slice(), &mut Vec::new()).is_err());
+}
@@ -1,4 +1,10 @@
-use base64::{self, engine::general_purpose::STANDARD as b64_engine};
+use base64::{
+    self,
+    engine::{
+        general_purpose::{STANDARD as b64_engine, URL_SAFE_NO_PAD as b64url_engine},
+        GeneralPurpose,
+    },
+};
 
 use std::io::{Read, Write};
 
@@ -31,21 +37,54 @@ where
     S: Read,
     D: Write,
 {
-    let mut encoder = base64::write::EncoderWriter::new(dst, &b64_engine);
+    encode_with(&b64_engine, src, dst)
+}
+
+pub fn decode_b64<S, D>(src: &mut S, dst: &mut D) -> Result<(), RfcError>
+where
+    S: Read,
+    D: Write,
+{
+    decode_with(&b64_engine, src, dst)
+}
+
+/// Encodes with the URL and filename safe Base64 alphabet (`-` and `_`), without padding.
+pub fn encode_b64url<S, D>(src: &mut S, dst: &mut D) -> Result<usize, RfcError>
+where
+    S: Read,
+    D: Write,
+{
+    encode_with(&b64url_engine, src, dst)
+}
+
+pub fn decode_b64url<S, D>(src: &mut S, dst: &mut D) -> Result<(), RfcError>
+where
+    S: Read,
+    D: Write,
+{
+    decode_with(&b64url_engine, src, dst)
+}
+
+fn encode_with<S, D>(engine: &GeneralPurpose, src: &mut S, dst: &mut D) -> Result<usize, RfcError>
+where
+    S: Read,
+    D: Write,
+{
+    let mut encoder = base64::write::EncoderWriter::new(dst, engine);
 
-    let written = std::io::copy(src, &mut encoder).map_err(|err| RfcError::IoError(err))?;
+    let written = std::io::copy(src, &mut encoder).map_err(RfcError::IoError)?;
 
     Ok(written as usize)
 }
 
-pub fn decode_b64<S, D>(src: &mut S, dst: &mut D) -> Result<(), RfcError>
+fn decode_with<S, D>(engine: &GeneralPurpose, src: &mut S, dst: &mut D) -> Result<(), RfcError>
 where
     S: Read,
     D: Write,
 {
-    let mut decoder = base64::read::DecoderReader::new(src, &b64_engine);
+    let mut decoder = base64::read::DecoderReader::new(src, engine);
 
-    std::io::copy(&mut decoder, dst).map_err(|err| RfcError::IoError(err))?;
+    std::io::copy(&mut decoder, dst).map_err(RfcError::IoError)?;
 
     Ok(())
 }
@@ -73,3 +112,19 @@ fn test_b64() {
 
     assert_eq!(original_bytes, decoded);
 }
+
+#[test]
+fn test_b64url() {
+    let bytes = [0xfb, 0xff, 0xbf, 0x00];
+    let mut encoded = Vec::new();
+    let mut decoded = Vec::new();
+
+    encode_b64url(&mut bytes.as_slice(), &mut encoded).unwrap();
+    assert_eq!(b"-_-_AA", encoded.as_slice());
+
+    decode_b64url(&mut encoded.as_slice(), &mut decoded).unwrap();
+    assert_eq!(bytes.as_slice(), decoded);
+
+    // Standard alphabet and padding are rejected
+    assert!(decode_b64url(&mut b"+/+/AA==".as_slice(), &mut Vec::new()).is_err());
+}
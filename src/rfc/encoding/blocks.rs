@@ -0,0 +1,121 @@
+use std::io::{ErrorKind, Read, Write};
+
+use crate::rfc::error::RfcError;
+
+/// Number of encoding blocks processed per read
+const BLOCKS_PER_READ: usize = 4096;
+
+/// Streams `src` to `dst` through `encode`, which is called with whole multiples of
+/// `block` input bytes, except for the last call at the end of input.
+/// Returns the number of bytes read from `src`.
+pub(super) fn encode_blocks<S, D, F>(
+    src: &mut S,
+    dst: &mut D,
+    block: usize,
+    encode: F,
+) -> Result<usize, RfcError>
+where
+    S: Read,
+    D: Write,
+    F: Fn(&[u8]) -> String,
+{
+    let mut buf = vec![0u8; block * BLOCKS_PER_READ];
+    let mut read = 0;
+
+    loop {
+        let n = read_full(src, &mut buf)?;
+        if n > 0 {
+            dst.write_all(encode(&buf[..n]).as_bytes())
+                .map_err(RfcError::IoError)?;
+            read += n;
+        }
+
+        if n < buf.len() {
+            return Ok(read);
+        }
+    }
+}
+
+/// Streams encoded `src` to `dst` through `decode`, which is called with whole multiples
+/// of `block` encoded bytes, except for the last call at the end of input.
+/// Whitespace such as line breaks is skipped.
+pub(super) fn decode_blocks<S, D, F>(
+    src: &mut S,
+    dst: &mut D,
+    block: usize,
+    decode: F,
+) -> Result<(), RfcError>
+where
+    S: Read,
+    D: Write,
+    F: Fn(&[u8]) -> Result<Vec<u8>, RfcError>,
+{
+    let mut buf = vec![0u8; block * BLOCKS_PER_READ];
+    let mut pending: Vec<u8> = Vec::with_capacity(buf.len() + block);
+
+    loop {
+        let n = read_full(src, &mut buf)?;
+        pending.extend(buf[..n].iter().filter(|b| !b.is_ascii_whitespace()));
+
+        let complete = match n < buf.len() {
+            true => pending.len(),
+            false => pending.len() - pending.len() % block,
+        };
+
+        if complete > 0 {
+            dst.write_all(&decode(&pending[..complete])?)
+                .map_err(RfcError::IoError)?;
+            pending.drain(..complete);
+        }
+
+        if n < buf.len() {
+            return Ok(());
+        }
+    }
+}
+
+/// Reads from `src` until `buf` is full or the input ends, returning the number of bytes read.
+fn read_full<R: Read>(src: &mut R, buf: &mut [u8]) -> Result<usize, RfcError> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match src.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(RfcError::IoError(err)),
+        }
+    }
+
+    Ok(filled)
+}
+
+#[test]
+fn test_blocks() {
+    // Letter "encoding" with 3-byte blocks, longer than a single read
+    let bytes: Vec<u8> = (0..=255)
+        .cycle()
+        .take(3 * BLOCKS_PER_READ * 2 + 2)
+        .collect();
+    let encode =
+        |chunk: &[u8]| String::from_utf8(chunk.iter().map(|b| b'a' + b % 26).collect()).unwrap();
+
+    let mut encoded = Vec::new();
+    let read = encode_blocks(&mut bytes.as_slice(), &mut encoded, 3, encode).unwrap();
+    assert_eq!(read, bytes.len());
+    assert_eq!(encoded.len(), bytes.len());
+
+    // Line breaks are skipped, so blocks are realigned
+    let wrapped: Vec<u8> = encoded
+        .chunks(61)
+        .flat_map(|line| line.iter().copied().chain(*b"\r\n"))
+        .collect();
+
+    let mut decoded = Vec::new();
+    decode_blocks(&mut wrapped.as_slice(), &mut decoded, 3, |chunk| {
+        Ok(chunk.to_vec())
+    })
+    .unwrap();
+
+    assert_eq!(decoded, encoded);
+}
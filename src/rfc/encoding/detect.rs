@@ -0,0 +1,197 @@
+use super::{
+    decode_b32, decode_b58, decode_b64, decode_b64url, decode_hex_buf, decode_z85, Encoding,
+    ARMOR_BEGIN,
+};
+use crate::rfc::error::RfcError;
+use crate::rfc::header::MAGIC;
+use crate::rfc::seekable;
+
+/// Number of leading characters that block encodings need to decode MAGIC
+const SAMPLE_LEN: usize = 8;
+
+/// Number of leading characters probed for encodings without blocks, and for files
+/// without magic. Base58 decodes in quadratic time, so the whole input is never probed.
+const PROBE_LEN: usize = 64;
+
+/// Detects the encoding of an rfc encrypted file or stream by decoding its start with each
+/// supported encoding, and looking for the binary magic. Alphabets alone are not
+/// enough, since hex and Base32 digits are also valid Base64.
+pub fn detect_encoding(bytes: &[u8]) -> Result<Encoding, RfcError> {
+    if bytes.starts_with(MAGIC) || bytes.starts_with(seekable::MAGIC) {
+        return Ok(Encoding::Plain);
+    }
+    if contains(bytes, ARMOR_BEGIN.as_bytes()) {
+        return Ok(Encoding::Armor);
+    }
+
+    let sample = sample(bytes, SAMPLE_LEN);
+    let decodes_to_magic = |len: usize, encoding: Encoding| {
+        sample.len() >= len
+            && decode_sample(encoding, &sample[..len])
+                .is_ok_and(|decoded| starts_with_magic(&decoded))
+    };
+
+    if decodes_to_magic(8, Encoding::Hex) {
+        return Ok(Encoding::Hex);
+    }
+    if decodes_to_magic(8, Encoding::B32) {
+        return Ok(Encoding::B32);
+    }
+    if decodes_to_magic(8, Encoding::B64) {
+        // The magic encodes to the same characters in both Base64 alphabets
+        let url_safe = bytes.iter().any(|b| matches!(b, b'-' | b'_'))
+            || (!bytes.contains(&b'=') && !encoded_len(bytes).is_multiple_of(4));
+
+        return match url_safe {
+            true => Ok(Encoding::B64Url),
+            false => Ok(Encoding::B64),
+        };
+    }
+    if decodes_to_magic(5, Encoding::Z85) {
+        return Ok(Encoding::Z85);
+    }
+
+    // Base58 has no blocks, so no prefix decodes to the magic. Only the alphabet of a
+    // prefix is checked here, and the magic once the whole input is decoded.
+    if detect_encoding_among(bytes, &[Encoding::B58]) == Encoding::B58 {
+        return Ok(Encoding::B58);
+    }
+
+    Err(RfcError::Deserialize(String::from(
+        "input is not an rfc encrypted file in any supported encoding",
+    )))
+}
+
+/// Detects the encoding of files without magic, e.g. gfc ones, by decoding a prefix of
+/// `bytes` with each of `candidates` in order. Random binary data practically never
+/// decodes, so input that no candidate decodes is taken to be unencoded.
+pub fn detect_encoding_among(bytes: &[u8], candidates: &[Encoding]) -> Encoding {
+    let sample = sample(bytes, PROBE_LEN);
+
+    candidates
+        .iter()
+        .copied()
+        .find(|encoding| !sample.is_empty() && decode_sample(*encoding, &sample).is_ok())
+        .unwrap_or(Encoding::Plain)
+}
+
+/// Returns whether a decoded sample, at least as long as MAGIC, starts with the magic of rfc
+/// files or streams. Samples may be shorter than the magic of streams.
+fn starts_with_magic(decoded: &[u8]) -> bool {
+    decoded.len() >= MAGIC.len()
+        && (decoded.starts_with(MAGIC)
+            || seekable::MAGIC.starts_with(&decoded[..decoded.len().min(seekable::MAGIC.len())]))
+}
+
+/// Returns the first `len` non-whitespace characters of `bytes`.
+fn sample(bytes: &[u8], len: usize) -> Vec<u8> {
+    bytes
+        .iter()
+        .filter(|b| !b.is_ascii_whitespace())
+        .take(len)
+        .copied()
+        .collect()
+}
+
+/// Decodes `sample` with `encoding`, which must not be one of the framed encodings.
+fn decode_sample(encoding: Encoding, sample: &[u8]) -> Result<Vec<u8>, RfcError> {
+    match encoding {
+        Encoding::Hex => decode_hex_buf(sample),
+        Encoding::B32 => decode_with(decode_b32, sample),
+        Encoding::B64 => decode_with(decode_b64, sample),
+        Encoding::B64Url => decode_with(decode_b64url, sample),
+        Encoding::B58 => decode_with(decode_b58, sample),
+        Encoding::Z85 => decode_with(decode_z85, sample),
+        Encoding::Plain | Encoding::Armor | Encoding::Auto => Err(RfcError::Encoding(format!(
+            "{} cannot be detected from a sample",
+            encoding
+        ))),
+    }
+}
+
+fn decode_with<'a>(
+    decode: fn(&mut &'a [u8], &mut Vec<u8>) -> Result<(), RfcError>,
+    mut encoded: &'a [u8],
+) -> Result<Vec<u8>, RfcError> {
+    let mut decoded = Vec::new();
+    decode(&mut encoded, &mut decoded)?;
+
+    Ok(decoded)
+}
+
+fn contains(bytes: &[u8], needle: &[u8]) -> bool {
+    bytes.windows(needle.len()).any(|window| window == needle)
+}
+
+/// Returns the number of non-whitespace characters in `bytes`.
+fn encoded_len(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|b| !b.is_ascii_whitespace()).count()
+}
+
+#[test]
+fn test_detect_encoding() {
+    use super::{
+        encode_armor, encode_b32, encode_b58, encode_b64, encode_b64url, encode_hex, encode_z85,
+    };
+
+    let mut file = MAGIC.to_vec();
+    file.extend_from_slice(b"\x01\x02\x00\x00 rest of the encrypted file");
+
+    fn encode<'a>(
+        encode: fn(&mut &'a [u8], &mut Vec<u8>) -> Result<usize, RfcError>,
+        mut file: &'a [u8],
+    ) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        encode(&mut file, &mut encoded).unwrap();
+        encoded
+    }
+
+    let cases = [
+        (Encoding::Plain, file.clone()),
+        (Encoding::Hex, encode(encode_hex, &file)),
+        (
+            Encoding::Hex,
+            encode(encode_hex, &file).to_ascii_uppercase(),
+        ),
+        (Encoding::B64, encode(encode_b64, &file)),
+        (Encoding::B64Url, encode(encode_b64url, &file)),
+        (Encoding::B32, encode(encode_b32, &file)),
+        (Encoding::B58, encode(encode_b58, &file)),
+        (Encoding::Z85, encode(encode_z85, &file)),
+        (Encoding::Armor, encode_armor(&file, &[])),
+    ];
+
+    for (encoding, mut encoded) in cases {
+        assert_eq!(detect_encoding(&encoded).unwrap(), encoding);
+
+        // Trailing line breaks are common after copy and paste
+        encoded.extend_from_slice(b"\r\n");
+        assert_eq!(detect_encoding(&encoded).unwrap(), encoding);
+    }
+
+    assert!(detect_encoding(b"").is_err());
+    assert!(detect_encoding(b"hello, world").is_err());
+    assert!(detect_encoding(&file[1..]).is_err());
+
+    // Only a prefix of large input is decoded as Base58
+    let large = vec![b'2'; 16 * 1024 * 1024];
+    assert_eq!(detect_encoding(&large).unwrap(), Encoding::B58);
+}
+
+#[test]
+fn test_detect_encoding_among() {
+    let candidates = [Encoding::Hex, Encoding::B64];
+
+    assert_eq!(detect_encoding_among(b"00ff\n", &candidates), Encoding::Hex);
+    assert_eq!(detect_encoding_among(b"AP8=\n", &candidates), Encoding::B64);
+    assert_eq!(
+        detect_encoding_among(b"\x00\xff", &candidates),
+        Encoding::Plain
+    );
+    assert_eq!(detect_encoding_among(b"", &candidates), Encoding::Plain);
+    // Hex digits are also Base64, so candidates are tried in order
+    assert_eq!(
+        detect_encoding_among(b"00ff", &[Encoding::B64, Encoding::Hex]),
+        Encoding::B64
+    );
+}
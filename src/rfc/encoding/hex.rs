@@ -1,7 +1,12 @@
 use hex;
 
+use std::io::{self, Read, Write};
+
 use crate::rfc::error::RfcError;
 
+/// Number of bytes hex-encoded or -decoded at a time by the streaming adapters
+const HEX_CHUNK: usize = 8192;
+
 pub fn encode_hex_buf<T>(plain: T) -> Vec<u8>
 where
     T: AsRef<[u8]>,
@@ -13,5 +18,193 @@ pub fn decode_hex_buf<T>(hex_data: T) -> Result<Vec<u8>, RfcError>
 where
     T: AsRef<[u8]>,
 {
-    hex::decode(hex_data).map_err(|err| RfcError::Encoding(err.to_string()))
+    let mut hex_data = hex_data.as_ref();
+    let mut buf = Vec::with_capacity(hex_data.len() / 2);
+    decode_hex(&mut hex_data, &mut buf)?;
+
+    Ok(buf)
+}
+
+pub fn encode_hex<S, D>(src: &mut S, dst: &mut D) -> Result<usize, RfcError>
+where
+    S: Read,
+    D: Write,
+{
+    let mut encoder = HexEncoder::new(dst);
+
+    let written = io::copy(src, &mut encoder).map_err(RfcError::IoError)?;
+
+    Ok(written as usize)
+}
+
+pub fn decode_hex<S, D>(src: &mut S, dst: &mut D) -> Result<(), RfcError>
+where
+    S: Read,
+    D: Write,
+{
+    let mut decoder = HexDecoder::new(src);
+
+    io::copy(&mut decoder, dst).map_err(RfcError::from_io)?;
+
+    Ok(())
+}
+
+/// HexEncoder writes bytes written to it as lowercase hex to the inner writer.
+pub struct HexEncoder<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> HexEncoder<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buf: vec![0u8; HEX_CHUNK * 2],
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for HexEncoder<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let bytes = &bytes[..bytes.len().min(HEX_CHUNK)];
+        let encoded = &mut self.buf[..bytes.len() * 2];
+
+        hex::encode_to_slice(bytes, encoded).expect("hex buffer too small");
+        self.inner.write_all(encoded)?;
+
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// HexDecoder reads hex from the inner reader and yields the decoded bytes.
+/// Uppercase digits and whitespace such as line breaks are accepted.
+pub struct HexDecoder<R: Read> {
+    inner: R,
+    buf: Vec<u8>,
+    /// High nibble of a byte whose low nibble has not been read yet
+    nibble: Option<u8>,
+}
+
+impl<R: Read> HexDecoder<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![0u8; HEX_CHUNK * 2],
+            nibble: None,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for HexDecoder<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+
+        // At most 2 hex digits per output byte are read, so decoded bytes always fit `out`
+        let max_read = self.buf.len().min(out.len() * 2);
+
+        loop {
+            let n = self.inner.read(&mut self.buf[..max_read])?;
+            if n == 0 {
+                return match self.nibble {
+                    Some(_) => Err(invalid_hex("odd number of hex digits")),
+                    None => Ok(0),
+                };
+            }
+
+            let mut written = 0;
+            for &digit in &self.buf[..n] {
+                if digit.is_ascii_whitespace() {
+                    continue;
+                }
+
+                let value = match digit {
+                    b'0'..=b'9' => digit - b'0',
+                    b'a'..=b'f' => digit - b'a' + 10,
+                    b'A'..=b'F' => digit - b'A' + 10,
+                    _ => {
+                        return Err(invalid_hex(&format!(
+                            "invalid hex digit {:?}",
+                            digit as char
+                        )))
+                    }
+                };
+
+                match self.nibble.take() {
+                    Some(high) => {
+                        out[written] = high << 4 | value;
+                        written += 1;
+                    }
+                    None => self.nibble = Some(value),
+                }
+            }
+
+            // Reading only whitespace or a single digit is not the end of input
+            if written > 0 {
+                return Ok(written);
+            }
+        }
+    }
+}
+
+fn invalid_hex(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        RfcError::Encoding(String::from(msg)),
+    )
+}
+
+#[test]
+fn test_hex() {
+    let original_bytes = std::fs::read("./Cargo.toml").unwrap();
+    let mut encoded = Vec::new();
+    let mut decoded = Vec::new();
+
+    encode_hex(&mut original_bytes.as_slice(), &mut encoded).unwrap();
+    assert_eq!(encode_hex_buf(&original_bytes), encoded);
+
+    decode_hex(&mut encoded.as_slice(), &mut decoded).unwrap();
+    assert_eq!(original_bytes, decoded);
+
+    // Uppercase, wrapped with CRLF and indented
+    let pasted: Vec<u8> = encoded
+        .to_ascii_uppercase()
+        .chunks(63)
+        .flat_map(|line| b"  ".iter().chain(line).chain(b"\r\n").copied())
+        .collect();
+    assert_eq!(original_bytes, decode_hex_buf(&pasted).unwrap());
+
+    assert_eq!(
+        decode_hex_buf("DEad bE\nEf").unwrap(),
+        [0xde, 0xad, 0xbe, 0xef]
+    );
+    assert!(matches!(decode_hex_buf("abc"), Err(RfcError::Encoding(_))));
+    assert!(matches!(decode_hex_buf("0g"), Err(RfcError::Encoding(_))));
+}
+
+#[test]
+fn test_hex_decoder_small_reads() {
+    // Output buffers of 1 byte must still make progress
+    let mut decoder = HexDecoder::new(" 0 1\n\nff ".as_bytes());
+    let mut byte = [0u8; 1];
+    let mut decoded = Vec::new();
+
+    while decoder.read(&mut byte).unwrap() > 0 {
+        decoded.push(byte[0]);
+    }
+
+    assert_eq!(decoded, [0x01, 0xff]);
 }
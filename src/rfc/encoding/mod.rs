@@ -1,16 +1,42 @@
+mod armor;
+mod b32;
+mod b58;
 mod b64;
+mod blocks;
+mod detect;
 mod hex;
+mod streaming;
+mod z85;
 
 use clap;
 
+pub use self::armor::*;
+pub use self::b32::*;
+pub use self::b58::*;
 pub use self::b64::*;
+pub use self::detect::*;
 pub use self::hex::*;
+pub use self::streaming::*;
+pub use self::z85::*;
 
 #[derive(Copy, Clone, Debug, PartialEq, clap::ValueEnum)]
 pub enum Encoding {
     Plain,
     Hex,
     B64,
+    /// URL-safe Base64 without padding
+    B64Url,
+    /// RFC 4648 Base32
+    B32,
+    /// Bitcoin Base58, slow on large files
+    B58,
+    /// ZeroMQ Z85
+    Z85,
+    /// Base64 wrapped in PEM-style BEGIN/END lines, with a CRC-24 checksum
+    Armor,
+    /// Detect the encoding of encrypted input, only valid for decryption. Any encoding is
+    /// detected for rfc files, while other formats only detect the encodings they write.
+    Auto,
 }
 
 impl std::fmt::Display for Encoding {
@@ -19,6 +45,12 @@ impl std::fmt::Display for Encoding {
             Self::Plain => write!(f, "plain"),
             Self::Hex => write!(f, "hex"),
             Self::B64 => write!(f, "b64"),
+            Self::B64Url => write!(f, "b64-url"),
+            Self::B32 => write!(f, "b32"),
+            Self::B58 => write!(f, "b58"),
+            Self::Z85 => write!(f, "z85"),
+            Self::Armor => write!(f, "armor"),
+            Self::Auto => write!(f, "auto"),
         }
     }
 }
@@ -0,0 +1,141 @@
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::engine::GeneralPurpose;
+use base64::read::DecoderReader;
+use base64::write::EncoderWriter;
+
+use std::io::{self, Read, Write};
+
+use super::{Encoding, HexDecoder, HexEncoder};
+use crate::rfc::error::RfcError;
+
+/// Encoder writes bytes written to it to the inner writer in one of the encodings that
+/// can be written incrementally. Base32, Base58, Z85 and armor are only encoded whole.
+pub enum Encoder<W: Write> {
+    Plain(W),
+    Hex(HexEncoder<W>),
+    /// Boxed, since the encoder buffers a whole block of output
+    B64(Box<EncoderWriter<'static, GeneralPurpose, W>>),
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(encoding: Encoding, inner: W) -> Result<Self, RfcError> {
+        match encoding {
+            Encoding::Plain => Ok(Self::Plain(inner)),
+            Encoding::Hex => Ok(Self::Hex(HexEncoder::new(inner))),
+            Encoding::B64 => Ok(Self::B64(Box::new(EncoderWriter::new(inner, &STANDARD)))),
+            Encoding::B64Url => Ok(Self::B64(Box::new(EncoderWriter::new(
+                inner,
+                &URL_SAFE_NO_PAD,
+            )))),
+            _ => Err(not_streamable(encoding)),
+        }
+    }
+
+    /// Writes the end of the encoding, e.g. Base64 padding, and returns the inner writer.
+    pub fn finish(self) -> Result<W, RfcError> {
+        match self {
+            Self::Plain(inner) => Ok(inner),
+            Self::Hex(encoder) => Ok(encoder.into_inner()),
+            Self::B64(mut encoder) => encoder.finish().map_err(RfcError::IoError),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(inner) => inner.write(bytes),
+            Self::Hex(encoder) => encoder.write(bytes),
+            Self::B64(encoder) => encoder.write(bytes),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(inner) => inner.flush(),
+            Self::Hex(encoder) => encoder.flush(),
+            Self::B64(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Decoder yields the bytes decoded from the inner reader, in the encodings `Encoder` writes.
+pub enum Decoder<R: Read> {
+    Plain(R),
+    Hex(HexDecoder<R>),
+    /// Boxed, since the decoder buffers a whole block of input
+    B64(Box<DecoderReader<'static, GeneralPurpose, R>>),
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(encoding: Encoding, inner: R) -> Result<Self, RfcError> {
+        match encoding {
+            Encoding::Plain => Ok(Self::Plain(inner)),
+            Encoding::Hex => Ok(Self::Hex(HexDecoder::new(inner))),
+            Encoding::B64 => Ok(Self::B64(Box::new(DecoderReader::new(inner, &STANDARD)))),
+            Encoding::B64Url => Ok(Self::B64(Box::new(DecoderReader::new(
+                inner,
+                &URL_SAFE_NO_PAD,
+            )))),
+            _ => Err(not_streamable(encoding)),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        match self {
+            Self::Plain(inner) => inner,
+            Self::Hex(decoder) => decoder.into_inner(),
+            Self::B64(decoder) => decoder.into_inner(),
+        }
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(inner) => inner.read(buf),
+            Self::Hex(decoder) => decoder.read(buf),
+            Self::B64(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+fn not_streamable(encoding: Encoding) -> RfcError {
+    RfcError::NotImplemented(format!("streaming {} encoding", encoding))
+}
+
+#[test]
+fn test_streaming() {
+    let bytes = include_bytes!("./streaming.rs");
+
+    for encoding in [
+        Encoding::Plain,
+        Encoding::Hex,
+        Encoding::B64,
+        Encoding::B64Url,
+    ] {
+        let mut encoder = Encoder::new(encoding, Vec::new()).unwrap();
+        for chunk in bytes.chunks(7) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let encoded = encoder.finish().unwrap();
+
+        // Streamed output is the same as the whole input encoded at once
+        let mut whole = Vec::new();
+        crate::rfc::encode_write(encoding, &mut whole, bytes).unwrap();
+        assert_eq!(encoded, whole, "{}", encoding);
+
+        let mut decoded = Vec::new();
+        Decoder::new(encoding, &encoded[..])
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, bytes);
+    }
+
+    assert!(matches!(
+        Encoder::new(Encoding::B58, Vec::new()),
+        Err(RfcError::NotImplemented(_))
+    ));
+    assert!(Decoder::new(Encoding::Armor, &b""[..]).is_err());
+}
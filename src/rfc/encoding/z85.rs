@@ -0,0 +1,50 @@
+use z85;
+
+use std::io::{Read, Write};
+
+use super::blocks::{decode_blocks, encode_blocks};
+use crate::rfc::error::RfcError;
+
+/// Bytes per Z85 block, which encode to 5 characters. Input that is not
+/// a multiple of 4 bytes ends with a `#`-padded block.
+const Z85_BLOCK: usize = 4;
+const Z85_ENCODED_BLOCK: usize = 5;
+
+pub fn encode_z85<S, D>(src: &mut S, dst: &mut D) -> Result<usize, RfcError>
+where
+    S: Read,
+    D: Write,
+{
+    encode_blocks(src, dst, Z85_BLOCK, |chunk| z85::encode(chunk))
+}
+
+pub fn decode_z85<S, D>(src: &mut S, dst: &mut D) -> Result<(), RfcError>
+where
+    S: Read,
+    D: Write,
+{
+    decode_blocks(src, dst, Z85_ENCODED_BLOCK, |chunk| {
+        z85::decode(chunk).map_err(|err| RfcError::Encoding(err.to_string()))
+    })
+}
+
+#[test]
+fn test_z85() {
+    // Test vector from the ZeroMQ Z85 specification
+    let plain = [0x86, 0x4F, 0xD2, 0x6F, 0xB5, 0x59, 0xF7, 0x5B];
+    let mut encoded = Vec::new();
+    encode_z85(&mut plain.as_slice(), &mut encoded).unwrap();
+    assert_eq!(b"HelloWorld", encoded.as_slice());
+
+    let original_bytes = std::fs::read("./Cargo.toml").unwrap();
+    for len in [0, 1, 2, 3, 4, original_bytes.len()] {
+        let mut encoded = Vec::new();
+        let mut decoded = Vec::new();
+
+        encode_z85(&mut &original_bytes[..len], &mut encoded).unwrap();
+        decode_z85(&mut encoded.as_slice(), &mut decoded).unwrap();
+        assert_eq!(&original_bytes[..len], decoded);
+    }
+
+    assert!(decode_z85(&mut b"Hello Worl".as_slice(), &mut Vec::new()).is_err());
+}
@@ -2,30 +2,116 @@ use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum RfcError {
-    #[error("not implemented")]
+    #[error("not implemented: {0}")]
     NotImplemented(String),
 
-    #[error("io error")]
+    #[error("io error: {0}")]
     IoError(std::io::Error),
 
-    #[error("serialize error")]
+    #[error("output file {0} already exists")]
+    OutputExists(String),
+
+    #[error("input and output are the same file: {0}")]
+    SameFile(String),
+
+    #[error("serialize error: {0}")]
     Serialize(String),
 
-    #[error("deserialize error")]
+    #[error("deserialize error: {0}")]
     Deserialize(String),
 
-    #[error("encoding error")]
+    #[error("metadata error: {0}")]
+    Metadata(String),
+
+    #[error("encoding error: {0}")]
     Encoding(String),
 
-    #[error("encryption error")]
+    #[error("encryption error: {0}")]
     Encryption(String),
 
-    #[error("decryption error")]
+    #[error("decryption error: {0}")]
     Decryption(String),
 
-    #[error("compression error")]
-    Compression,
+    #[error("wrong key or passphrase")]
+    WrongKey,
+
+    #[error("input is truncated: {0}")]
+    Truncated(String),
+
+    #[error("input is corrupted or has been tampered with: {0}")]
+    Tampered(String),
+
+    #[error("unsupported format version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("unknown cipher id {0}")]
+    UnknownCipher(u8),
+
+    #[error("unknown KDF id {0}")]
+    UnknownKdf(u8),
+
+    #[error("input was encrypted with {0}, not {1}")]
+    CipherMismatch(String, String),
+
+    #[error("registry error: {0}")]
+    Registry(String),
+
+    #[error("invalid key file: {0}")]
+    InvalidKey(String),
+
+    #[error("bad signature: {0}")]
+    BadSignature(String),
+
+    #[error("compression error: {0}")]
+    Compression(String),
+
+    #[error("decompression error: {0}")]
+    Decompression(String),
+
+    #[error("thread pool error: {0}")]
+    ThreadPool(String),
+}
+
+impl RfcError {
+    /// Converts an I/O error to an RfcError, unwrapping RfcErrors that were
+    /// wrapped into an I/O error to pass through `std::io::Read` and friends.
+    pub fn from_io(err: std::io::Error) -> Self {
+        match err.get_ref().is_some_and(|inner| inner.is::<Self>()) {
+            true => *err
+                .into_inner()
+                .expect("no inner error")
+                .downcast::<Self>()
+                .expect("inner error is not RfcError"),
+            false => Self::IoError(err),
+        }
+    }
+
+    /// Returns a stable code for the kind of error, used as the process exit code of the
+    /// CLI and as the return value of the C API, so that callers can tell failures apart.
+    /// Code 2 is left for usage errors.
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::WrongKey => 3,
+            Self::Tampered(_) => 4,
+            Self::Truncated(_) => 5,
+            Self::UnsupportedVersion(_) => 6,
+            Self::UnknownCipher(_) => 7,
+            Self::IoError(_) => 8,
+            Self::BadSignature(_) => 9,
+            Self::UnknownKdf(_) => 10,
+            Self::OutputExists(_) => 11,
+            Self::SameFile(_) => 12,
+            Self::CipherMismatch(..) => 13,
+            _ => 1,
+        }
+    }
+}
+
+#[test]
+fn test_from_io() {
+    let err = std::io::Error::other(RfcError::WrongKey);
+    assert!(matches!(RfcError::from_io(err), RfcError::WrongKey));
 
-    #[error("decompression error")]
-    Decompression,
+    let err = std::io::Error::from(std::io::ErrorKind::NotFound);
+    assert!(matches!(RfcError::from_io(err), RfcError::IoError(_)));
 }
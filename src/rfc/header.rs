@@ -0,0 +1,258 @@
+use rkyv::AlignedVec;
+
+use super::error::RfcError;
+use super::mac::MAC_SIZE;
+use super::padding::Padding;
+use super::sign::SIGNATURE_SIZE;
+use super::{KdfId, Mode};
+
+/// Magic bytes at the start of every rfc file, except seekable ones
+pub const MAGIC: &[u8; 4] = b"rfc\0";
+
+/// Current version of the rfc file format
+pub const VERSION: u8 = 2;
+
+/// Size of the plaintext prefix preceding the archived `Header` and the ciphertext.
+///
+/// Layout: `magic (4) | version (1) | cipher id (1) | flags (1) | KDF id (1) |
+/// header length (u32 LE) | plaintext length (u64 LE)`
+///
+/// The ciphertext is the plaintext zero-filled to the cipher's block size. It is followed
+/// by an HMAC-SHA256 tag over everything before it, and signed files then by an Ed25519
+/// signature over everything before that.
+pub const PREFIX_SIZE: usize = 20;
+
+const FLAGS_OFFSET: usize = 6;
+
+const KDF_OFFSET: usize = 7;
+
+const HEADER_LEN_OFFSET: usize = 8;
+
+const PLAINTEXT_LEN_OFFSET: usize = 12;
+
+/// Flag set if the file has an embedded signature
+const FLAG_SIGNED: u8 = 1;
+
+/// Prefix is the parsed plaintext prefix of an rfc file.
+pub struct Prefix<'a> {
+    pub mode: Mode,
+    pub kdf: KdfId,
+    /// The archived header
+    pub header: &'a [u8],
+    /// Length of the padded plaintext, without the cipher's zero-fill
+    pub plaintext_len: usize,
+    pub ciphertext: &'a [u8],
+    /// HMAC-SHA256 tag over the prefix, header and ciphertext
+    pub mac: &'a [u8],
+    /// Embedded signature, if the file is signed
+    pub signature: Option<&'a [u8]>,
+}
+
+/// Header is stored in plaintext, wrapped together with the ciphertext.
+/// It holds everything needed to derive the key and post-process the decrypted bytes.
+#[derive(
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    PartialEq,
+    Debug,
+)]
+#[archive(check_bytes)]
+pub struct Header {
+    /// Serialized parameters of the KDF, e.g. the PBKDF2 salt
+    pub kdf_params: Vec<u8>,
+    /// Padding applied to the plaintext before encryption
+    pub padding: Padding,
+    /// Key-check value, used to detect a wrong key
+    pub key_check: Vec<u8>,
+}
+
+impl Header {
+    /// Archives the header with rkyv.
+    pub fn encode(&self) -> Result<AlignedVec, RfcError> {
+        rkyv::to_bytes::<_, 256>(self).map_err(|err| RfcError::Serialize(err.to_string()))
+    }
+}
+
+/// Returns the prefix of a file encrypted with `mode`, whose key was derived with `kdf`,
+/// with a header archive of `header_len` bytes and `plaintext_len` bytes of padded plaintext.
+/// Signed files must be marked as `signed` here, since the flags are authenticated along
+/// with the rest of the prefix.
+pub fn prefix(
+    mode: Mode,
+    kdf: KdfId,
+    signed: bool,
+    header_len: usize,
+    plaintext_len: usize,
+) -> Result<[u8; PREFIX_SIZE], RfcError> {
+    let header_len = u32::try_from(header_len)
+        .map_err(|_| RfcError::Serialize(format!("header of {} bytes", header_len)))?;
+
+    let mut prefix = [0u8; PREFIX_SIZE];
+    prefix[..4].copy_from_slice(MAGIC);
+    prefix[4] = VERSION;
+    prefix[5] = mode.id();
+    if signed {
+        prefix[FLAGS_OFFSET] |= FLAG_SIGNED;
+    }
+    prefix[KDF_OFFSET] = kdf.id();
+    prefix[HEADER_LEN_OFFSET..PLAINTEXT_LEN_OFFSET].copy_from_slice(&header_len.to_le_bytes());
+    prefix[PLAINTEXT_LEN_OFFSET..].copy_from_slice(&(plaintext_len as u64).to_le_bytes());
+
+    Ok(prefix)
+}
+
+/// Returns the length of the ciphertext of `plaintext_len` bytes, which ciphers with
+/// `block_size` byte blocks encrypt zero-filled to full blocks.
+pub fn ciphertext_len(plaintext_len: usize, block_size: usize) -> Option<usize> {
+    plaintext_len.checked_next_multiple_of(block_size)
+}
+
+/// Parses the prefix of `bytes`, returning it with the header, ciphertext, tag and signature
+/// that follow it.
+pub fn parse_prefix(bytes: &[u8]) -> Result<Prefix<'_>, RfcError> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(RfcError::Deserialize(String::from(
+            "not an rfc encrypted file",
+        )));
+    }
+    if bytes.len() < PREFIX_SIZE {
+        return Err(RfcError::Truncated(format!(
+            "got {} bytes, shorter than the {}-byte prefix",
+            bytes.len(),
+            PREFIX_SIZE
+        )));
+    }
+    if bytes[4] != VERSION {
+        return Err(RfcError::UnsupportedVersion(bytes[4]));
+    }
+
+    let mode = Mode::from_id(bytes[5])?;
+    let kdf = KdfId::from_id(bytes[KDF_OFFSET])?;
+    let signed = bytes[FLAGS_OFFSET] & FLAG_SIGNED != 0;
+    let header_len = u32::from_le_bytes(
+        bytes[HEADER_LEN_OFFSET..PLAINTEXT_LEN_OFFSET]
+            .try_into()
+            .unwrap(),
+    ) as usize;
+    let plaintext_len =
+        u64::from_le_bytes(bytes[PLAINTEXT_LEN_OFFSET..PREFIX_SIZE].try_into().unwrap());
+    let rest = &bytes[PREFIX_SIZE..];
+
+    // A length that does not fit in memory is no shorter than the rest of the file
+    let ciphertext_len = usize::try_from(plaintext_len)
+        .ok()
+        .and_then(|len| ciphertext_len(len, mode.cipher().ok()?.block_size()));
+    let expected_len = ciphertext_len.map_or(u64::MAX, |len| {
+        (header_len as u64)
+            .saturating_add(len as u64)
+            .saturating_add(MAC_SIZE as u64)
+            .saturating_add(if signed { SIGNATURE_SIZE as u64 } else { 0 })
+    });
+
+    match (rest.len() as u64).cmp(&expected_len) {
+        std::cmp::Ordering::Less => Err(RfcError::Truncated(format!(
+            "expected {} bytes after the prefix, got {}",
+            expected_len,
+            rest.len()
+        ))),
+        std::cmp::Ordering::Greater => Err(RfcError::Tampered(format!(
+            "{} unexpected trailing bytes",
+            rest.len() as u64 - expected_len
+        ))),
+        std::cmp::Ordering::Equal => {
+            let (header, rest) = rest.split_at(header_len);
+            let (ciphertext, rest) = rest.split_at(ciphertext_len.unwrap_or_default());
+            let (mac, signature) = rest.split_at(MAC_SIZE);
+            Ok(Prefix {
+                mode,
+                kdf,
+                header,
+                plaintext_len: plaintext_len as usize,
+                ciphertext,
+                mac,
+                signature: signed.then_some(signature),
+            })
+        }
+    }
+}
+
+#[test]
+fn test_parse_prefix() {
+    let header = b"header_bytes";
+    // AES ciphertexts are zero-filled to full blocks
+    let ciphertext = [3; 32];
+    let prefix_of = |signed| prefix(Mode::AES128, KdfId::PBKDF2, signed, header.len(), 20).unwrap();
+    let mut bytes = prefix_of(false).to_vec();
+    bytes.extend_from_slice(header);
+    bytes.extend_from_slice(&ciphertext);
+    bytes.extend_from_slice(&[2; MAC_SIZE]);
+
+    let prefix = parse_prefix(&bytes).expect("failed to parse prefix");
+    assert_eq!(prefix.mode, Mode::AES128);
+    assert_eq!(prefix.kdf, KdfId::PBKDF2);
+    assert_eq!(prefix.header, header);
+    assert_eq!(prefix.plaintext_len, 20);
+    assert_eq!(prefix.ciphertext, ciphertext);
+    assert_eq!(prefix.mac, &[2; MAC_SIZE][..]);
+    assert!(prefix.signature.is_none());
+
+    let mut signed = bytes.clone();
+    signed[..PREFIX_SIZE].copy_from_slice(&prefix_of(true));
+    assert!(matches!(parse_prefix(&signed), Err(RfcError::Truncated(_))));
+    signed.extend_from_slice(&[1; SIGNATURE_SIZE]);
+    let prefix = parse_prefix(&signed).expect("failed to parse signed prefix");
+    assert_eq!(prefix.header, header);
+    assert_eq!(prefix.ciphertext, ciphertext);
+    assert_eq!(prefix.mac, &[2; MAC_SIZE][..]);
+    assert_eq!(prefix.signature, Some(&[1; SIGNATURE_SIZE][..]));
+
+    // Plaintext lengths are checked against the ciphertext, however large
+    let mut huge = bytes.clone();
+    huge[PLAINTEXT_LEN_OFFSET..PREFIX_SIZE].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(matches!(parse_prefix(&huge), Err(RfcError::Truncated(_))));
+    let mut short = bytes.clone();
+    short[PLAINTEXT_LEN_OFFSET] = 16;
+    assert!(matches!(parse_prefix(&short), Err(RfcError::Tampered(_))));
+
+    assert!(matches!(
+        parse_prefix(&bytes[..bytes.len() - 1]),
+        Err(RfcError::Truncated(_))
+    ));
+    assert!(matches!(
+        parse_prefix(&bytes[..10]),
+        Err(RfcError::Truncated(_))
+    ));
+    assert!(matches!(
+        parse_prefix(b"not rfc"),
+        Err(RfcError::Deserialize(_))
+    ));
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(matches!(
+        parse_prefix(&trailing),
+        Err(RfcError::Tampered(_))
+    ));
+
+    let mut version = bytes.clone();
+    version[4] = 42;
+    assert!(matches!(
+        parse_prefix(&version),
+        Err(RfcError::UnsupportedVersion(42))
+    ));
+
+    let mut cipher = bytes.clone();
+    cipher[5] = 42;
+    assert!(matches!(
+        parse_prefix(&cipher),
+        Err(RfcError::UnknownCipher(42))
+    ));
+
+    let mut kdf = bytes;
+    kdf[KDF_OFFSET] = 42;
+    assert!(matches!(parse_prefix(&kdf), Err(RfcError::UnknownKdf(42))));
+}
@@ -0,0 +1,211 @@
+//! Key derivation functions of rfc's own format, and the registry of KDFs by header id.
+//!
+//! PBKDF2 and scrypt are registered by default. Downstream crates can implement `Kdf`
+//! and `register` their own, which are then usable as a `KdfId` by id or name. Files
+//! store the id of their KDF, so decryption needs no KDF option.
+use std::sync::{Arc, LazyLock};
+
+use crate::rfc::error::RfcError;
+use crate::rfc::pbkdf2::KdfPbkdf2;
+use crate::rfc::registry::{Registry, RegistryEntry};
+use crate::rfc::scrypt::KdfScrypt;
+
+/// Key derivation function of rfc's own format, deriving cipher keys from keys and
+/// passphrases. Its parameters, e.g. a salt, are serialized into the header of each file,
+/// and are authenticated by rfc.
+pub trait Kdf: Send + Sync {
+    /// Id of the KDF in file headers, which must be unique
+    fn id(&self) -> u8;
+
+    /// Name of the KDF
+    fn name(&self) -> &'static str;
+
+    /// Returns the serialized parameters for a new file, e.g. a random salt.
+    fn generate_params(&self) -> Result<Vec<u8>, RfcError>;
+
+    /// Derives a key of `len` bytes from `secret` and the serialized `params` of a file.
+    fn derive(&self, secret: &[u8], params: &[u8], len: usize) -> Result<Vec<u8>, RfcError>;
+}
+
+/// KDF used to derive cipher keys from keys and passphrases, by its id in the KDF registry
+/// and in file headers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KdfId(u8);
+
+impl KdfId {
+    /// PBKDF2-HMAC-SHA256 with a random salt
+    pub const PBKDF2: Self = Self(0);
+    /// scrypt with a random salt, as age uses for passphrases
+    pub const SCRYPT: Self = Self(1);
+
+    /// Returns the id identifying the KDF in file headers.
+    pub fn id(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the KDF identified by `id` in file headers.
+    pub fn from_id(id: u8) -> Result<Self, RfcError> {
+        lookup(id).map(|_| Self(id))
+    }
+
+    /// Returns the KDF registered as `name`.
+    pub fn from_name(name: &str) -> Result<Self, RfcError> {
+        let kdf =
+            lookup_name(name).ok_or_else(|| RfcError::Registry(format!("unknown KDF {}", name)))?;
+
+        Ok(Self(kdf.id()))
+    }
+
+    /// Returns the registered KDF.
+    pub fn kdf(self) -> Result<Arc<dyn Kdf>, RfcError> {
+        lookup(self.0)
+    }
+}
+
+impl Default for KdfId {
+    fn default() -> Self {
+        Self::PBKDF2
+    }
+}
+
+impl std::fmt::Display for KdfId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kdf() {
+            Ok(kdf) => write!(f, "{}", kdf.name()),
+            Err(_) => write!(f, "KDF {}", self.0),
+        }
+    }
+}
+
+impl RegistryEntry<u8> for dyn Kdf {
+    fn entry_id(&self) -> u8 {
+        self.id()
+    }
+
+    fn entry_name(&self) -> &'static str {
+        self.name()
+    }
+}
+
+static REGISTRY: LazyLock<Registry<u8, dyn Kdf>> = LazyLock::new(|| {
+    let builtins: [Arc<dyn Kdf>; 2] = [Arc::new(KdfPbkdf2 {}), Arc::new(KdfScrypt {})];
+
+    Registry::new("KDF", builtins)
+});
+
+/// Registers `kdf` under its id. Ids and names already registered cannot be replaced.
+pub fn register<K>(kdf: K) -> Result<(), RfcError>
+where
+    K: Kdf + 'static,
+{
+    REGISTRY.register(Arc::new(kdf))
+}
+
+/// Returns the KDF registered under `id`.
+pub fn lookup(id: u8) -> Result<Arc<dyn Kdf>, RfcError> {
+    REGISTRY.get(id).ok_or(RfcError::UnknownKdf(id))
+}
+
+/// Returns the KDF registered as `name`.
+pub fn lookup_name(name: &str) -> Option<Arc<dyn Kdf>> {
+    REGISTRY.get_name(name)
+}
+
+/// Returns all registered KDFs, ordered by id.
+pub fn registered() -> Vec<Arc<dyn Kdf>> {
+    REGISTRY.entries()
+}
+
+#[test]
+fn test_registry() {
+    use hkdf::Hkdf;
+    use rand_core::{OsRng, RngCore};
+    use sha2::Sha256;
+
+    use std::io::{Cursor, Read};
+
+    use crate::rfc::header::parse_prefix;
+    use crate::rfc::seekable::{encrypt_seekable, SeekableReader, DEFAULT_CHUNK_SIZE};
+    use crate::rfc::{Decryptor, Encryptor, Mode};
+
+    /// HKDF with a random salt, only fit for high-entropy keys
+    struct HkdfTest;
+
+    impl Kdf for HkdfTest {
+        fn id(&self) -> u8 {
+            200
+        }
+
+        fn name(&self) -> &'static str {
+            "hkdf-test"
+        }
+
+        fn generate_params(&self) -> Result<Vec<u8>, RfcError> {
+            let mut salt = vec![0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            Ok(salt)
+        }
+
+        fn derive(&self, secret: &[u8], params: &[u8], len: usize) -> Result<Vec<u8>, RfcError> {
+            let mut key = vec![0u8; len];
+            Hkdf::<Sha256>::new(Some(params), secret)
+                .expand(b"rfc", &mut key)
+                .map_err(|err| RfcError::Encryption(err.to_string()))?;
+
+            Ok(key)
+        }
+    }
+
+    assert_eq!(lookup(0).unwrap().name(), "pbkdf2");
+    assert_eq!(lookup_name("pbkdf2").unwrap().id(), 0);
+    assert!(matches!(lookup(200), Err(RfcError::UnknownKdf(200))));
+
+    register(HkdfTest).unwrap();
+    assert!(register(HkdfTest).is_err());
+    assert_eq!(lookup(200).unwrap().name(), "hkdf-test");
+    assert!(registered().iter().any(|kdf| kdf.name() == "hkdf-test"));
+
+    // rfc's own format stores the KDF id, so decryptors need not know the KDF
+    let kdf = KdfId::from_name("hkdf-test").unwrap();
+    assert_eq!(kdf, KdfId::from_id(200).unwrap());
+    assert_eq!(kdf.to_string(), "hkdf-test");
+    assert_eq!(KdfId::from_name("pbkdf2").unwrap(), KdfId::PBKDF2);
+    assert_eq!(KdfId::from_name("scrypt").unwrap(), KdfId::SCRYPT);
+
+    let encrypted = Encryptor::new()
+        .kdf(kdf)
+        .key("key")
+        .encrypt_buf(&b"plaintext"[..])
+        .unwrap();
+    assert_eq!(parse_prefix(&encrypted).unwrap().kdf, kdf);
+
+    let decryptor = Decryptor::new().key("key");
+    assert_eq!(decryptor.decrypt_buf(&encrypted[..]).unwrap(), b"plaintext");
+    assert!(matches!(
+        decryptor.key("wrong").decrypt_buf(&encrypted[..]),
+        Err(RfcError::WrongKey)
+    ));
+
+    // Seekable files store the KDF id as well
+    let mut seekable = Vec::new();
+    encrypt_seekable(
+        &b"plaintext"[..],
+        &mut seekable,
+        "key",
+        Mode::AES256,
+        kdf,
+        DEFAULT_CHUNK_SIZE,
+    )
+    .unwrap();
+
+    let mut decrypted = Vec::new();
+    SeekableReader::new(Cursor::new(&seekable), "key")
+        .unwrap()
+        .read_to_end(&mut decrypted)
+        .unwrap();
+    assert_eq!(decrypted, b"plaintext");
+    assert!(matches!(
+        SeekableReader::new(Cursor::new(&seekable), "wrong"),
+        Err(RfcError::WrongKey)
+    ));
+}
@@ -0,0 +1,98 @@
+//! Decryption of files written before rfc's versioned format, which have no magic,
+//! version, MAC or key check.
+//!
+//! A legacy file is an archive of the PBKDF2 salt and the output of the AES cipher.
+//! It does not record its cipher, which is AES-256 unless the caller says otherwise,
+//! like the legacy CLI's default. Without a MAC, a wrong key or a corrupted file is
+//! only caught if decompression fails, so legacy files are read but never written.
+use super::aes;
+use super::encoding::Encoding;
+use super::error::RfcError;
+use super::header::MAGIC;
+use super::wrapper::{self, WrapperBytes};
+use super::{KdfId, Mode};
+
+/// Encodings that the legacy CLI wrote
+pub const ENCODINGS: [Encoding; 2] = [Encoding::Hex, Encoding::B64];
+
+/// Returns whether decoded `bytes` are a legacy file, i.e. they do not start with the magic.
+pub fn is_legacy(bytes: &[u8]) -> bool {
+    !bytes.starts_with(MAGIC)
+}
+
+/// Decrypts the legacy file `bytes` with `key`, using `mode` or AES-256 if not given.
+pub fn decrypt(bytes: &[u8], key: Vec<u8>, mode: Option<Mode>) -> Result<Vec<u8>, RfcError> {
+    let mode = mode.unwrap_or(Mode::AES256);
+    if mode != Mode::AES128 && mode != Mode::AES256 {
+        return Err(RfcError::NotImplemented(format!(
+            "legacy files with cipher {}",
+            mode
+        )));
+    }
+
+    let archive = wrapper::aligned(bytes);
+    let wrapped = WrapperBytes::<Vec<u8>>::decode_archived(&archive)
+        .map_err(|_| RfcError::Deserialize(String::from("not an rfc encrypted file")))?;
+
+    let cipher_key = super::cipher_key(key, KdfId::PBKDF2, &wrapped.0, mode)?;
+    // The inner archive is only copied if it is not aligned for rkyv
+    let ciphertext = wrapper::aligned(&wrapped.1);
+
+    aes::decrypt_legacy(mode.cipher()?.as_ref(), &ciphertext, &cipher_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rfc::{Decryptor, Encoding, Mode, RfcError};
+
+    const PLAINTEXT: &[u8] = include_bytes!("../../tests/fixtures/legacy/plaintext.txt");
+    const KEY: &[u8] = include_bytes!("../../tests/fixtures/legacy/key");
+
+    /// Decrypts the fixtures written by tests/fixtures/legacy/generate.sh
+    #[test]
+    fn test_legacy_fixtures() {
+        let aes256 = include_bytes!("../../tests/fixtures/legacy/aes256.bin");
+        let aes128_lz4 = include_bytes!("../../tests/fixtures/legacy/aes128-lz4.bin");
+        let aes256_b64 = include_bytes!("../../tests/fixtures/legacy/aes256.b64");
+
+        let decryptor = Decryptor::new().key(KEY);
+        assert_eq!(decryptor.decrypt_buf(&aes256[..]).unwrap(), PLAINTEXT);
+        // The encoding of legacy files is detected without a magic
+        assert_eq!(decryptor.decrypt_buf(&aes256_b64[..]).unwrap(), PLAINTEXT);
+        assert_eq!(
+            decryptor
+                .clone()
+                .encoding(Encoding::B64)
+                .decrypt_buf(&aes256_b64[..])
+                .unwrap(),
+            PLAINTEXT
+        );
+
+        let decryptor = Decryptor::new()
+            .key(KEY)
+            .cipher(Mode::AES128)
+            .compress(true);
+        assert_eq!(decryptor.decrypt_buf(&aes128_lz4[..]).unwrap(), PLAINTEXT);
+
+        let mut output = Vec::new();
+        decryptor.decrypt_slice(aes128_lz4, &mut output).unwrap();
+        assert_eq!(output, PLAINTEXT);
+
+        // Without a MAC, a wrong key is only caught by decompression
+        let result = Decryptor::new()
+            .key(b"wrong key")
+            .cipher(Mode::AES128)
+            .compress(true)
+            .decrypt_buf(&aes128_lz4[..]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_not_legacy() {
+        let result = Decryptor::new().key(KEY).decrypt_buf(&b"hello, world"[..]);
+        assert!(matches!(result, Err(RfcError::Deserialize(_))));
+
+        let result = Decryptor::new().key(KEY).decrypt_buf(&b""[..]);
+        assert!(result.is_err());
+    }
+}
@@ -1,9 +1,13 @@
 use lz4_flex;
+use rayon::prelude::*;
 
-use std::io::{self, Read, Write};
+use std::io::{self, BufRead, Read, Write};
 
 use super::error::RfcError;
 
+/// Size of plaintext chunks compressed in parallel, each into its own LZ4 frame
+pub const PAR_CHUNK_SIZE: usize = 1024 * 1024;
+
 pub fn compress_bytes<T>(bytes: T) -> Vec<u8>
 where
     T: AsRef<[u8]>,
@@ -11,6 +15,79 @@ where
     lz4_flex::compress(bytes.as_ref())
 }
 
+/// Compresses `bytes` in chunks of `PAR_CHUNK_SIZE` on the current rayon thread pool.
+/// Each chunk is compressed into an independent LZ4 frame, and the frames are
+/// concatenated in order, which `FrameDecoder` reads back as a single stream.
+pub fn compress_bytes_par<T>(bytes: T) -> Result<Vec<u8>, RfcError>
+where
+    T: AsRef<[u8]>,
+{
+    let bytes = bytes.as_ref();
+    if bytes.is_empty() {
+        return compress_to_bytes_sized(bytes, None);
+    }
+
+    let frames = bytes
+        .par_chunks(PAR_CHUNK_SIZE)
+        .map(|chunk| compress_to_bytes_sized(chunk, Some(chunk.len())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(frames.concat())
+}
+
+/// Like `compress_bytes_par`, but reads `from` in batches of one `PAR_CHUNK_SIZE` frame per
+/// thread, so that only the compressed output and the current batch are held in memory.
+/// Returns the uncompressed length and the concatenated frames.
+pub fn compress_reader_par<R>(mut from: R) -> Result<(usize, Vec<u8>), RfcError>
+where
+    R: Read,
+{
+    let batch_size = rayon::current_num_threads();
+    let mut len = 0;
+    let mut compressed = Vec::new();
+
+    loop {
+        let mut chunks = Vec::with_capacity(batch_size);
+        for _ in 0..batch_size {
+            let mut chunk = Vec::with_capacity(PAR_CHUNK_SIZE);
+            (&mut from)
+                .take(PAR_CHUNK_SIZE as u64)
+                .read_to_end(&mut chunk)
+                .map_err(RfcError::IoError)?;
+
+            let full = chunk.len() == PAR_CHUNK_SIZE;
+            if !chunk.is_empty() {
+                chunks.push(chunk);
+            }
+            if !full {
+                break;
+            }
+        }
+
+        let done = chunks.len() < batch_size || chunks.last().map(Vec::len) != Some(PAR_CHUNK_SIZE);
+        let frames = chunks
+            .par_iter()
+            .map(|chunk| compress_to_bytes_sized(chunk.as_slice(), Some(chunk.len())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        len += chunks.iter().map(Vec::len).sum::<usize>();
+        frames
+            .iter()
+            .for_each(|frame| compressed.extend_from_slice(frame));
+
+        if done {
+            break;
+        }
+    }
+
+    // Empty input still gets a frame, like compress_bytes_par
+    if len == 0 {
+        compressed = compress_to_bytes_sized(&[][..], None)?;
+    }
+
+    Ok((len, compressed))
+}
+
 /// Pre-allocates a buffer with capacity `prealloc` if any,
 /// and compresses data from `from` into the buffer,
 /// truncating any buffer extra capacity before returning the buffer.
@@ -50,26 +127,48 @@ where
 {
     let mut compressor = lz4_flex::frame::FrameEncoder::new(to);
 
-    let written =
-        io::copy(&mut from, &mut compressor).map_err(|err| RfcError::IoError(err.into()))?;
+    let written = io::copy(&mut from, &mut compressor)
+        .map_err(|err| lz4_error(err, RfcError::Compression))?;
 
     compressor
         .finish()
-        .map_err(|err| RfcError::IoError(err.into()))?;
+        .map_err(|err| lz4_error(err.into(), RfcError::Compression))?;
 
     Ok(written as usize)
 }
 
-/// Decompresses from Reader `r` to Writer `w`
+/// Decompresses from Reader `r` to Writer `w`.
+/// `r` may contain multiple concatenated LZ4 frames, e.g. from `compress_bytes_par`.
 pub fn decompress_reader_to_writer<R, W>(r: R, mut w: W) -> Result<usize, RfcError>
 where
     R: Read,
     W: Write,
 {
-    let mut decompressor = lz4_flex::frame::FrameDecoder::new(r);
-    let written = io::copy(&mut decompressor, &mut w).map_err(|err| RfcError::IoError(err))?;
+    let mut r = io::BufReader::new(r);
+    let mut written = 0;
 
-    Ok(written as usize)
+    // FrameDecoder stops at the end of a frame, so start a new one for every frame
+    loop {
+        let mut decompressor = lz4_flex::frame::FrameDecoder::new(&mut r);
+        written += io::copy(&mut decompressor, &mut w)
+            .map_err(|err| lz4_error(err, RfcError::Decompression))?;
+
+        if r.fill_buf().map_err(RfcError::IoError)?.is_empty() {
+            return Ok(written as usize);
+        }
+    }
+}
+
+/// Reports errors of the LZ4 frame format with `error`, and other errors, e.g. of the
+/// underlying reader or writer, as I/O errors.
+fn lz4_error(err: io::Error, error: fn(String) -> RfcError) -> RfcError {
+    match err
+        .get_ref()
+        .is_some_and(|inner| inner.is::<lz4_flex::frame::Error>())
+    {
+        true => error(err.to_string()),
+        false => RfcError::IoError(err),
+    }
 }
 
 #[test]
@@ -87,6 +186,26 @@ fn test_compress_bytes() {
     assert_eq!(original, decompressed)
 }
 
+#[test]
+fn test_compress_bytes_par() {
+    let file_bytes = std::fs::read("./Cargo.lock").expect("failed to read file");
+    // Make sure the input spans multiple frames
+    let bytes = file_bytes.repeat(PAR_CHUNK_SIZE * 3 / file_bytes.len());
+
+    for bytes in [&bytes[..], &file_bytes[..], &[]] {
+        let compressed = compress_bytes_par(bytes).expect("failed to compress");
+        let decompressed = decompress_to_bytes_sized(compressed.as_slice(), Some(bytes.len()))
+            .expect("failed to decompress");
+
+        assert_eq!(bytes, decompressed);
+
+        // Reading in batches yields the same frames
+        let (len, streamed) = compress_reader_par(bytes).expect("failed to compress");
+        assert_eq!(len, bytes.len());
+        assert_eq!(streamed, compressed);
+    }
+}
+
 #[test]
 fn testinfilepress() {
     let filename = "./Cargo.lock";
@@ -120,6 +239,7 @@ fn test_compress_file() {
     let infile = std::fs::File::open(filename).expect("failed to open infile");
     let tmp_file = std::fs::OpenOptions::new()
         .create(true)
+        .truncate(true)
         .write(true)
         .read(true)
         .open(tmp_filename)
@@ -151,8 +271,19 @@ pub struct Level(pub Option<i32>);
 impl std::fmt::Display for Level {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.0 {
-            Some(level) => write!(f, "{}", level.to_string()),
+            Some(level) => write!(f, "{}", level),
             None => write!(f, ""),
         }
     }
 }
+
+#[test]
+fn test_decompress_corrupted() {
+    let mut compressed = compress_bytes_par(include_bytes!("./lz4.rs")).unwrap();
+    compressed[0] ^= 1;
+
+    assert!(matches!(
+        decompress_to_bytes_sized(compressed.as_slice(), None),
+        Err(RfcError::Decompression(_))
+    ));
+}
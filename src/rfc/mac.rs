@@ -0,0 +1,126 @@
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use super::error::RfcError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Size of the HMAC-SHA256 key and tag
+pub const MAC_SIZE: usize = 32;
+
+/// Size of the key-check value
+pub const KEY_CHECK_SIZE: usize = 8;
+
+/// HKDF info, which separates the MAC key from the cipher key it is derived from
+const MAC_KEY_INFO: &[u8] = b"rfc hmac-sha256";
+
+/// HKDF info for the key-check value
+const KEY_CHECK_INFO: &[u8] = b"rfc key check";
+
+/// Derives the MAC key from `cipher_key`, the KDF output used for encryption.
+pub fn mac_key(cipher_key: &[u8]) -> [u8; MAC_SIZE] {
+    let mut key = [0u8; MAC_SIZE];
+    Hkdf::<Sha256>::new(None, cipher_key)
+        .expand(MAC_KEY_INFO, &mut key)
+        .expect("MAC_SIZE is a valid HKDF-SHA256 output length");
+
+    key
+}
+
+/// Derives the key-check value from `cipher_key`. It is stored in the header, so that
+/// a wrong key can be told apart from a corrupted file before the ciphertext is authenticated.
+pub fn key_check(cipher_key: &[u8]) -> [u8; KEY_CHECK_SIZE] {
+    let mut check = [0u8; KEY_CHECK_SIZE];
+    Hkdf::<Sha256>::new(None, cipher_key)
+        .expand(KEY_CHECK_INFO, &mut check)
+        .expect("KEY_CHECK_SIZE is a valid HKDF-SHA256 output length");
+
+    check
+}
+
+/// Verifies that `expected` is the key-check value of `cipher_key` in constant time.
+pub fn verify_key_check(cipher_key: &[u8], expected: &[u8]) -> Result<(), RfcError> {
+    match bool::from(key_check(cipher_key).ct_eq(expected)) {
+        true => Ok(()),
+        false => Err(RfcError::WrongKey),
+    }
+}
+
+/// Returns the HMAC-SHA256 tag over the raw `prefix` of a file and its `archive`,
+/// which holds the KDF parameters, padding, key-check value and ciphertext.
+pub fn tag(mac_key: &[u8], prefix: &[u8], archive: &[u8]) -> Vec<u8> {
+    hmac(mac_key, prefix, archive)
+        .finalize()
+        .into_bytes()
+        .to_vec()
+}
+
+/// Verifies `tag` against the raw `prefix` and `archive` in constant time.
+pub fn verify(mac_key: &[u8], prefix: &[u8], archive: &[u8], tag: &[u8]) -> Result<(), RfcError> {
+    hmac(mac_key, prefix, archive)
+        .verify_slice(tag)
+        .map_err(|_| RfcError::Tampered(String::from("HMAC verification failed")))
+}
+
+/// Tag computed incrementally over a file that is written in parts, e.g. segments of its
+/// ciphertext. It equals `tag` over the prefix and the concatenated parts.
+pub struct Tagger(HmacSha256);
+
+impl Tagger {
+    pub fn new(mac_key: &[u8], prefix: &[u8]) -> Self {
+        Self(hmac(mac_key, prefix, &[]))
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        self.0.finalize().into_bytes().to_vec()
+    }
+}
+
+fn hmac(mac_key: &[u8], prefix: &[u8], archive: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(mac_key).expect("HMAC takes keys of any size");
+
+    // The prefix has a fixed size, so its bytes cannot be shifted into the archive
+    mac.update(prefix);
+    mac.update(archive);
+
+    mac
+}
+
+#[test]
+fn test_tag_verify() {
+    let key = mac_key(b"cipher_key");
+    let archive = include_bytes!("./mac.rs");
+    let tag = tag(&key, b"prefix", archive);
+
+    assert_eq!(tag.len(), MAC_SIZE);
+    assert!(verify(&key, b"prefix", archive, &tag).is_ok());
+
+    // Any change to the key, prefix or archive must fail verification
+    let other_key = mac_key(b"other_key");
+    assert!(verify(&other_key, b"prefix", archive, &tag).is_err());
+    assert!(verify(&key, b"prefiy", archive, &tag).is_err());
+    assert!(verify(&key, b"prefix", &archive[1..], &tag).is_err());
+    assert!(verify(&key, b"prefix", archive, &tag[1..]).is_err());
+
+    let mut tagger = Tagger::new(&key, b"prefix");
+    archive.chunks(100).for_each(|part| tagger.update(part));
+    assert_eq!(tagger.finalize(), tag);
+}
+
+#[test]
+fn test_key_check() {
+    let check = key_check(b"cipher_key");
+
+    assert!(verify_key_check(b"cipher_key", &check).is_ok());
+    assert!(matches!(
+        verify_key_check(b"other_key", &check),
+        Err(RfcError::WrongKey)
+    ));
+    assert!(verify_key_check(b"cipher_key", &check[1..]).is_err());
+}
@@ -0,0 +1,240 @@
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use super::error::RfcError;
+use super::wrapper;
+
+/// Permission bits stored and restored. Special bits are dropped, so that restoring a file
+/// from someone else never creates setuid or setgid files.
+const MODE_MASK: u32 = 0o777;
+
+/// Metadata of the original input file. It is encrypted in the header of the plaintext,
+/// which precedes the payload, so it is never stored in plaintext.
+#[derive(
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    PartialEq,
+    Debug,
+    Default,
+)]
+#[archive(check_bytes)]
+pub struct Metadata {
+    /// Base name of the original file
+    pub filename: Option<String>,
+    /// Original (uncompressed) size in bytes
+    pub size: u64,
+    /// Unix permission bits, without the setuid, setgid and sticky bits
+    pub mode: Option<u32>,
+    /// Modification time in seconds since the Unix epoch
+    pub mtime: Option<u64>,
+    /// Free-form user comment
+    pub comment: Option<String>,
+}
+
+impl Metadata {
+    /// Collects metadata from the file at `path`.
+    pub fn from_path<P>(path: P, comment: Option<String>) -> Result<Self, RfcError>
+    where
+        P: AsRef<Path>,
+    {
+        let stat = std::fs::metadata(path.as_ref()).map_err(RfcError::IoError)?;
+
+        Ok(Self {
+            filename: path
+                .as_ref()
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string()),
+            size: stat.len(),
+            mode: file_mode(&stat),
+            mtime: stat
+                .modified()
+                .ok()
+                .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+                .map(|since_epoch| since_epoch.as_secs()),
+            comment,
+        })
+    }
+
+    /// Returns the stored filename if it is a plain file name, i.e. it has no directory
+    /// components that could make us write outside of the current directory.
+    pub fn safe_filename(&self) -> Result<&str, RfcError> {
+        let filename = self
+            .filename
+            .as_deref()
+            .ok_or(RfcError::Metadata(String::from("no filename in metadata")))?;
+
+        match Path::new(filename).file_name() {
+            Some(name) if name == filename => Ok(filename),
+            _ => Err(RfcError::Metadata(format!(
+                "refusing to use unsafe filename {:?}",
+                filename
+            ))),
+        }
+    }
+
+    /// Applies the stored permission bits and modification time to `file`.
+    pub fn apply(&self, file: &std::fs::File) -> Result<(), RfcError> {
+        if let Some(mtime) = self.mtime {
+            file.set_modified(UNIX_EPOCH + Duration::from_secs(mtime))
+                .map_err(RfcError::IoError)?;
+        }
+
+        set_file_mode(file, self.mode)
+    }
+}
+
+impl std::fmt::Display for Metadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "filename: {}", self.filename.as_deref().unwrap_or("-"))?;
+        writeln!(f, "size: {}", self.size)?;
+        match self.mode {
+            Some(mode) => writeln!(f, "mode: {:o}", mode)?,
+            None => writeln!(f, "mode: -")?,
+        }
+        match self.mtime {
+            Some(mtime) => writeln!(f, "mtime: {}", mtime)?,
+            None => writeln!(f, "mtime: -")?,
+        }
+        write!(f, "comment: {}", self.comment.as_deref().unwrap_or("-"))
+    }
+}
+
+#[cfg(unix)]
+fn file_mode(stat: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(stat.permissions().mode() & MODE_MASK)
+}
+
+#[cfg(not(unix))]
+fn file_mode(_stat: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn set_file_mode(file: &std::fs::File, mode: Option<u32>) -> Result<(), RfcError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    match mode {
+        Some(mode) => file
+            .set_permissions(std::fs::Permissions::from_mode(mode & MODE_MASK))
+            .map_err(RfcError::IoError),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn set_file_mode(_file: &std::fs::File, _mode: Option<u32>) -> Result<(), RfcError> {
+    Ok(())
+}
+
+/// Size of the length that precedes the archived metadata in the plaintext
+const HEADER_LEN_SIZE: usize = 4;
+
+/// Encodes optional `metadata` as the header of the plaintext, which precedes the payload.
+/// It is the archived metadata prefixed with its length as u32 LE, so that the payload
+/// is never copied to be wrapped with it.
+pub fn encode_header(metadata: &Option<Metadata>) -> Result<Vec<u8>, RfcError> {
+    let archive =
+        rkyv::to_bytes::<_, 256>(metadata).map_err(|err| RfcError::Serialize(err.to_string()))?;
+    let len = u32::try_from(archive.len())
+        .map_err(|_| RfcError::Serialize(String::from("metadata too large")))?;
+
+    let mut header = Vec::with_capacity(HEADER_LEN_SIZE + archive.len());
+    header.extend_from_slice(&len.to_le_bytes());
+    header.extend_from_slice(&archive);
+
+    Ok(header)
+}
+
+/// Splits a decrypted plaintext into the metadata of its header and the payload,
+/// which is moved to the front of `plaintext` in place.
+pub fn split_payload(mut plaintext: Vec<u8>) -> Result<(Option<Metadata>, Vec<u8>), RfcError> {
+    let truncated = || RfcError::Deserialize(String::from("truncated metadata header"));
+
+    let len = plaintext
+        .get(..HEADER_LEN_SIZE)
+        .map(|len| u32::from_le_bytes(len.try_into().expect("length is 4 bytes")) as usize)
+        .ok_or_else(truncated)?;
+    let end = HEADER_LEN_SIZE + len;
+    let archive = plaintext.get(HEADER_LEN_SIZE..end).ok_or_else(truncated)?;
+
+    let metadata = rkyv::from_bytes::<Option<Metadata>>(&wrapper::aligned(archive))
+        .map_err(|err| RfcError::Deserialize(format!("bad metadata: {}", err)))?;
+    plaintext.drain(..end);
+
+    Ok((metadata, plaintext))
+}
+
+#[test]
+fn test_metadata_from_path() {
+    let filename = "./Cargo.toml";
+    let metadata =
+        Metadata::from_path(filename, Some("my comment".to_string())).expect("no metadata");
+
+    assert_eq!(metadata.filename.as_deref(), Some("Cargo.toml"));
+    assert_eq!(metadata.size, std::fs::metadata(filename).unwrap().len());
+    assert!(metadata.mtime.is_some());
+    assert_eq!(metadata.comment.as_deref(), Some("my comment"));
+}
+
+#[test]
+fn test_safe_filename() {
+    let with_name = |name: &str| Metadata {
+        filename: Some(name.to_string()),
+        ..Default::default()
+    };
+
+    assert_eq!(with_name("foo.txt").safe_filename().unwrap(), "foo.txt");
+    assert!(with_name("../foo.txt").safe_filename().is_err());
+    assert!(with_name("/etc/passwd").safe_filename().is_err());
+    assert!(with_name("..").safe_filename().is_err());
+    assert!(Metadata::default().safe_filename().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_apply_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp_filename = "./tmp_apply_mode";
+    let file = std::fs::File::create(tmp_filename).expect("failed to create tmp file");
+    file.set_permissions(std::fs::Permissions::from_mode(0o4755))
+        .unwrap();
+    let stored = Metadata::from_path(tmp_filename, None).unwrap().mode;
+
+    let metadata = Metadata {
+        mode: Some(0o6755),
+        ..Default::default()
+    };
+    metadata.apply(&file).unwrap();
+    let restored = file.metadata().unwrap().permissions().mode() & 0o7777;
+    std::fs::remove_file(tmp_filename).expect("failed to remove tmp file");
+
+    assert_eq!(stored, Some(0o755));
+    assert_eq!(restored, 0o755);
+}
+
+#[test]
+fn test_split_payload() {
+    let bytes = include_bytes!("./metadata.rs").to_vec();
+    let metadata = Metadata::from_path("./src/rfc/metadata.rs", None).expect("no metadata");
+
+    for metadata in [None, Some(metadata)] {
+        let mut plaintext = encode_header(&metadata).expect("failed to encode");
+        plaintext.extend_from_slice(&bytes);
+        let (split_metadata, payload) = split_payload(plaintext).expect("failed to split");
+
+        assert_eq!(metadata, split_metadata);
+        assert_eq!(bytes, payload);
+    }
+
+    // The header length must be within the plaintext
+    let mut plaintext = encode_header(&None).unwrap();
+    plaintext[0] = 0xff;
+    assert!(split_payload(plaintext).is_err());
+    assert!(split_payload(vec![0, 0]).is_err());
+}
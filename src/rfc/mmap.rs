@@ -0,0 +1,39 @@
+use memmap2::Mmap;
+
+use super::error::RfcError;
+
+/// Memory-maps `file` for reading, so that it can be processed without first being
+/// copied into a heap buffer.
+///
+/// Returns `None` if `file` is not a regular, non-empty file (e.g. a pipe or a terminal),
+/// in which case callers should fall back to streaming reads.
+pub fn map_file(file: &std::fs::File) -> Result<Option<Mmap>, RfcError> {
+    let stat = file.metadata().map_err(RfcError::IoError)?;
+    if !stat.is_file() || stat.len() == 0 {
+        return Ok(None);
+    }
+
+    // SAFETY: the mapping is read-only, and rfc never writes to its input file.
+    // As with any mmap, the caller must not modify or truncate the file while it is mapped.
+    let map = unsafe { Mmap::map(file) }.map_err(RfcError::IoError)?;
+
+    Ok(Some(map))
+}
+
+#[test]
+fn test_map_file() {
+    let filename = "./Cargo.toml";
+    let file = std::fs::File::open(filename).expect("failed to open file");
+    let map = map_file(&file)
+        .expect("failed to map file")
+        .expect("regular file not mapped");
+
+    assert_eq!(std::fs::read(filename).unwrap(), &map[..]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_map_file_fallback() {
+    let file = std::fs::File::open("/dev/null").expect("failed to open /dev/null");
+    assert!(map_file(&file).expect("failed to map file").is_none());
+}
@@ -1,92 +1,358 @@
 pub mod aes;
+pub mod age;
+#[cfg(feature = "tokio")]
+pub mod async_stream;
 pub mod buf;
+pub mod builder;
+pub mod chacha20;
 pub mod cipher;
 pub mod encoding;
 pub mod error;
+pub mod gfc;
+pub mod header;
+pub mod kdf;
+pub mod legacy;
 pub mod lz4;
+pub mod mac;
+pub mod metadata;
+pub mod mmap;
+pub mod openssl;
+pub mod padding;
+pub mod parallel;
 pub mod pbkdf2;
+pub mod registry;
+pub mod scrypt;
+pub mod seekable;
+pub mod sign;
+pub mod stream;
 pub mod wrapper;
 
+use rkyv::AlignedVec;
+
+use std::borrow::Cow;
 use std::io::{Read, Write};
 
 // Exports as lib
-use self::aes::{CipherAes128, CipherAes256};
+#[cfg(feature = "tokio")]
+pub use self::async_stream::{AsyncDecryptReader, AsyncEncryptWriter};
+pub use self::builder::{Decryptor, Encryptor};
+pub use self::encoding::Encoding;
+pub use self::error::RfcError;
+pub use self::kdf::KdfId;
+pub use self::metadata::Metadata;
+pub use self::padding::Padding;
+pub use self::stream::{DecryptReader, EncryptWriter};
+
 use self::cipher::Cipher;
-use self::encoding::Encoding;
-use self::error::RfcError;
-use self::pbkdf2::{generate_salt, pbkdf2_key};
+use self::header::Header;
+use self::sign::{Signing, SigningKey};
 use self::wrapper::WrapperBytes;
 
+/// Options of the core functions, set by the builder methods of `Encryptor` and `Decryptor`.
+///
+/// `kdf`, `padding` and `metadata` are only used during encryption, since decryption reads
+/// them from the header and the encrypted payload. `signing` signs the output
+/// during encryption, and verifies the input's signature during decryption.
+pub(crate) struct Options {
+    pub decrypt: bool,
+    pub key: Vec<u8>,
+    /// Cipher to encrypt with. Decryption reads the cipher from the header,
+    /// and only checks it against `mode` if set.
+    pub mode: Option<Mode>,
+    pub kdf: KdfId,
+    pub codec: Encoding,
+    pub compress: bool,
+    pub padding: Padding,
+    pub metadata: Option<Metadata>,
+    pub signing: Option<Signing>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            decrypt: false,
+            key: Vec::new(),
+            mode: Some(Mode::AES256),
+            kdf: KdfId::default(),
+            codec: Encoding::Plain,
+            compress: false,
+            padding: Padding::None,
+            metadata: None,
+            signing: None,
+        }
+    }
+}
+
 /// core wraps all core rfc logic into a function.
 /// It writes its output to `output`.
-pub fn core<R, W>(
-    decrypt: bool,
-    key: Vec<u8>,
-    mode: Mode,
+pub(crate) fn core<R, W>(
+    options: Options,
     input: R,
     input_len: Option<usize>,
     mut output: W,
-    codec: encoding::Encoding,
-    compress: bool,
 ) -> Result<usize, RfcError>
 where
     R: Read,
     W: Write,
 {
+    let (decrypt, codec, compress) = (options.decrypt, options.codec, options.compress);
     let bytes = pre_process(decrypt, input, input_len, codec, compress)?;
-    let bytes = crypt(decrypt, bytes, key, mode)?;
+    if decrypt && is_stream(&bytes) {
+        return stream::decrypt_core(options, &bytes, output);
+    }
+
+    let (bytes, _) = crypt_payload(options, bytes)?;
 
     post_process_write(decrypt, bytes, codec, compress, &mut output)
 }
 
 /// core_buf wraps all core rfc logic into a function.
-/// It returns the output of rfc core as bytes
-pub fn core_buf<R>(
-    decrypt: bool,
-    key: Vec<u8>,
-    mode: Mode,
+/// It returns the output of rfc core as bytes, as well as the metadata embedded
+/// in decrypted input, if any.
+pub(crate) fn core_buf<R>(
+    options: Options,
     input: R,
     input_len: Option<usize>,
-    codec: encoding::Encoding,
-    compress: bool,
-) -> Result<Vec<u8>, RfcError>
+) -> Result<(Vec<u8>, Option<Metadata>), RfcError>
 where
     R: Read,
 {
+    let (decrypt, codec, compress) = (options.decrypt, options.codec, options.compress);
     let bytes = pre_process(decrypt, input, input_len, codec, compress)?;
-    let bytes = crypt(decrypt, bytes, key, mode)?;
+    if decrypt && is_stream(&bytes) {
+        let mut plaintext = Vec::new();
+        stream::decrypt_core(options, &bytes, &mut plaintext)?;
+        return Ok((plaintext, None));
+    }
+
+    let (bytes, metadata) = crypt_payload(options, bytes)?;
 
-    post_process_buf(decrypt, bytes, codec, compress)
+    Ok((post_process_buf(decrypt, bytes, codec, compress)?, metadata))
+}
+
+/// core_slice is like `core`, but reads its input from a byte slice, e.g. a memory-mapped file.
+/// The input is borrowed, and only copied by decoding, compression or decryption.
+/// Unencoded and unsigned files are encrypted in segments and written as they are
+/// encrypted, so neither the input nor the output is ever buffered whole.
+pub(crate) fn core_slice<W>(
+    options: Options,
+    input: &[u8],
+    mut output: W,
+) -> Result<usize, RfcError>
+where
+    W: Write,
+{
+    let (decrypt, codec, compress) = (options.decrypt, options.codec, options.compress);
+    let bytes = pre_process_slice(decrypt, input, codec, compress)?;
+    if decrypt && is_stream(&bytes) {
+        return stream::decrypt_core(options, &bytes, output);
+    }
+
+    let signed = matches!(options.signing, Some(Signing::Sign(_)));
+    if !decrypt && codec == Encoding::Plain && !signed && options.mode != Some(Mode::NONE) {
+        return encrypt_write(options, &bytes, &mut output);
+    }
+
+    let (bytes, _) = crypt_payload(options, bytes)?;
+
+    post_process_write(decrypt, bytes, codec, compress, &mut output)
+}
+
+/// Returns whether decoded input is an rfc stream, e.g. written by `EncryptWriter`, which
+/// carries its own compression and is decrypted by `stream::decrypt_core`.
+fn is_stream(bytes: &[u8]) -> bool {
+    bytes.starts_with(seekable::MAGIC)
+}
+
+/// Bytes between pre-processing and encryption or post-processing.
+enum Payload<'a> {
+    /// The input itself, which needed no pre-processing
+    Borrowed(&'a [u8]),
+    Owned(Vec<u8>),
+    /// Compressed bytes, archived with their uncompressed length
+    Archived(AlignedVec),
+}
+
+impl Payload<'_> {
+    /// Returns the payload as a vector, copying it unless it is `Owned`.
+    fn into_vec(self) -> Vec<u8> {
+        match self {
+            Self::Owned(bytes) => bytes,
+            payload => payload.to_vec(),
+        }
+    }
+}
+
+impl std::ops::Deref for Payload<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Borrowed(bytes) => bytes,
+            Self::Owned(bytes) => bytes,
+            Self::Archived(bytes) => bytes,
+        }
+    }
+}
+
+impl<'a> From<Cow<'a, [u8]>> for Payload<'a> {
+    fn from(bytes: Cow<'a, [u8]>) -> Self {
+        match bytes {
+            Cow::Borrowed(bytes) => Self::Borrowed(bytes),
+            Cow::Owned(bytes) => Self::Owned(bytes),
+        }
+    }
 }
 
 /// Pre-processes input bytes
 fn pre_process<R>(
     decrypt: bool,
-    mut input: R,
+    input: R,
     input_len: Option<usize>,
     codec: encoding::Encoding,
     compress: bool,
-) -> Result<Vec<u8>, RfcError>
+) -> Result<Payload<'static>, RfcError>
 where
     R: Read,
 {
     match decrypt {
-        true => decode_read(codec, input, input_len),
+        true => decode_read(codec, input, input_len).map(Payload::Owned),
         false => match compress {
-            true => compress_read(input, input_len),
-            false => buf::read_bytes(input, input_len),
+            true => compress_read(input).map(Payload::Archived),
+            false => buf::read_bytes(input, input_len).map(Payload::Owned),
         },
     }
 }
 
-/// Derives new key from `key` using PBKDF2 and use the new key to encrypt/decrypt bytes.
-fn crypt(decrypt: bool, bytes: Vec<u8>, key: Vec<u8>, mode: Mode) -> Result<Vec<u8>, RfcError> {
+/// Pre-processes input bytes from a slice, borrowing `input` if it needs no decoding or compression.
+fn pre_process_slice(
+    decrypt: bool,
+    input: &[u8],
+    codec: encoding::Encoding,
+    compress: bool,
+) -> Result<Payload<'_>, RfcError> {
+    match (decrypt, codec, compress) {
+        (true, Encoding::Plain, _) => Ok(Payload::Borrowed(input)),
+        (true, Encoding::Auto, _) => decode_auto(input).map(Payload::from),
+        (true, Encoding::Armor, _) => {
+            encoding::decode_armor(input).map(|armor| Payload::Owned(armor.bytes))
+        }
+        (true, _, _) => decode_read(codec, input, Some(input.len())).map(Payload::Owned),
+        (false, _, true) => compress_buf(input).map(Payload::Archived),
+        (false, _, false) => Ok(Payload::Borrowed(input)),
+    }
+}
+
+/// Derives new key from `key` using `kdf` and use the new key to encrypt/decrypt bytes.
+/// With `Mode::NONE`, bytes are passed through as is, so that only encoding and compression apply.
+fn crypt(
+    decrypt: bool,
+    bytes: Vec<u8>,
+    key: Vec<u8>,
+    mode: Mode,
+    kdf: KdfId,
+    padding: Padding,
+) -> Result<Vec<u8>, RfcError> {
+    match (decrypt, mode) {
+        (_, Mode::NONE) => Ok(bytes),
+        (false, _) => rfc_encrypt(&[&bytes], key, mode, kdf, padding, None),
+        (true, _) => rfc_decrypt(&bytes, key, Some(mode)),
+    }
+}
+
+/// Prepends the `metadata` header to `bytes` before encrypting, or splits it off after decrypting.
+/// Encrypted files are signed after encryption, and verified before decryption.
+/// Legacy files, which predate the magic, are decrypted by `legacy`.
+fn crypt_payload(
+    options: Options,
+    bytes: Payload,
+) -> Result<(Vec<u8>, Option<Metadata>), RfcError> {
+    let Options {
+        decrypt,
+        key,
+        mode,
+        kdf,
+        padding,
+        metadata,
+        signing,
+        ..
+    } = options;
+
+    if mode == Some(Mode::NONE) {
+        // Without encryption, there is no header to carry metadata or signatures
+        if metadata.is_some() || signing.is_some() {
+            return Err(RfcError::NotImplemented(String::from(
+                "metadata and signatures without encryption",
+            )));
+        }
+
+        return crypt(decrypt, bytes.into_vec(), key, Mode::NONE, kdf, padding)
+            .map(|bytes| (bytes, None));
+    }
+
     match decrypt {
-        false => rfc_encrypt(bytes, key, mode),
-        true => rfc_decrypt(bytes, key, mode),
+        false => {
+            let mode = mode.ok_or(RfcError::Encryption(String::from(
+                "no cipher to encrypt with",
+            )))?;
+            let metadata = metadata::encode_header(&metadata)?;
+            let signing_key = match signing {
+                Some(Signing::Sign(signing_key)) => Some(signing_key),
+                _ => None,
+            };
+
+            rfc_encrypt(
+                &[&metadata, &bytes],
+                key,
+                mode,
+                kdf,
+                padding,
+                signing_key.as_ref(),
+            )
+            .map(|file| (file, None))
+        }
+        true if legacy::is_legacy(&bytes) => {
+            if signing.is_some() {
+                return Err(RfcError::BadSignature(String::from(
+                    "legacy files are not signed",
+                )));
+            }
+
+            legacy::decrypt(&bytes, key, mode).map(|bytes| (bytes, None))
+        }
+        true => {
+            if let Some(Signing::Verify(verifying_key)) = signing {
+                sign::verify_embedded(&bytes, &verifying_key)?;
+            }
+
+            let payload = rfc_decrypt(&bytes, key, mode)?;
+            let (metadata, bytes) = metadata::split_payload(payload)?;
+            Ok((bytes, metadata))
+        }
     }
 }
 
+/// Encrypts `bytes` like `crypt_payload`, writing the file to `output` with `rfc_encrypt_write`.
+fn encrypt_write<W: Write>(
+    options: Options,
+    bytes: &[u8],
+    output: &mut W,
+) -> Result<usize, RfcError> {
+    let mode = options.mode.ok_or(RfcError::Encryption(String::from(
+        "no cipher to encrypt with",
+    )))?;
+    let metadata = metadata::encode_header(&options.metadata)?;
+
+    rfc_encrypt_write(
+        &[&metadata, bytes],
+        options.key,
+        mode,
+        options.kdf,
+        options.padding,
+        output,
+    )
+}
+
 /// Post-processes bytes and writes the result to output.
 fn post_process_write<W: Write>(
     decrypt: bool,
@@ -122,41 +388,193 @@ fn post_process_buf(
     }
 }
 
-/// Expand key with some random salt, and uses the derived key to encrypt `bytes`.
-/// The encryption output is concatenated with salt and archived using rkyv.
-fn rfc_encrypt(bytes: Vec<u8>, key: Vec<u8>, mode: Mode) -> Result<Vec<u8>, RfcError> {
-    let salt = generate_salt()?;
+/// Size of the plaintext segments that `rfc_encrypt_write` encrypts at a time
+const SEGMENT_SIZE: usize = 64 * 1024;
+
+/// Cipher, key, header and lengths of a file being encrypted.
+struct Seal {
+    cipher: std::sync::Arc<dyn Cipher>,
+    cipher_key: Vec<u8>,
+    prefix: [u8; header::PREFIX_SIZE],
+    header: AlignedVec,
+    ciphertext_len: usize,
+}
+
+impl Seal {
+    /// Expands `key` with `kdf` and new parameters, e.g. a random salt, for a plaintext of `len` bytes.
+    fn new(
+        len: usize,
+        key: Vec<u8>,
+        mode: Mode,
+        kdf: KdfId,
+        padding: Padding,
+        signed: bool,
+    ) -> Result<Self, RfcError> {
+        let cipher = mode.cipher()?;
+        let kdf_params = kdf.kdf()?.generate_params()?;
+        let cipher_key = cipher_key(key, kdf, &kdf_params, mode)?;
+
+        let header = Header {
+            key_check: mac::key_check(&cipher_key).to_vec(),
+            kdf_params,
+            padding,
+        }
+        .encode()?;
 
-    let ciphertext = match mode {
-        Mode::Aes128 => CipherAes128::encrypt(
-            bytes,
-            pbkdf2_key::<{ CipherAes128::KEY_SIZE }, _, _>(key, &salt)?,
-        ),
-        Mode::Aes256 => CipherAes256::encrypt(
-            bytes,
-            pbkdf2_key::<{ CipherAes256::KEY_SIZE }, _, _>(key, &salt)?,
-        ),
-    }?;
+        let plaintext_len = padding::padded_len(len, padding);
+        let ciphertext_len = header::ciphertext_len(plaintext_len, cipher.block_size())
+            .ok_or(RfcError::Encryption(String::from("input too large")))?;
+        let prefix = header::prefix(mode, kdf, signed, header.len(), plaintext_len)?;
 
-    WrapperBytes::<Vec<u8>>(salt, ciphertext).encode()
+        Ok(Self {
+            cipher,
+            cipher_key,
+            prefix,
+            header,
+            ciphertext_len,
+        })
+    }
+
+    /// Returns the length of the file without signature.
+    fn file_len(&self) -> usize {
+        header::PREFIX_SIZE + self.header.len() + self.ciphertext_len + mac::MAC_SIZE
+    }
 }
 
-/// Extracts salt and ciphertext embedded in `bytes` and uses salt to derive the encryption key,
-/// and uses the key to decrypt data, returning the bytes.
-fn rfc_decrypt(bytes: Vec<u8>, key: Vec<u8>, mode: Mode) -> Result<Vec<u8>, RfcError> {
-    let wrapped = WrapperBytes::<Vec<u8>>::decode_archived(&bytes)?;
-    let (salt, bytes) = (&wrapped.0, &wrapped.1);
+/// Encrypts the plaintext `parts`, e.g. the metadata header and the payload, with padding.
+/// The file is assembled in a single buffer: the prefix, the archived header and the
+/// plaintext, which is then encrypted in place. The prefix, header and ciphertext are
+/// authenticated with HMAC-SHA256 using a subkey of the derived key, and signed with
+/// `signing_key` if given.
+fn rfc_encrypt(
+    parts: &[&[u8]],
+    key: Vec<u8>,
+    mode: Mode,
+    kdf: KdfId,
+    padding: Padding,
+    signing_key: Option<&SigningKey>,
+) -> Result<Vec<u8>, RfcError> {
+    let len = parts.iter().map(|part| part.len()).sum();
+    let seal = Seal::new(len, key, mode, kdf, padding, signing_key.is_some())?;
+
+    let signature_len = signing_key.map_or(0, |_| sign::SIGNATURE_SIZE);
+    let mut output = Vec::with_capacity(seal.file_len() + signature_len);
+    output.extend_from_slice(&seal.prefix);
+    output.extend_from_slice(&seal.header);
 
-    match mode {
-        Mode::Aes128 => CipherAes128::decrypt(
-            bytes.to_vec(),
-            pbkdf2_key::<{ CipherAes128::KEY_SIZE }, _, _>(key, &salt)?,
-        ),
-        Mode::Aes256 => CipherAes256::decrypt(
-            bytes.to_vec(),
-            pbkdf2_key::<{ CipherAes256::KEY_SIZE }, _, _>(key, &salt)?,
-        ),
+    let start = output.len();
+    parts.iter().for_each(|part| output.extend_from_slice(part));
+    padding::pad(&mut output, len, padding);
+    output.resize(start + seal.ciphertext_len, 0);
+    seal.cipher
+        .encrypt(&mut output[start..], 0, &seal.cipher_key)?;
+
+    let (prefix, body) = output.split_at(header::PREFIX_SIZE);
+    let tag = mac::tag(&mac::mac_key(&seal.cipher_key), prefix, body);
+    output.extend_from_slice(&tag);
+
+    match signing_key {
+        Some(signing_key) => Ok(sign::sign_embedded(output, signing_key)),
+        None => Ok(output),
+    }
+}
+
+/// Encrypts the plaintext `parts` like `rfc_encrypt`, without signing, but writes the file
+/// to `output` one segment of `SEGMENT_SIZE` at a time. Only the segment being encrypted
+/// is buffered, and the tag is computed as the segments are written.
+fn rfc_encrypt_write<W: Write>(
+    parts: &[&[u8]],
+    key: Vec<u8>,
+    mode: Mode,
+    kdf: KdfId,
+    padding: Padding,
+    output: &mut W,
+) -> Result<usize, RfcError> {
+    let len: usize = parts.iter().map(|part| part.len()).sum();
+    let seal = Seal::new(len, key, mode, kdf, padding, false)?;
+
+    // The padding marker, and zeros up to the block size, follow the parts
+    let marker = padding::marker(padding);
+    let zeros = (seal.ciphertext_len - len - marker.len()) as u64;
+    let mut plaintext: Box<dyn Read> = Box::new(std::io::empty());
+    for part in parts {
+        plaintext = Box::new(plaintext.chain(*part));
+    }
+    let mut plaintext = plaintext
+        .chain(marker)
+        .chain(std::io::repeat(0).take(zeros));
+
+    let mut tagger = mac::Tagger::new(&mac::mac_key(&seal.cipher_key), &seal.prefix);
+    tagger.update(&seal.header);
+    buf::write_bytes(&mut *output, seal.prefix)?;
+    buf::write_bytes(&mut *output, &seal.header)?;
+
+    let segment_size = SEGMENT_SIZE.next_multiple_of(seal.cipher.block_size());
+    let mut segment = vec![0u8; std::cmp::min(segment_size, seal.ciphertext_len)];
+    let mut offset = 0;
+    while offset < seal.ciphertext_len {
+        let segment = &mut segment[..std::cmp::min(segment_size, seal.ciphertext_len - offset)];
+        plaintext.read_exact(segment).map_err(RfcError::IoError)?;
+        seal.cipher
+            .encrypt(segment, offset as u64, &seal.cipher_key)?;
+
+        tagger.update(segment);
+        buf::write_bytes(&mut *output, &segment)?;
+        offset += segment.len();
+    }
+
+    buf::write_bytes(&mut *output, tagger.finalize())?;
+
+    Ok(seal.file_len())
+}
+
+/// Extracts header and ciphertext embedded in `bytes` and derives the encryption key with
+/// the cipher, KDF and parameters of the header. If `expected` is set, the cipher of the header must match it.
+/// The key and the ciphertext are verified before the ciphertext is copied once and
+/// decrypted in place, returning the bytes with padding removed.
+fn rfc_decrypt(bytes: &[u8], key: Vec<u8>, expected: Option<Mode>) -> Result<Vec<u8>, RfcError> {
+    use rkyv::Deserialize;
+
+    let prefix = header::parse_prefix(bytes)?;
+    let mode = prefix.mode;
+    if let Some(expected) = expected.filter(|expected| *expected != mode) {
+        return Err(RfcError::CipherMismatch(
+            mode.to_string(),
+            expected.to_string(),
+        ));
     }
+
+    let archive = wrapper::aligned(prefix.header);
+    let header = rkyv::check_archived_root::<Header>(&archive)
+        .map_err(|err| RfcError::Tampered(format!("bad header: {}", err)))?;
+    let padding: Padding = header
+        .padding
+        .deserialize(&mut rkyv::Infallible)
+        .map_err(|err| RfcError::Deserialize(err.to_string()))?;
+
+    let cipher_key = cipher_key(key, prefix.kdf, &header.kdf_params, mode)?;
+    mac::verify_key_check(&cipher_key, &header.key_check)?;
+
+    let (raw_prefix, rest) = bytes.split_at(header::PREFIX_SIZE);
+    let body = &rest[..prefix.header.len() + prefix.ciphertext.len()];
+    mac::verify(&mac::mac_key(&cipher_key), raw_prefix, body, prefix.mac)?;
+
+    let mut plaintext = prefix.ciphertext.to_vec();
+    mode.cipher()?.decrypt(&mut plaintext, 0, &cipher_key)?;
+    plaintext.truncate(prefix.plaintext_len);
+
+    padding::unpad(plaintext, padding)
+}
+
+/// Derives the key for `mode` from `key` with `kdf` and its serialized `kdf_params`.
+fn cipher_key(
+    key: Vec<u8>,
+    kdf: KdfId,
+    kdf_params: &[u8],
+    mode: Mode,
+) -> Result<Vec<u8>, RfcError> {
+    let key_size = mode.cipher()?.key_size();
+    kdf.kdf()?.derive(&key, kdf_params, key_size)
 }
 
 /// Encode `bytes` and write the result to `output`.
@@ -167,8 +585,14 @@ where
 {
     match codec {
         Encoding::B64 => encoding::encode_b64(&mut bytes.as_ref(), output),
-        Encoding::Hex => buf::write_bytes(output, encoding::encode_hex_buf(bytes)),
-        _ => buf::write_bytes(output, &bytes),
+        Encoding::Hex => encoding::encode_hex(&mut bytes.as_ref(), output),
+        Encoding::B64Url => encoding::encode_b64url(&mut bytes.as_ref(), output),
+        Encoding::B32 => encoding::encode_b32(&mut bytes.as_ref(), output),
+        Encoding::B58 => encoding::encode_b58(&mut bytes.as_ref(), output),
+        Encoding::Z85 => encoding::encode_z85(&mut bytes.as_ref(), output),
+        Encoding::Armor => buf::write_bytes(output, encoding::encode_armor(bytes.as_ref(), &[])),
+        Encoding::Plain => buf::write_bytes(output, &bytes),
+        Encoding::Auto => Err(auto_encoding_error()),
     }
 }
 
@@ -177,7 +601,14 @@ fn encode_buf(codec: Encoding, bytes: Vec<u8>) -> Result<Vec<u8>, RfcError> {
     match codec {
         Encoding::B64 => encoding::encode_b64_buf(&mut bytes.as_slice(), bytes.len()),
         Encoding::Hex => Ok(encoding::encode_hex_buf(bytes)),
-        _ => Ok(bytes),
+        Encoding::Armor => Ok(encoding::encode_armor(&bytes, &[])),
+        Encoding::Plain => Ok(bytes),
+        Encoding::Auto => Err(auto_encoding_error()),
+        _ => {
+            let mut buf = Vec::with_capacity(bytes.len() * 2);
+            encode_write(codec, &mut buf, bytes)?;
+            Ok(buf)
+        }
     }
 }
 
@@ -202,32 +633,87 @@ where
             buf.truncate(buf.len());
             Ok(buf)
         }
-        encoding::Encoding::Hex => {
+        Encoding::Hex => decode_into(encoding::decode_hex, input, input_len),
+        encoding::Encoding::Armor => {
             let bytes = buf::read_bytes(input, input_len)?;
-            encoding::decode_hex_buf(bytes)
+            encoding::decode_armor(&bytes).map(|armor| armor.bytes)
         }
+        Encoding::B64Url => decode_into(encoding::decode_b64url, input, input_len),
+        Encoding::B32 => decode_into(encoding::decode_b32, input, input_len),
+        Encoding::B58 => decode_into(encoding::decode_b58, input, input_len),
+        Encoding::Z85 => decode_into(encoding::decode_z85, input, input_len),
+        Encoding::Auto => {
+            let bytes = buf::read_bytes(input, input_len)?;
+
+            let decoded = match decode_auto(&bytes)? {
+                Cow::Owned(decoded) => Some(decoded),
+                Cow::Borrowed(_) => None,
+            };
+
+            Ok(decoded.unwrap_or(bytes))
+        }
+    }
+}
+
+/// Decodes encrypted input of unknown encoding, borrowing it if it is not encoded.
+/// Legacy files have no magic to detect, so input that is no current rfc file is decoded
+/// with the first encoding of legacy files that accepts all of it, if any.
+fn decode_auto(bytes: &[u8]) -> Result<Cow<'_, [u8]>, RfcError> {
+    match encoding::detect_encoding(bytes) {
+        Ok(Encoding::Plain) => Ok(Cow::Borrowed(bytes)),
+        Ok(Encoding::Armor) => encoding::decode_armor(bytes).map(|armor| Cow::Owned(armor.bytes)),
+        Ok(codec) => decode_read(codec, bytes, Some(bytes.len())).map(Cow::Owned),
+        Err(_) => Ok(legacy::ENCODINGS
+            .iter()
+            .find_map(|codec| decode_read(*codec, bytes, Some(bytes.len())).ok())
+            .map_or(Cow::Borrowed(bytes), Cow::Owned)),
     }
 }
 
+fn auto_encoding_error() -> RfcError {
+    RfcError::Encoding(String::from(
+        "auto encoding detection is only supported for decryption",
+    ))
+}
+
+/// Decodes `input` with the streaming `decode` function into a byte vector.
+fn decode_into<R, F>(decode: F, mut input: R, input_len: Option<usize>) -> Result<Vec<u8>, RfcError>
+where
+    R: Read,
+    F: FnOnce(&mut R, &mut Vec<u8>) -> Result<(), RfcError>,
+{
+    // Encoded input is always larger than its decoded bytes
+    let mut buf = Vec::with_capacity(input_len.unwrap_or(0));
+    decode(&mut input, &mut buf)?;
+
+    Ok(buf)
+}
+
 /// Reads bytes from `input`, encoding the original uncompressed length to the output bytes
 /// so that we can accurately allocate a buffer for decompression.
-fn compress_read<R>(input: R, input_len: Option<usize>) -> Result<Vec<u8>, RfcError>
+fn compress_read<R>(input: R) -> Result<AlignedVec, RfcError>
 where
     R: Read,
 {
-    let (uncompressed_len, compressed): (usize, Vec<u8>) = match input_len {
-        // If input_len is not given, then read to bytes before compress so that we know uncompressed length
-        None => {
-            let bytes = buf::read_bytes(input, Some(1024))?;
-            (bytes.len(), lz4::compress_bytes(&bytes))
-        }
-        Some(len) => {
-            let compressed = lz4::compress_to_bytes_sized(input, input_len)?;
-            (len, compressed)
-        }
-    };
+    // Frames are read and compressed in batches, so the input is never buffered whole
+    let (len, compressed) = lz4::compress_reader_par(input)?;
 
-    WrapperBytes::<usize>(uncompressed_len, compressed).encode()
+    wrap_compressed(len, compressed)
+}
+
+/// Compresses `bytes`, encoding the original uncompressed length to the output bytes.
+fn compress_buf(bytes: &[u8]) -> Result<AlignedVec, RfcError> {
+    let compressed = lz4::compress_bytes_par(bytes)?;
+
+    wrap_compressed(bytes.len(), compressed)
+}
+
+/// Archives LZ4 frames with their uncompressed `len`, which only sizes the decompression buffer.
+/// rkyv archives `usize` as `u32`, so larger lengths are saturated rather than wrapped.
+fn wrap_compressed(len: usize, compressed: Vec<u8>) -> Result<AlignedVec, RfcError> {
+    let len = std::cmp::min(len, u32::MAX as usize);
+
+    WrapperBytes::<usize>(len, compressed).encode()
 }
 
 /// Decompresses bytes and write the decompressed bytes to output.
@@ -251,16 +737,52 @@ fn decompress_buf(bytes: Vec<u8>) -> Result<Vec<u8>, RfcError> {
     lz4::decompress_to_bytes_sized(compressed.as_slice(), Some(uncompressed_len as usize))
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-pub enum Mode {
-    Aes128,
-    Aes256,
+/// Cipher of rfc's own format, by its id in the cipher registry and in file headers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Mode(u8);
+
+impl Mode {
+    /// No encryption, for encoding and compression only. Never written to file headers.
+    pub const NONE: Self = Self(0);
+    pub const AES128: Self = Self(1);
+    pub const AES256: Self = Self(2);
+    pub const CHACHA20: Self = Self(3);
+
+    /// Returns the id identifying the mode in file headers.
+    pub fn id(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the mode identified by `id` in file headers.
+    pub fn from_id(id: u8) -> Result<Self, RfcError> {
+        cipher::lookup(id).map(|_| Self(id))
+    }
+
+    /// Returns the mode of the cipher registered as `name`.
+    pub fn from_name(name: &str) -> Result<Self, RfcError> {
+        let cipher = cipher::lookup_name(name)
+            .ok_or_else(|| RfcError::Registry(format!("unknown cipher {}", name)))?;
+
+        Ok(Self(cipher.id()))
+    }
+
+    /// Returns the registered cipher of the mode.
+    pub fn cipher(self) -> Result<std::sync::Arc<dyn Cipher>, RfcError> {
+        match self {
+            Self::NONE => Err(RfcError::Encryption(String::from(
+                "mode none does not encrypt, and has no key",
+            ))),
+            _ => cipher::lookup(self.0),
+        }
+    }
 }
+
 impl std::fmt::Display for Mode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Aes128 => write!(f, "aes128"),
-            Self::Aes256 => write!(f, "aes256"),
+        match self.cipher() {
+            Ok(cipher) => write!(f, "{}", cipher.name()),
+            Err(_) if *self == Self::NONE => write!(f, "none"),
+            Err(_) => write!(f, "cipher {}", self.0),
         }
     }
 }
@@ -268,32 +790,34 @@ impl std::fmt::Display for Mode {
 #[cfg(test)]
 pub mod tests {
     use super::{
-        buf::open_file,
-        core, core_buf, crypt,
+        buf::open_input,
+        core, core_buf, core_slice, crypt, encoding,
         encoding::Encoding::{self, *},
-        Cipher, Mode,
+        header, sign, wrapper, Cipher, Header, KdfId, Metadata, Mode, Options, Padding, RfcError,
+        Signing,
     };
     use std::io::Read;
 
     #[test]
     fn test_crypt() {
-        test_rfc_crypt(Mode::Aes128);
-        test_rfc_crypt(Mode::Aes256);
+        test_rfc_crypt(Mode::AES128);
+        test_rfc_crypt(Mode::AES256);
+        test_rfc_crypt(Mode::CHACHA20);
     }
 
     #[test]
     fn test_core_file() {
-        let modes: Vec<Mode> = vec![Mode::Aes128, Mode::Aes256];
-        let encodings: Vec<Encoding> = vec![Plain, Hex, B64];
+        let modes: Vec<Mode> = vec![Mode::AES128, Mode::AES256];
+        let encodings: Vec<Encoding> = vec![Plain, Hex, B64, B64Url, B32, B58, Z85, Armor];
         let compresses: [bool; 2] = [false, true];
 
         let infiles = vec!["./Cargo.toml"];
         let key = b"this_is_my_key".to_vec();
 
         infiles.into_iter().for_each(|filename| {
-            let mut infile = open_file(filename, false).unwrap();
-            let infile_len = Some(infile.metadata().unwrap().len() as usize);
-            let mut plaintext = Vec::with_capacity(infile_len.unwrap());
+            let mut infile = open_input(filename).unwrap();
+            let infile_len = infile.metadata().unwrap().len() as usize;
+            let mut plaintext = Vec::with_capacity(infile_len);
 
             infile.read_to_end(&mut plaintext).unwrap();
 
@@ -301,7 +825,7 @@ pub mod tests {
                 encodings.iter().for_each(|codec| {
                     modes.iter().for_each(|mode| {
                         // Open again for every sub-test
-                        let infile = open_file(filename, false).unwrap();
+                        let infile = open_input(filename).unwrap();
                         println!(
                             "testing with mode: {mode}, compress: {compress}, encoding: {codec}"
                         );
@@ -311,7 +835,7 @@ pub mod tests {
                             *mode,
                             key.clone(),
                             &infile,
-                            infile_len,
+                            Some(infile_len),
                             *codec,
                             *compress,
                         )
@@ -341,17 +865,17 @@ pub mod tests {
             expected_bytes.capacity()
         );
 
-        core(
-            encrypt,
-            key.clone(),
-            mode,
-            input,
-            input_len,
-            &mut ciphertext,
+        let options = |decrypt, codec| Options {
+            decrypt,
+            key: key.clone(),
+            mode: Some(mode),
             codec,
             compress,
-        )
-        .expect("encryption failed");
+            ..Options::default()
+        };
+
+        core(options(encrypt, codec), input, input_len, &mut ciphertext)
+            .expect("encryption failed");
 
         println!(
             "ciphertext len: {} cap: {}",
@@ -367,14 +891,10 @@ pub mod tests {
         );
 
         core(
-            decrypt,
-            key,
-            mode,
+            options(decrypt, codec),
             &ciphertext[..],
             Some(ciphertext.len()),
             &mut decrypted,
-            codec,
-            compress,
         )
         .expect("decryption failed");
 
@@ -385,24 +905,45 @@ pub mod tests {
         );
 
         assert_eq!(expected_bytes, decrypted);
+
+        let mut detected = Vec::<u8>::with_capacity(expected_bytes.len());
+        core(
+            options(decrypt, Auto),
+            &ciphertext[..],
+            Some(ciphertext.len()),
+            &mut detected,
+        )
+        .expect("decryption with detected encoding failed");
+
+        assert_eq!(expected_bytes, detected);
     }
 
     #[test]
     fn test_core_buf() {
-        let encodings: Vec<Encoding> = vec![Plain, Hex, B64];
-        let modes: Vec<Mode> = vec![Mode::Aes128, Mode::Aes256];
-        let compresses = vec![false, true];
+        let encodings: Vec<Encoding> = vec![Plain, Hex, B64, Armor];
+        let modes: Vec<Mode> = vec![Mode::AES128, Mode::AES256];
+        let compresses = [false, true];
+        let paddings = [Padding::None, Padding::Padme];
 
         let key = b"this_is_my_key".to_vec();
         test_cases().into_iter().for_each(|plaintext| {
             compresses.iter().for_each(|compress| {
                 encodings.iter().for_each(|codec| {
                     modes.iter().for_each(|mode| {
-                        println!(
-                            "testing with mode: {mode}, compress: {compress}, encoding: {codec}"
-                        );
+                        paddings.iter().for_each(|padding| {
+                            println!(
+                                "testing with mode: {mode}, compress: {compress}, encoding: {codec}, padding: {padding}"
+                            );
 
-                        test_rfc_core_buf(plaintext.clone(), key.clone(), *mode, *codec, *compress)
+                            test_rfc_core_buf(
+                                plaintext.clone(),
+                                key.clone(),
+                                *mode,
+                                *codec,
+                                *compress,
+                                *padding,
+                            )
+                        })
                     })
                 })
             })
@@ -415,32 +956,358 @@ pub mod tests {
         mode: Mode,
         codec: Encoding,
         compress: bool,
+        padding: Padding,
     ) {
-        let ciphertext = core_buf(
-            false,
-            key.clone(),
-            mode,
-            &plaintext[..],
-            Some(plaintext.len()),
+        let options = |decrypt, padding| Options {
+            decrypt,
+            key: key.clone(),
+            mode: Some(mode),
             codec,
             compress,
+            padding,
+            ..Options::default()
+        };
+
+        let (ciphertext, _) = core_buf(
+            options(false, padding),
+            &plaintext[..],
+            Some(plaintext.len()),
         )
         .expect("encryption failed");
 
-        let decrypted = core_buf(
-            true,
-            key,
-            mode,
+        let (decrypted, _) = core_buf(
+            options(true, Padding::None),
             &ciphertext[..],
             Some(ciphertext.len()),
-            codec,
-            compress,
         )
         .expect("decryption failed");
 
         assert_eq!(plaintext, decrypted);
     }
 
+    #[test]
+    fn test_core_slice() {
+        let key = b"this_is_my_key".to_vec();
+        let mode = Mode::AES256;
+
+        for plaintext in test_cases() {
+            for codec in [Plain, Hex, B64, Armor] {
+                for compress in [false, true] {
+                    let options = |decrypt| Options {
+                        decrypt,
+                        key: key.clone(),
+                        mode: Some(mode),
+                        codec,
+                        compress,
+                        ..Options::default()
+                    };
+
+                    let mut ciphertext = Vec::new();
+                    core_slice(options(false), &plaintext, &mut ciphertext)
+                        .expect("encryption failed");
+
+                    let (decrypted, _) =
+                        core_buf(options(true), &ciphertext[..], Some(ciphertext.len()))
+                            .expect("decryption failed");
+                    assert_eq!(plaintext, decrypted);
+
+                    // Misaligned input must not trip up rkyv
+                    let mut misaligned = vec![0u8];
+                    misaligned.extend_from_slice(&ciphertext);
+
+                    let mut decrypted = Vec::new();
+                    core_slice(options(true), &misaligned[1..], &mut decrypted)
+                        .expect("decryption failed");
+                    assert_eq!(plaintext, decrypted);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_core_slice_segments() {
+        // Spans several segments, the last one partial
+        let plaintext: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        let metadata = Metadata {
+            filename: Some(String::from("segments")),
+            size: plaintext.len() as u64,
+            ..Default::default()
+        };
+
+        for mode in [Mode::AES128, Mode::AES256] {
+            for padding in [Padding::None, Padding::Padme] {
+                let options = |decrypt| Options {
+                    decrypt,
+                    key: b"this_is_my_key".to_vec(),
+                    mode: Some(mode),
+                    padding,
+                    metadata: Some(metadata.clone()),
+                    ..Options::default()
+                };
+
+                let mut ciphertext = Vec::new();
+                let written = core_slice(options(false), &plaintext, &mut ciphertext)
+                    .expect("encryption failed");
+                assert_eq!(written, ciphertext.len());
+
+                let (decrypted, decrypted_metadata) =
+                    core_buf(options(true), &ciphertext[..], None).expect("decryption failed");
+                assert_eq!(plaintext, decrypted);
+                assert_eq!(Some(metadata.clone()), decrypted_metadata);
+            }
+        }
+    }
+
+    #[test]
+    fn test_authentication() {
+        let plaintext = include_bytes!("./mod.rs").to_vec();
+        let key = b"this_is_my_key".to_vec();
+
+        for mode in [Mode::AES128, Mode::AES256] {
+            let ciphertext = crypt(
+                false,
+                plaintext.clone(),
+                key.clone(),
+                mode,
+                KdfId::PBKDF2,
+                Padding::None,
+            )
+            .expect("encryption failed");
+
+            let wrong_key = crypt(
+                true,
+                ciphertext.clone(),
+                b"wrong".to_vec(),
+                mode,
+                KdfId::PBKDF2,
+                Padding::None,
+            );
+            assert!(matches!(wrong_key, Err(RfcError::WrongKey)));
+
+            let archive = wrapper::aligned(header::parse_prefix(&ciphertext).unwrap().header);
+            let header: Header = rkyv::from_bytes(&archive).unwrap();
+            let position = |needle: &[u8]| {
+                ciphertext
+                    .windows(needle.len())
+                    .position(|window| window == needle)
+                    .unwrap()
+            };
+
+            // Flipping a bit in the ciphertext or tag fails authentication
+            for i in [ciphertext.len() / 2, ciphertext.len() - 1] {
+                let mut tampered = ciphertext.clone();
+                tampered[i] ^= 1;
+
+                let result = crypt(
+                    true,
+                    tampered,
+                    key.clone(),
+                    mode,
+                    KdfId::PBKDF2,
+                    Padding::None,
+                );
+                assert!(
+                    matches!(result, Err(RfcError::Tampered(_))),
+                    "tampered byte {} not detected",
+                    i
+                );
+            }
+
+            // Different KDF parameters derive a different key
+            let mut tampered = ciphertext.clone();
+            tampered[position(&header.kdf_params)] ^= 1;
+            assert!(crypt(
+                true,
+                tampered,
+                key.clone(),
+                mode,
+                KdfId::PBKDF2,
+                Padding::None
+            )
+            .is_err());
+
+            let truncated = ciphertext[..ciphertext.len() - 1].to_vec();
+            let result = crypt(
+                true,
+                truncated,
+                key.clone(),
+                mode,
+                KdfId::PBKDF2,
+                Padding::None,
+            );
+            assert!(matches!(result, Err(RfcError::Truncated(_))));
+        }
+    }
+
+    #[test]
+    fn test_prefix_authentication() {
+        let plaintext = include_bytes!("./header.rs").to_vec();
+        let key = b"this_is_my_key".to_vec();
+        let decrypt = |bytes| {
+            crypt(
+                true,
+                bytes,
+                key.clone(),
+                Mode::AES256,
+                KdfId::PBKDF2,
+                Padding::None,
+            )
+        };
+
+        let ciphertext = crypt(
+            false,
+            plaintext.clone(),
+            key.clone(),
+            Mode::AES256,
+            KdfId::PBKDF2,
+            Padding::None,
+        )
+        .expect("encryption failed");
+        assert_eq!(decrypt(ciphertext.clone()).unwrap(), plaintext);
+
+        // No byte of the prefix can be changed without failing decryption
+        for i in 0..header::PREFIX_SIZE {
+            let mut tampered = ciphertext.clone();
+            tampered[i] ^= 0x80;
+            assert!(
+                decrypt(tampered).is_err(),
+                "tampered prefix byte {} not detected",
+                i
+            );
+        }
+
+        // Flags are not checked while parsing, but by the MAC
+        let mut tampered = ciphertext.clone();
+        tampered[6] ^= 0x80;
+        assert!(matches!(decrypt(tampered), Err(RfcError::Tampered(_))));
+    }
+
+    #[test]
+    fn test_signing() {
+        let plaintext = include_bytes!("./sign.rs").to_vec();
+        let key = b"this_is_my_key".to_vec();
+        let signing_key = sign::generate_key();
+        let sign = Signing::Sign(signing_key.clone());
+        let verify = Signing::Verify(signing_key.verifying_key());
+        let wrong_verify = Signing::Verify(sign::generate_key().verifying_key());
+
+        let crypt = |decrypt, input: &[u8], codec, signing: Option<&Signing>| {
+            let options = Options {
+                decrypt,
+                key: key.clone(),
+                codec,
+                signing: signing.cloned(),
+                ..Options::default()
+            };
+
+            core_buf(options, input, Some(input.len())).map(|(bytes, _)| bytes)
+        };
+
+        for codec in [Plain, B64] {
+            let signed = crypt(false, &plaintext, codec, Some(&sign)).expect("encryption failed");
+            let unsigned = crypt(false, &plaintext, codec, None).expect("encryption failed");
+
+            let decrypted =
+                crypt(true, &signed, codec, Some(&verify)).expect("verification failed");
+            assert_eq!(plaintext, decrypted);
+
+            // Signatures are only checked when asked for
+            let decrypted = crypt(true, &signed, codec, None).expect("decryption failed");
+            assert_eq!(plaintext, decrypted);
+
+            assert!(matches!(
+                crypt(true, &signed, codec, Some(&wrong_verify)),
+                Err(RfcError::BadSignature(_))
+            ));
+            assert!(matches!(
+                crypt(true, &unsigned, codec, Some(&verify)),
+                Err(RfcError::BadSignature(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_decrypt_metadata() {
+        let filename = "./Cargo.toml";
+        let plaintext = std::fs::read(filename).unwrap();
+        let metadata = Metadata::from_path(filename, Some("foo".to_string())).unwrap();
+        let key = b"this_is_my_key".to_vec();
+
+        [false, true].into_iter().for_each(|compress| {
+            let options = || Options {
+                key: key.clone(),
+                codec: B64,
+                compress,
+                ..Options::default()
+            };
+            let (ciphertext, _) = core_buf(
+                Options {
+                    padding: Padding::Padme,
+                    metadata: Some(metadata.clone()),
+                    ..options()
+                },
+                &plaintext[..],
+                Some(plaintext.len()),
+            )
+            .expect("encryption failed");
+
+            let (decrypted, decrypted_metadata) = core_buf(
+                Options {
+                    decrypt: true,
+                    ..options()
+                },
+                &ciphertext[..],
+                Some(ciphertext.len()),
+            )
+            .expect("decryption failed");
+
+            assert_eq!(plaintext, decrypted);
+            assert_eq!(Some(metadata.clone()), decrypted_metadata);
+        });
+    }
+
+    #[test]
+    fn test_mode_none() {
+        let transform = |decode: bool, bytes: &[u8], codec, compress, metadata| {
+            let options = Options {
+                decrypt: decode,
+                mode: Some(Mode::NONE),
+                codec,
+                compress,
+                metadata,
+                ..Options::default()
+            };
+
+            core_buf(options, bytes, Some(bytes.len())).map(|(bytes, _)| bytes)
+        };
+
+        for plaintext in test_cases() {
+            for codec in [Plain, Hex, B64, B32, Z85, Armor] {
+                for compress in [false, true] {
+                    let encoded = transform(false, &plaintext, codec, compress, None)
+                        .expect("encoding failed");
+                    let decoded =
+                        transform(true, &encoded, codec, compress, None).expect("decoding failed");
+
+                    assert_eq!(plaintext, decoded);
+                }
+            }
+
+            // Bytes are passed through without any header
+            assert_eq!(
+                plaintext,
+                transform(false, &plaintext, Plain, false, None).unwrap()
+            );
+
+            let mut b64 = Vec::new();
+            encoding::encode_b64(&mut plaintext.as_slice(), &mut b64).unwrap();
+            assert_eq!(b64, transform(false, &plaintext, B64, false, None).unwrap());
+        }
+
+        let metadata = Metadata::from_path("./Cargo.toml", None).unwrap();
+        assert!(transform(false, b"foo", B64, false, Some(metadata)).is_err());
+    }
+
     pub fn test_cases() -> Vec<Vec<u8>> {
         vec![
             include_bytes!("../../Cargo.toml").to_vec(),
@@ -452,29 +1319,63 @@ pub mod tests {
 
     pub fn test_rfc_crypt(cipher: Mode) {
         test_cases().into_iter().for_each(|plaintext| {
-            let plaintext = plaintext;
             let key = b"this_is_my_key";
 
-            let ciphertext = crypt(false, plaintext.to_vec(), key.clone().to_vec(), cipher)
-                .expect("failed to encrypt");
+            [Padding::None, Padding::Padme]
+                .into_iter()
+                .for_each(|padding| {
+                    let ciphertext = crypt(
+                        false,
+                        plaintext.to_vec(),
+                        key.to_vec(),
+                        cipher,
+                        KdfId::PBKDF2,
+                        padding,
+                    )
+                    .expect("failed to encrypt");
 
-            let decrypt_result =
-                crypt(true, ciphertext, key.to_vec(), cipher).expect("failed to decrypt");
+                    let decrypt_result = crypt(
+                        true,
+                        ciphertext,
+                        key.to_vec(),
+                        cipher,
+                        KdfId::PBKDF2,
+                        Padding::None,
+                    )
+                    .expect("failed to decrypt");
 
-            assert_eq!(plaintext, decrypt_result);
+                    assert_eq!(plaintext, decrypt_result);
+                })
         })
     }
 
-    pub fn test_cipher<C: Cipher>() {
+    pub fn test_cipher(cipher: &dyn Cipher) {
         test_cases().into_iter().for_each(|plaintext| {
-            let plaintext = plaintext;
             let key = "this_is_my_key".as_bytes();
-            let ciphertext = C::encrypt(plaintext.clone(), &key).expect("encryption failed");
-            assert!(ciphertext.len() != 0);
+            let len = plaintext.len().next_multiple_of(cipher.block_size());
+            let mut padded = plaintext.clone();
+            padded.resize(len, 0);
+
+            let mut ciphertext = padded.clone();
+            cipher
+                .encrypt(&mut ciphertext, 0, key)
+                .expect("encryption failed");
+            assert!(len == 0 || ciphertext != padded);
+
+            // Encrypting in parts at their offsets gives the same ciphertext
+            let mid = (len / 2).next_multiple_of(cipher.block_size()).min(len);
+            let mut parts = padded.clone();
+            let (first, second) = parts.split_at_mut(mid);
+            cipher.encrypt(first, 0, key).expect("encryption failed");
+            cipher
+                .encrypt(second, mid as u64, key)
+                .expect("encryption failed");
+            assert_eq!(ciphertext, parts);
 
-            let plaintext_result =
-                C::decrypt::<Vec<u8>, &[u8]>(ciphertext, key.into()).expect("decryption failed");
-            assert_eq!(plaintext, plaintext_result);
+            cipher
+                .decrypt(&mut ciphertext, 0, key)
+                .expect("decryption failed");
+            assert_eq!(padded, ciphertext);
         });
     }
 }
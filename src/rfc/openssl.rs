@@ -0,0 +1,396 @@
+//! Compatibility with files of `openssl enc -aes-256-cbc -pbkdf2 -salt`.
+//!
+//! The files start with the `Salted__` magic and an 8-byte random salt. The AES key and
+//! CBC IV are derived together from the passphrase with PBKDF2-HMAC, whose digest and
+//! iterations are not recorded in the file and must be given again when decrypting:
+//!
+//! `Salted__ | salt (8) | AES-CBC ciphertext, PKCS#7 padded`
+//!
+//! With `openssl enc -a`, the whole file is Base64 in 64-column lines. openssl files are
+//! not authenticated, so a wrong passphrase is only detected by bad padding, and corrupted
+//! files may decrypt to garbage.
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use aes::{Aes128, Aes256};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use rand_core::{OsRng, RngCore};
+
+use std::io::{Read, Write};
+
+use super::buf;
+use super::encoding::Encoding;
+use super::error::RfcError;
+use super::pbkdf2::{pbkdf2_derive, Pbkdf2Digest};
+use super::Mode;
+
+/// Magic at the start of salted files
+const MAGIC: &[u8] = b"Salted__";
+
+/// Size of the salt following the magic
+const SALT_SIZE: usize = 8;
+
+/// Size of the AES block and the CBC IV
+const BLOCK_SIZE: usize = 16;
+
+/// Columns of Base64 lines written by `openssl enc -a`
+const B64_COLUMNS: usize = 64;
+
+/// PBKDF2 iterations of `openssl enc -pbkdf2` without `-iter`
+pub const DEFAULT_ITERATIONS: u32 = 10000;
+
+/// Parameters of openssl files, which are not recorded in the files themselves
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OpensslOptions {
+    /// AES-128-CBC or AES-256-CBC
+    pub cipher: Mode,
+    /// PBKDF2 digest, `-md`
+    pub digest: Pbkdf2Digest,
+    /// PBKDF2 iterations, `-iter`
+    pub iterations: u32,
+}
+
+impl Default for OpensslOptions {
+    /// The defaults of `openssl enc -aes-256-cbc -pbkdf2`
+    fn default() -> Self {
+        Self {
+            cipher: Mode::AES256,
+            digest: Pbkdf2Digest::Sha256,
+            iterations: DEFAULT_ITERATIONS,
+        }
+    }
+}
+
+/// Encrypts or decrypts an openssl file. The codec is `Encoding::Plain` for binary files
+/// or `Encoding::B64` for those of `openssl enc -a`; `Encoding::Auto` detects Base64 when
+/// decrypting.
+pub fn core_openssl<R, W>(
+    decrypt: bool,
+    key: Vec<u8>,
+    options: OpensslOptions,
+    input: R,
+    input_len: Option<usize>,
+    output: W,
+    codec: Encoding,
+) -> Result<usize, RfcError>
+where
+    R: Read,
+    W: Write,
+{
+    match (decrypt, codec) {
+        (_, Encoding::Plain | Encoding::B64) | (true, Encoding::Auto) => {}
+        (_, codec) => {
+            return Err(RfcError::Encoding(format!(
+                "openssl files cannot be encoded as {}",
+                codec
+            )))
+        }
+    }
+
+    let bytes = buf::read_bytes(input, input_len)?;
+
+    if !decrypt {
+        let ciphertext = encrypt_openssl(options, &key, &bytes)?;
+        let ciphertext = match codec {
+            Encoding::B64 => encode_b64(&ciphertext),
+            _ => ciphertext,
+        };
+
+        return buf::write_bytes(output, ciphertext);
+    }
+
+    let codec = match codec {
+        Encoding::Auto => detect_openssl_encoding(&bytes),
+        codec => codec,
+    };
+    let bytes = match codec {
+        Encoding::B64 => decode_b64(&bytes)?,
+        _ => bytes,
+    };
+
+    buf::write_bytes(output, decrypt_openssl(options, &key, &bytes)?)
+}
+
+/// Encrypts `plaintext` to a binary openssl file with a random salt.
+pub fn encrypt_openssl(
+    options: OpensslOptions,
+    key: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, RfcError> {
+    let mut salt = [0u8; SALT_SIZE];
+    OsRng.fill_bytes(&mut salt);
+
+    encrypt_with(options, key, &salt, plaintext)
+}
+
+/// Decrypts a binary openssl file.
+pub fn decrypt_openssl(
+    options: OpensslOptions,
+    key: &[u8],
+    bytes: &[u8],
+) -> Result<Vec<u8>, RfcError> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        return Err(RfcError::Deserialize(String::from(
+            "missing Salted__ magic, only salted openssl files are supported",
+        )));
+    };
+
+    let Some((salt, ciphertext)) = rest.split_at_checked(SALT_SIZE) else {
+        return Err(RfcError::Truncated(String::from(
+            "openssl salt is truncated",
+        )));
+    };
+    if ciphertext.is_empty() || ciphertext.len() % BLOCK_SIZE != 0 {
+        return Err(RfcError::Truncated(format!(
+            "openssl ciphertext of {} bytes is not whole AES blocks",
+            ciphertext.len()
+        )));
+    }
+
+    let (cipher_key, iv) = derive_key_iv(options, key, salt)?;
+    let plaintext = match options.cipher {
+        Mode::AES128 => cbc::Decryptor::<Aes128>::new_from_slices(&cipher_key, &iv)
+            .expect("bad AES-128 key or IV length")
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+        _ => cbc::Decryptor::<Aes256>::new_from_slices(&cipher_key, &iv)
+            .expect("bad AES-256 key or IV length")
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+    };
+
+    // Bad padding is all a wrong passphrase, digest or iteration count leaves behind
+    plaintext.map_err(|_| RfcError::WrongKey)
+}
+
+fn encrypt_with(
+    options: OpensslOptions,
+    key: &[u8],
+    salt: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, RfcError> {
+    let (cipher_key, iv) = derive_key_iv(options, key, salt)?;
+    let ciphertext = match options.cipher {
+        Mode::AES128 => cbc::Encryptor::<Aes128>::new_from_slices(&cipher_key, &iv)
+            .expect("bad AES-128 key or IV length")
+            .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+        _ => cbc::Encryptor::<Aes256>::new_from_slices(&cipher_key, &iv)
+            .expect("bad AES-256 key or IV length")
+            .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+    };
+
+    Ok([MAGIC, salt, &ciphertext].concat())
+}
+
+/// Derives the AES key and the IV, which openssl takes from one PBKDF2 output.
+fn derive_key_iv(
+    options: OpensslOptions,
+    key: &[u8],
+    salt: &[u8],
+) -> Result<(Vec<u8>, [u8; BLOCK_SIZE]), RfcError> {
+    let key_size = match options.cipher {
+        Mode::AES128 => 16,
+        Mode::AES256 => 32,
+        mode => {
+            return Err(RfcError::NotImplemented(format!(
+                "openssl files with cipher {}",
+                mode
+            )))
+        }
+    };
+    if options.iterations == 0 {
+        return Err(RfcError::InvalidKey(String::from(
+            "PBKDF2 needs at least 1 iteration",
+        )));
+    }
+
+    let mut derived = vec![0u8; key_size + BLOCK_SIZE];
+    pbkdf2_derive(options.digest, key, salt, options.iterations, &mut derived);
+
+    let iv = derived.split_off(key_size);
+    Ok((derived, iv.try_into().expect("bad IV length")))
+}
+
+/// Encodes `bytes` as `openssl enc -a` does, in 64-column lines.
+fn encode_b64(bytes: &[u8]) -> Vec<u8> {
+    let encoded = STANDARD.encode(bytes);
+
+    let mut wrapped = Vec::with_capacity(encoded.len() + encoded.len() / B64_COLUMNS + 1);
+    for line in encoded.as_bytes().chunks(B64_COLUMNS) {
+        wrapped.extend_from_slice(line);
+        wrapped.push(b'\n');
+    }
+
+    wrapped
+}
+
+/// Decodes Base64 in lines of any length.
+fn decode_b64(bytes: &[u8]) -> Result<Vec<u8>, RfcError> {
+    let encoded: Vec<u8> = bytes
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+
+    STANDARD
+        .decode(encoded)
+        .map_err(|err| RfcError::Encoding(format!("bad openssl Base64: {}", err)))
+}
+
+/// Detects Base64 files, whose magic always encodes to `U2FsdGVkX1`.
+fn detect_openssl_encoding(bytes: &[u8]) -> Encoding {
+    match bytes.trim_ascii_start().starts_with(b"U2FsdGVkX1") {
+        true => Encoding::B64,
+        false => Encoding::Plain,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        core_openssl, decrypt_openssl, encrypt_openssl, encrypt_with, OpensslOptions, MAGIC,
+        SALT_SIZE,
+    };
+    use crate::rfc::encoding::Encoding;
+    use crate::rfc::error::RfcError;
+    use crate::rfc::pbkdf2::Pbkdf2Digest;
+    use crate::rfc::Mode;
+
+    const PLAINTEXT: &[u8] = include_bytes!("../../tests/fixtures/openssl/plaintext.txt");
+    const PASSPHRASE: &[u8] = b"openssl test passphrase";
+
+    /// Fixtures written by tests/fixtures/openssl/generate.sh, with their options
+    fn fixtures() -> [(OpensslOptions, &'static [u8]); 2] {
+        [
+            (
+                OpensslOptions::default(),
+                include_bytes!("../../tests/fixtures/openssl/aes-256-cbc.bin"),
+            ),
+            (
+                OpensslOptions {
+                    cipher: Mode::AES128,
+                    digest: Pbkdf2Digest::Sha1,
+                    iterations: 5000,
+                },
+                include_bytes!("../../tests/fixtures/openssl/aes-128-cbc-sha1.bin"),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_openssl_known_answers() {
+        for (options, fixture) in fixtures() {
+            assert_eq!(
+                decrypt_openssl(options, PASSPHRASE, fixture).unwrap(),
+                PLAINTEXT
+            );
+
+            // Encrypting with the fixture's salt reproduces it
+            let salt = &fixture[MAGIC.len()..MAGIC.len() + SALT_SIZE];
+            let encrypted = encrypt_with(options, PASSPHRASE, salt, PLAINTEXT).unwrap();
+            assert_eq!(encrypted, fixture, "{:?} fixture mismatch", options);
+        }
+    }
+
+    #[test]
+    fn test_openssl_b64_fixture() {
+        let fixture = include_bytes!("../../tests/fixtures/openssl/aes-256-cbc-sha512.b64");
+        let options = OpensslOptions {
+            digest: Pbkdf2Digest::Sha512,
+            iterations: 1000,
+            ..Default::default()
+        };
+
+        for codec in [Encoding::B64, Encoding::Auto] {
+            let mut decrypted = Vec::new();
+            core_openssl(
+                true,
+                PASSPHRASE.to_vec(),
+                options,
+                &fixture[..],
+                None,
+                &mut decrypted,
+                codec,
+            )
+            .unwrap();
+            assert_eq!(decrypted, PLAINTEXT);
+        }
+    }
+
+    #[test]
+    fn test_openssl_round_trip() {
+        let plaintext = include_bytes!("./openssl.rs");
+
+        for codec in [Encoding::Plain, Encoding::B64] {
+            let mut encrypted = Vec::new();
+            core_openssl(
+                false,
+                b"key".to_vec(),
+                OpensslOptions::default(),
+                &plaintext[..],
+                None,
+                &mut encrypted,
+                codec,
+            )
+            .unwrap();
+
+            // openssl enc -a wraps lines at 64 columns
+            if codec == Encoding::B64 {
+                assert!(encrypted
+                    .split(|&b| b == b'\n')
+                    .all(|line| line.len() <= 64));
+            }
+
+            let mut decrypted = Vec::new();
+            core_openssl(
+                true,
+                b"key".to_vec(),
+                OpensslOptions::default(),
+                encrypted.as_slice(),
+                None,
+                &mut decrypted,
+                Encoding::Auto,
+            )
+            .unwrap();
+            assert_eq!(plaintext.as_slice(), decrypted);
+        }
+
+        let options = OpensslOptions::default();
+        let encrypted = encrypt_openssl(options, b"key", b"").unwrap();
+        assert_eq!(encrypted.len(), MAGIC.len() + SALT_SIZE + 16);
+        assert_eq!(decrypt_openssl(options, b"key", &encrypted).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_openssl_errors() {
+        let (options, fixture) = fixtures()[0];
+
+        assert!(matches!(
+            decrypt_openssl(options, b"wrong passphrase", fixture),
+            Err(RfcError::WrongKey)
+        ));
+
+        let other_digest = OpensslOptions {
+            digest: Pbkdf2Digest::Sha512,
+            ..options
+        };
+        assert!(decrypt_openssl(other_digest, PASSPHRASE, fixture).is_err());
+
+        assert!(matches!(
+            decrypt_openssl(options, PASSPHRASE, &fixture[..fixture.len() - 1]),
+            Err(RfcError::Truncated(_))
+        ));
+        assert!(matches!(
+            decrypt_openssl(options, PASSPHRASE, &fixture[..12]),
+            Err(RfcError::Truncated(_))
+        ));
+        assert!(matches!(
+            decrypt_openssl(options, PASSPHRASE, &fixture[MAGIC.len()..]),
+            Err(RfcError::Deserialize(_))
+        ));
+
+        let no_cipher = OpensslOptions {
+            cipher: Mode::NONE,
+            ..options
+        };
+        assert!(encrypt_openssl(no_cipher, b"key", b"").is_err());
+    }
+}
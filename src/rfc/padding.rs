@@ -0,0 +1,159 @@
+use super::error::RfcError;
+
+/// Padding is applied to the plaintext before encryption to hide its exact length.
+#[derive(
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    serde::Serialize,
+    serde::Deserialize,
+    Copy,
+    Clone,
+    PartialEq,
+    Debug,
+    clap::ValueEnum,
+)]
+#[archive(check_bytes)]
+pub enum Padding {
+    /// No padding other than what the cipher needs
+    None,
+    /// Padmé, which leaks at most O(log log L) bits of the length L, with at most 12% overhead
+    Padme,
+}
+
+impl std::fmt::Display for Padding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Padme => write!(f, "padme"),
+        }
+    }
+}
+
+/// Padded lengths are always multiples of PAD_BLOCKSIZE, so that
+/// block ciphers do not have to add (and record) their own padding.
+const PAD_BLOCKSIZE: usize = 16;
+
+/// The first padding byte. All following padding bytes are 0x00 (ISO/IEC 7816-4).
+const PAD_MARKER: u8 = 0x80;
+
+/// Returns the Padmé length for `len`, i.e. `len` rounded up so that only
+/// the top `floor(log2(e)) + 1` bits of it are kept, where `e = floor(log2(len))`.
+pub fn padme_len(len: usize) -> usize {
+    if len < 2 {
+        return len;
+    }
+
+    let e = len.ilog2();
+    let s = e.ilog2() + 1;
+    let mask = (1usize << (e - s)) - 1;
+
+    (len + mask) & !mask
+}
+
+/// Returns the total length of `len` bytes of plaintext after padding.
+pub fn padded_len(len: usize, padding: Padding) -> usize {
+    match padding {
+        Padding::None => len,
+        // One extra byte for the padding marker
+        Padding::Padme => padme_len(len + 1).next_multiple_of(PAD_BLOCKSIZE),
+    }
+}
+
+/// Appends the padding of `len` bytes of plaintext, which end `buf`, according to `padding`.
+pub fn pad(buf: &mut Vec<u8>, len: usize, padding: Padding) {
+    if padding == Padding::None {
+        return;
+    }
+
+    let padding_len = padded_len(len, padding) - len;
+    buf.reserve_exact(padding_len);
+    buf.push(PAD_MARKER);
+    buf.resize(buf.len() + padding_len - 1, 0);
+}
+
+/// Returns the padding bytes that `pad` appends before zeros, i.e. the marker if any.
+pub fn marker(padding: Padding) -> &'static [u8] {
+    match padding {
+        Padding::None => &[],
+        Padding::Padme => &[PAD_MARKER],
+    }
+}
+
+/// Removes padding added by `pad` from `bytes`.
+pub fn unpad(mut bytes: Vec<u8>, padding: Padding) -> Result<Vec<u8>, RfcError> {
+    if padding == Padding::None {
+        return Ok(bytes);
+    }
+
+    match bytes.iter().rposition(|byte| *byte != 0) {
+        Some(i) if bytes[i] == PAD_MARKER => {
+            bytes.truncate(i);
+            Ok(bytes)
+        }
+        _ => Err(RfcError::Decryption(String::from("bad padding"))),
+    }
+}
+
+#[test]
+fn test_padme_len() {
+    let cases = [
+        (0, 0),
+        (1, 1),
+        (9, 10),
+        (100, 104),
+        (1000, 1024),
+        (1025, 1088),
+    ];
+    for (len, expected) in cases {
+        assert_eq!(
+            padme_len(len),
+            expected,
+            "unexpected padme length for {}",
+            len
+        );
+    }
+
+    for len in 2..100_000 {
+        let padded = padme_len(len);
+        assert!(padded >= len);
+        // Padmé overhead is at most 12%
+        assert!(
+            (padded - len) * 100 <= len * 12,
+            "overhead too large for {}",
+            len
+        );
+    }
+}
+
+#[test]
+fn test_pad_hides_length() {
+    let lens: std::collections::HashSet<usize> = (1000..2000)
+        .map(|len| padded_len(len, Padding::Padme))
+        .collect();
+
+    assert!(lens.len() < 20);
+    assert!(lens.iter().all(|len| len % PAD_BLOCKSIZE == 0));
+}
+
+#[test]
+fn test_pad_unpad() {
+    let bytes = include_bytes!("./padding.rs").to_vec();
+    for len in [0, 1, 15, 16, 17, 1000, bytes.len()] {
+        // Trailing 0x00 and 0x80 bytes in plaintext must survive padding removal
+        let mut plaintext = bytes[..len].to_vec();
+        plaintext.extend_from_slice(&[PAD_MARKER, 0, 0]);
+
+        for padding in [Padding::None, Padding::Padme] {
+            let mut padded = plaintext.clone();
+            pad(&mut padded, plaintext.len(), padding);
+            assert_eq!(padded.len(), padded_len(plaintext.len(), padding));
+
+            let unpadded = unpad(padded, padding).expect("failed to unpad");
+            assert_eq!(plaintext, unpadded);
+        }
+    }
+
+    assert!(unpad(vec![1, 2, 3, 0, 0], Padding::Padme).is_err());
+    assert!(unpad(vec![0, 0], Padding::Padme).is_err());
+}
@@ -0,0 +1,31 @@
+use super::error::RfcError;
+
+/// Runs `f` on a thread pool of `threads` threads. Parallel work done by `f`,
+/// like block encryption and chunk compression, is spread across that pool.
+///
+/// If `threads` is `None`, rayon's global pool with one thread per core is used.
+pub fn with_threads<T, F>(threads: Option<usize>, f: F) -> Result<T, RfcError>
+where
+    T: Send,
+    F: FnOnce() -> Result<T, RfcError> + Send,
+{
+    match threads {
+        None => f(),
+        Some(threads) => rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|err| RfcError::ThreadPool(err.to_string()))?
+            .install(f),
+    }
+}
+
+#[test]
+fn test_with_threads() {
+    for threads in [1, 2, 3] {
+        let n = with_threads(Some(threads), || Ok(rayon::current_num_threads())).unwrap();
+        assert_eq!(threads, n);
+    }
+
+    let n = with_threads(None, || Ok(rayon::current_num_threads())).unwrap();
+    assert_eq!(rayon::current_num_threads(), n);
+}
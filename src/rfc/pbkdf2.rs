@@ -3,9 +3,11 @@ use base64::{self, engine::general_purpose};
 use password_hash::Salt;
 use pbkdf2::pbkdf2_hmac;
 use rand_core::{OsRng, RngCore};
-use sha2::Sha256;
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
 
 use super::error::RfcError;
+use super::kdf::Kdf;
 
 const PBKDF2_ROUNDS: u32 = 4096;
 
@@ -20,6 +22,73 @@ where
     Ok(key)
 }
 
+/// Like `pbkdf2_key`, for key sizes only known at runtime, such as those of registered ciphers.
+pub fn pbkdf2_key_vec<T, U>(password: T, salt: &U, len: usize) -> Vec<u8>
+where
+    T: AsRef<[u8]>,
+    U: AsRef<[u8]>,
+{
+    let mut key = vec![0u8; len];
+
+    pbkdf2_hmac::<Sha256>(password.as_ref(), salt.as_ref(), PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+/// PBKDF2-HMAC-SHA256 of rfc's own files, whose parameters are the salt.
+/// Its id is 0, the value of the reserved prefix byte before files stored KDF ids.
+pub struct KdfPbkdf2 {}
+
+impl Kdf for KdfPbkdf2 {
+    fn id(&self) -> u8 {
+        0
+    }
+
+    fn name(&self) -> &'static str {
+        "pbkdf2"
+    }
+
+    fn generate_params(&self) -> Result<Vec<u8>, RfcError> {
+        generate_salt()
+    }
+
+    fn derive(&self, secret: &[u8], params: &[u8], len: usize) -> Result<Vec<u8>, RfcError> {
+        Ok(pbkdf2_key_vec(secret, &params, len))
+    }
+}
+
+/// Hash function of PBKDF2-HMAC, for foreign formats that let users choose it
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum Pbkdf2Digest {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl std::fmt::Display for Pbkdf2Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sha1 => write!(f, "sha1"),
+            Self::Sha256 => write!(f, "sha256"),
+            Self::Sha512 => write!(f, "sha512"),
+        }
+    }
+}
+
+/// Fills `key` with PBKDF2-HMAC of `digest` and `rounds`. rfc's own files use `pbkdf2_key`.
+pub fn pbkdf2_derive(
+    digest: Pbkdf2Digest,
+    password: &[u8],
+    salt: &[u8],
+    rounds: u32,
+    key: &mut [u8],
+) {
+    match digest {
+        Pbkdf2Digest::Sha1 => pbkdf2_hmac::<Sha1>(password, salt, rounds, key),
+        Pbkdf2Digest::Sha256 => pbkdf2_hmac::<Sha256>(password, salt, rounds, key),
+        Pbkdf2Digest::Sha512 => pbkdf2_hmac::<Sha512>(password, salt, rounds, key),
+    }
+}
+
 pub fn generate_salt() -> Result<Vec<u8>, RfcError> {
     // 16-byte random Base64 for salt generation (will be 22 bytes in Base64)
     let mut b64 = rand_b64()?;
@@ -27,10 +96,7 @@ pub fn generate_salt() -> Result<Vec<u8>, RfcError> {
 
     let salt =
         Salt::from_b64(std::str::from_utf8(&b64).expect("b64 is not UTF8")).map_err(|err| {
-            RfcError::Encryption(format!(
-                "failed to generate salt from b64: {}",
-                err.to_string()
-            ))
+            RfcError::Encryption(format!("failed to generate salt from b64: {}", err))
         })?;
 
     Ok(salt.to_string().as_bytes().to_vec())
@@ -38,8 +104,7 @@ pub fn generate_salt() -> Result<Vec<u8>, RfcError> {
 
 fn rand_b64() -> Result<Vec<u8>, RfcError> {
     // 16-byte random Base64 for salt generation (will be 22 bytes in Base64)
-    let mut b64 = Vec::<u8>::new();
-    b64.resize(16 * 4 / 3 + 4, 0);
+    let mut b64 = vec![0u8; 16 * 4 / 3 + 4];
 
     {
         let mut rand = [0u8; 16];
@@ -47,10 +112,7 @@ fn rand_b64() -> Result<Vec<u8>, RfcError> {
         general_purpose::STANDARD_NO_PAD
             .encode_slice(rand, &mut b64)
             .map_err(|err| {
-                RfcError::Encryption(format!(
-                    "failed to encode rand bytes to b64: {}",
-                    err.to_string(),
-                ))
+                RfcError::Encryption(format!("failed to encode rand bytes to b64: {}", err,))
             })?;
     }
 
@@ -59,7 +121,7 @@ fn rand_b64() -> Result<Vec<u8>, RfcError> {
 
 #[test]
 fn test_gen_salt() {
-    [0..5].into_iter().for_each(|_| {
+    (0..5).for_each(|_| {
         if let Err(err) = generate_salt() {
             eprintln!("got error: {:?}", err);
             panic!("generate_salt returned an error");
@@ -76,6 +138,8 @@ fn test_gen_key() {
         .expect("failed to create pbkdf2 key");
 
     assert_eq!(key1.len(), 32);
+    assert_eq!(pbkdf2_key_vec(password, &salt, 32), key1);
+    assert_eq!(KdfPbkdf2 {}.derive(password, &salt, 32).unwrap(), key1);
 
     let key2 = pbkdf2_key::<32, &[u8], Vec<u8>>(&password[..], &salt)
         .expect("failed to create pbkdf2 key");
@@ -83,19 +147,51 @@ fn test_gen_key() {
     assert_eq!(key1, key2);
 }
 
+#[test]
+fn test_pbkdf2_derive() {
+    // RFC 6070 and its SHA-2 counterparts, with 1 round
+    let vectors = [
+        (
+            Pbkdf2Digest::Sha1,
+            "0c60c80f961f0e71f3a9b524af6012062fe037a6",
+        ),
+        (
+            Pbkdf2Digest::Sha256,
+            "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b",
+        ),
+        (
+            Pbkdf2Digest::Sha512,
+            "867f70cf1ade02cff3752599a3a53dc4af34c7a669815ae5d513554e1c8cf252\
+             c02d470a285a0501bad999bfe943c08f050235d7d68b1da55e63f73b60a57fce",
+        ),
+    ];
+
+    for (digest, expected) in vectors {
+        let mut key = vec![0u8; expected.len() / 2];
+        pbkdf2_derive(digest, b"password", b"salt", 1, &mut key);
+        assert_eq!(hex::encode(key), expected, "{}", digest);
+    }
+}
+
 #[test]
 fn test_pbkdf2_encryption() {
-    use super::Cipher;
+    use super::cipher::Cipher;
 
     let password = b"password";
     let salt = generate_salt().expect("failed to generate salt");
     let key1 = pbkdf2_key::<32, _, _>(password, &salt).expect("failed to generate pbkdf2 key");
 
-    let plaintext = include_str!("./mod.rs").as_bytes();
-    let ciphertext = super::aes::CipherAes256::encrypt(plaintext, key1).expect("failed to encrypt");
+    let mut plaintext = include_str!("./mod.rs").as_bytes().to_vec();
+    plaintext.resize(plaintext.len().next_multiple_of(16), 0);
+    let mut buf = plaintext.clone();
+    super::aes::CipherAes256 {}
+        .encrypt(&mut buf, 0, &key1)
+        .expect("failed to encrypt");
 
     let key2 = pbkdf2_key::<32, _, _>(password, &salt).expect("failed to generate pbkdf2 key");
-    let decrypted = super::aes::CipherAes256::decrypt(ciphertext, key2).expect("failed to decrypt");
+    super::aes::CipherAes256 {}
+        .decrypt(&mut buf, 0, &key2)
+        .expect("failed to decrypt");
 
-    assert_eq!(plaintext, decrypted);
+    assert_eq!(plaintext, buf);
 }
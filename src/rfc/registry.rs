@@ -0,0 +1,134 @@
+//! Registry of pluggable implementations by id and name, shared by the cipher and KDF
+//! registries.
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::{Arc, RwLock};
+
+use crate::rfc::error::RfcError;
+
+/// Entry of a registry, identified by a unique id and a unique name.
+pub trait RegistryEntry<Id> {
+    fn entry_id(&self) -> Id;
+
+    fn entry_name(&self) -> &'static str;
+}
+
+/// Entries by id. Entries can be added but not replaced or removed.
+pub struct Registry<Id, Entry: ?Sized> {
+    /// Kind of the entries, e.g. "cipher", for error messages
+    kind: &'static str,
+    entries: RwLock<BTreeMap<Id, Arc<Entry>>>,
+}
+
+impl<Id, Entry> Registry<Id, Entry>
+where
+    Id: Ord + Copy + Display,
+    Entry: RegistryEntry<Id> + ?Sized,
+{
+    /// Returns a registry of `kind` holding the built-in entries `builtins`.
+    pub fn new<I>(kind: &'static str, builtins: I) -> Self
+    where
+        I: IntoIterator<Item = Arc<Entry>>,
+    {
+        let entries = builtins
+            .into_iter()
+            .map(|entry| (entry.entry_id(), entry))
+            .collect();
+
+        Self {
+            kind,
+            entries: RwLock::new(entries),
+        }
+    }
+
+    /// Adds `entry` under its id. Ids and names already registered cannot be replaced.
+    pub fn register(&self, entry: Arc<Entry>) -> Result<(), RfcError> {
+        let mut entries = self.entries.write().unwrap_or_else(|_| self.poisoned());
+
+        let id = entry.entry_id();
+        if let Some(registered) = entries.get(&id) {
+            return Err(RfcError::Registry(format!(
+                "{} id {} is already registered to {}",
+                self.kind,
+                id,
+                registered.entry_name()
+            )));
+        }
+        if entries
+            .values()
+            .any(|registered| registered.entry_name() == entry.entry_name())
+        {
+            return Err(RfcError::Registry(format!(
+                "{} name {} is already registered",
+                self.kind,
+                entry.entry_name()
+            )));
+        }
+
+        entries.insert(id, entry);
+        Ok(())
+    }
+
+    /// Returns the entry registered under `id`.
+    pub fn get(&self, id: Id) -> Option<Arc<Entry>> {
+        self.read().get(&id).cloned()
+    }
+
+    /// Returns the entry registered as `name`.
+    pub fn get_name(&self, name: &str) -> Option<Arc<Entry>> {
+        self.read()
+            .values()
+            .find(|entry| entry.entry_name() == name)
+            .cloned()
+    }
+
+    /// Returns all entries, ordered by id.
+    pub fn entries(&self) -> Vec<Arc<Entry>> {
+        self.read().values().cloned().collect()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, BTreeMap<Id, Arc<Entry>>> {
+        self.entries.read().unwrap_or_else(|_| self.poisoned())
+    }
+
+    fn poisoned(&self) -> ! {
+        panic!("{} registry is poisoned", self.kind)
+    }
+}
+
+#[test]
+fn test_registry() {
+    struct Named(u8, &'static str);
+
+    impl RegistryEntry<u8> for Named {
+        fn entry_id(&self) -> u8 {
+            self.0
+        }
+
+        fn entry_name(&self) -> &'static str {
+            self.1
+        }
+    }
+
+    let registry = Registry::new("test", [Arc::new(Named(2, "two"))]);
+    registry.register(Arc::new(Named(1, "one"))).unwrap();
+
+    assert_eq!(registry.get(1).unwrap().entry_name(), "one");
+    assert_eq!(registry.get_name("two").unwrap().entry_id(), 2);
+    assert!(registry.get(3).is_none());
+    assert!(registry.get_name("three").is_none());
+
+    let ids: Vec<u8> = registry.entries().iter().map(|entry| entry.0).collect();
+    assert_eq!(ids, [1, 2]);
+
+    // Neither ids nor names can be registered twice
+    assert!(matches!(
+        registry.register(Arc::new(Named(2, "deux"))),
+        Err(RfcError::Registry(_))
+    ));
+    assert!(matches!(
+        registry.register(Arc::new(Named(3, "one"))),
+        Err(RfcError::Registry(_))
+    ));
+    assert_eq!(registry.entries().len(), 2);
+}
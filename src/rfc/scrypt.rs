@@ -0,0 +1,74 @@
+//! scrypt KDF of rfc's own files and of age passphrases.
+//!
+//! Its parameters are the work factor, as log2(N), followed by the salt. r and p are
+//! 8 and 1, as age uses.
+use rand_core::{OsRng, RngCore};
+use scrypt::Params;
+
+use super::error::RfcError;
+use super::kdf::Kdf;
+
+/// Work factor of new files, as log2(N)
+pub const SCRYPT_WORK_FACTOR: u8 = 18;
+
+/// Highest work factor accepted when decrypting, which bounds the memory and time used
+pub const MAX_SCRYPT_WORK_FACTOR: u8 = 22;
+
+const SALT_SIZE: usize = 16;
+
+/// scrypt with a random salt and a work factor of `SCRYPT_WORK_FACTOR`
+pub struct KdfScrypt {}
+
+impl Kdf for KdfScrypt {
+    fn id(&self) -> u8 {
+        1
+    }
+
+    fn name(&self) -> &'static str {
+        "scrypt"
+    }
+
+    fn generate_params(&self) -> Result<Vec<u8>, RfcError> {
+        let mut params = vec![0u8; 1 + SALT_SIZE];
+        params[0] = SCRYPT_WORK_FACTOR;
+        OsRng.fill_bytes(&mut params[1..]);
+
+        Ok(params)
+    }
+
+    fn derive(&self, secret: &[u8], params: &[u8], len: usize) -> Result<Vec<u8>, RfcError> {
+        let bad_params =
+            |msg: &str| RfcError::Decryption(format!("bad scrypt parameters: {}", msg));
+
+        let (&work_factor, salt) = params.split_first().ok_or(bad_params("empty"))?;
+        if work_factor > MAX_SCRYPT_WORK_FACTOR {
+            return Err(bad_params(&format!("work factor {}", work_factor)));
+        }
+        let params =
+            Params::new(work_factor, 8, 1, len).map_err(|err| bad_params(&err.to_string()))?;
+
+        let mut key = vec![0u8; len];
+        scrypt::scrypt(secret, salt, &params, &mut key)
+            .map_err(|err| bad_params(&err.to_string()))?;
+
+        Ok(key)
+    }
+}
+
+#[test]
+fn test_scrypt() {
+    // Known answers are checked by the scrypt vectors of the age testkit
+    let params = KdfScrypt {}.generate_params().unwrap();
+    assert_eq!(params.len(), 1 + SALT_SIZE);
+    assert_eq!(params[0], SCRYPT_WORK_FACTOR);
+
+    let params = [&[10], &params[1..]].concat();
+    let key = KdfScrypt {}.derive(b"password", &params, 32).unwrap();
+    assert_eq!(key.len(), 32);
+    assert_eq!(KdfScrypt {}.derive(b"password", &params, 32).unwrap(), key);
+
+    assert!(KdfScrypt {}.derive(b"password", &[], 32).is_err());
+    assert!(KdfScrypt {}
+        .derive(b"password", &[MAX_SCRYPT_WORK_FACTOR + 1], 32)
+        .is_err());
+}
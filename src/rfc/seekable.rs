@@ -0,0 +1,676 @@
+//! Seekable container format, which allows decrypting any byte range without
+//! decrypting the whole file.
+//!
+//! The plaintext is split into chunks of `chunk_size` bytes, and each chunk is sealed
+//! independently with the AEAD of the header's cipher, e.g. AES-GCM. Chunk nonces are built from a random prefix, the chunk index
+//! and a flag marking the last chunk (the STREAM construction), so reordered, dropped or
+//! truncated chunks fail authentication. The header is used as associated data for every chunk.
+//! Keys are derived with the KDF registered under the header's KDF id.
+//!
+//! Layout: `magic | version | mode | KDF id | chunk_size (u32 LE) | nonce prefix |
+//! KDF params len (u16 LE) | KDF params | key check | chunks..`
+use rand_core::{OsRng, RngCore};
+use rayon::prelude::*;
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use super::cipher::ChunkAead;
+use super::error::RfcError;
+use super::mac::{self, KEY_CHECK_SIZE};
+use super::{KdfId, Mode};
+
+pub const MAGIC: &[u8; 8] = b"rfc-seek";
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+/// Largest chunk size accepted, which bounds the buffers allocated from an unauthenticated header
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+const VERSION: u8 = 3;
+const TAG_SIZE: usize = 16;
+const NONCE_PREFIX_SIZE: usize = 7;
+/// Size of the header up to the KDF params: magic, version, mode, KDF id, chunk size,
+/// nonce prefix and KDF params length
+pub(crate) const FIXED_HEADER_SIZE: usize = 8 + 1 + 1 + 1 + 4 + NONCE_PREFIX_SIZE + 2;
+
+struct SeekableHeader {
+    mode: Mode,
+    kdf: KdfId,
+    chunk_size: usize,
+    nonce_prefix: [u8; NONCE_PREFIX_SIZE],
+    kdf_params: Vec<u8>,
+    key_check: [u8; KEY_CHECK_SIZE],
+}
+
+impl SeekableHeader {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(FIXED_HEADER_SIZE + self.kdf_params.len() + KEY_CHECK_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(self.mode.id());
+        bytes.push(self.kdf.id());
+        bytes.extend_from_slice(&(self.chunk_size as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.nonce_prefix);
+        bytes.extend_from_slice(&(self.kdf_params.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&self.kdf_params);
+        bytes.extend_from_slice(&self.key_check);
+
+        bytes
+    }
+
+    /// Reads and parses a header from `r`, returning it with its raw bytes.
+    fn read_from<R: Read>(mut r: R) -> Result<(Self, Vec<u8>), RfcError> {
+        let mut fixed = [0u8; FIXED_HEADER_SIZE];
+        r.read_exact(&mut fixed).map_err(header_read_error)?;
+        check_fixed_header(&fixed)?;
+
+        let mode = Mode::from_id(fixed[9])?;
+        let kdf = KdfId::from_id(fixed[10])?;
+        let chunk_size = u32::from_le_bytes(fixed[11..15].try_into().unwrap()) as usize;
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(RfcError::Tampered(format!("bad chunk size {}", chunk_size)));
+        }
+
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+        nonce_prefix.copy_from_slice(&fixed[15..15 + NONCE_PREFIX_SIZE]);
+
+        let params_len = u16::from_le_bytes(fixed[FIXED_HEADER_SIZE - 2..].try_into().unwrap());
+        let mut kdf_params = vec![0u8; params_len as usize];
+        r.read_exact(&mut kdf_params).map_err(header_read_error)?;
+
+        let mut key_check = [0u8; KEY_CHECK_SIZE];
+        r.read_exact(&mut key_check).map_err(header_read_error)?;
+
+        let mut raw = fixed.to_vec();
+        raw.extend_from_slice(&kdf_params);
+        raw.extend_from_slice(&key_check);
+
+        Ok((
+            Self {
+                mode,
+                kdf,
+                chunk_size,
+                nonce_prefix,
+                kdf_params,
+                key_check,
+            },
+            raw,
+        ))
+    }
+}
+
+fn check_fixed_header(fixed: &[u8; FIXED_HEADER_SIZE]) -> Result<(), RfcError> {
+    if &fixed[..8] != MAGIC {
+        return Err(RfcError::Deserialize(String::from(
+            "not a seekable rfc file",
+        )));
+    }
+    if fixed[8] != VERSION {
+        return Err(RfcError::UnsupportedVersion(fixed[8]));
+    }
+
+    Ok(())
+}
+
+/// Returns the size of the whole header from its first `FIXED_HEADER_SIZE` bytes, for
+/// readers that cannot parse it from a `Read`.
+#[cfg(feature = "tokio")]
+pub(crate) fn header_size(fixed: &[u8; FIXED_HEADER_SIZE]) -> Result<usize, RfcError> {
+    check_fixed_header(fixed)?;
+    let params_len = u16::from_le_bytes(fixed[FIXED_HEADER_SIZE - 2..].try_into().unwrap());
+
+    Ok(FIXED_HEADER_SIZE + params_len as usize + KEY_CHECK_SIZE)
+}
+
+/// Reports EOF while reading the header as truncation.
+fn header_read_error(err: io::Error) -> RfcError {
+    match err.kind() {
+        io::ErrorKind::UnexpectedEof => {
+            RfcError::Truncated(String::from("incomplete seekable header"))
+        }
+        _ => RfcError::IoError(err),
+    }
+}
+
+/// Returns the AEAD of the cipher of `mode` keyed with `cipher_key`, which seals the chunks.
+fn chunk_aead(mode: Mode, cipher_key: &[u8]) -> Result<Box<dyn ChunkAead>, RfcError> {
+    mode.cipher()?
+        .chunk_aead(cipher_key)
+        .ok_or(RfcError::UnknownCipher(mode.id()))
+}
+
+fn chunk_nonce(prefix: &[u8; NONCE_PREFIX_SIZE], index: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..NONCE_PREFIX_SIZE].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_SIZE..11].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = last as u8;
+
+    nonce
+}
+
+/// Header and chunk cipher of a seekable file, which seal and open its chunks. Shared
+/// with the streaming adapters of `stream.rs`, which write and read the same format.
+pub(crate) struct Chunks {
+    mode: Mode,
+    cipher: Box<dyn ChunkAead>,
+    /// Raw header bytes, the associated data of every chunk
+    header: Vec<u8>,
+    nonce_prefix: [u8; NONCE_PREFIX_SIZE],
+    chunk_size: usize,
+}
+
+impl Chunks {
+    /// Creates the header of a new file, deriving the cipher key from `key` with `kdf`.
+    pub(crate) fn create(
+        key: &[u8],
+        mode: Mode,
+        kdf: KdfId,
+        chunk_size: usize,
+    ) -> Result<Self, RfcError> {
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(RfcError::Encryption(format!(
+                "bad chunk size {}",
+                chunk_size
+            )));
+        }
+        if mode == Mode::NONE {
+            // Chunks are always sealed, so there is no unencrypted seekable format
+            return Err(RfcError::UnknownCipher(mode.id()));
+        }
+
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+        OsRng.fill_bytes(&mut nonce_prefix);
+
+        let kdf_params = kdf.kdf()?.generate_params()?;
+        if kdf_params.len() > u16::MAX as usize {
+            return Err(RfcError::Encryption(format!(
+                "{} parameters of {} bytes do not fit the seekable header",
+                kdf,
+                kdf_params.len()
+            )));
+        }
+
+        let cipher_key = super::cipher_key(key.to_vec(), kdf, &kdf_params, mode)?;
+        let header = SeekableHeader {
+            mode,
+            kdf,
+            chunk_size,
+            nonce_prefix,
+            kdf_params,
+            key_check: mac::key_check(&cipher_key),
+        };
+
+        Ok(Self {
+            mode,
+            cipher: chunk_aead(mode, &cipher_key)?,
+            header: header.to_bytes(),
+            nonce_prefix,
+            chunk_size,
+        })
+    }
+
+    /// Reads the header of a file from `r`, and derives and checks the cipher key.
+    pub(crate) fn open<R: Read>(r: R, key: &[u8]) -> Result<Self, RfcError> {
+        let (header, raw) = SeekableHeader::read_from(r)?;
+
+        let cipher_key =
+            super::cipher_key(key.to_vec(), header.kdf, &header.kdf_params, header.mode)?;
+        mac::verify_key_check(&cipher_key, &header.key_check)?;
+
+        Ok(Self {
+            mode: header.mode,
+            cipher: chunk_aead(header.mode, &cipher_key)?,
+            header: raw,
+            nonce_prefix: header.nonce_prefix,
+            chunk_size: header.chunk_size,
+        })
+    }
+
+    pub(crate) fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Returns the raw header, which precedes the chunks.
+    pub(crate) fn header(&self) -> &[u8] {
+        &self.header
+    }
+
+    /// Returns the plaintext size of every chunk but the last.
+    pub(crate) fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Returns the size of every sealed chunk but the last.
+    pub(crate) fn sealed_chunk_size(&self) -> usize {
+        self.chunk_size + TAG_SIZE
+    }
+
+    /// Seals chunk `index` of the plaintext. Only the `last` chunk may be shorter than the
+    /// chunk size, and it is sealed with a different nonce so that truncation is detected.
+    pub(crate) fn seal(&self, index: u64, last: bool, chunk: &[u8]) -> Result<Vec<u8>, RfcError> {
+        let index = u32::try_from(index)
+            .map_err(|_| RfcError::Encryption(String::from("input too large")))?;
+
+        self.cipher.seal(
+            &chunk_nonce(&self.nonce_prefix, index, last),
+            &self.header,
+            chunk,
+        )
+    }
+
+    /// Opens sealed chunk `index`, which must be the `last` one if the file ends with it.
+    pub(crate) fn open_chunk(
+        &self,
+        index: u64,
+        last: bool,
+        sealed: &[u8],
+    ) -> Result<Vec<u8>, RfcError> {
+        if sealed.len() < TAG_SIZE {
+            return Err(RfcError::Truncated(String::from("incomplete last chunk")));
+        }
+        let index = u32::try_from(index)
+            .map_err(|_| RfcError::Tampered(format!("bad chunk index {}", index)))?;
+
+        self.cipher.open(
+            &chunk_nonce(&self.nonce_prefix, index, last),
+            &self.header,
+            sealed,
+        )
+    }
+}
+
+/// Reads from `r` until `buf` is full or EOF is reached, returning the number of bytes read.
+pub(crate) fn read_full<R: Read>(mut r: R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(filled)
+}
+
+/// Returns whether `r` starts with the seekable format magic, rewinding `r` afterwards.
+pub fn is_seekable<R: Read + Seek>(mut r: R) -> Result<bool, RfcError> {
+    let mut magic = [0u8; 8];
+    let n = read_full(&mut r, &mut magic).map_err(RfcError::IoError)?;
+    r.seek(SeekFrom::Start(0)).map_err(RfcError::IoError)?;
+
+    Ok(n == magic.len() && &magic == MAGIC)
+}
+
+/// Encrypts all bytes from `input` into the seekable format, writing to `output`.
+/// Returns the number of bytes written.
+pub fn encrypt_seekable<R, W, K>(
+    mut input: R,
+    mut output: W,
+    key: K,
+    mode: Mode,
+    kdf: KdfId,
+    chunk_size: usize,
+) -> Result<usize, RfcError>
+where
+    R: Read,
+    W: Write,
+    K: AsRef<[u8]>,
+{
+    let chunks = Chunks::create(key.as_ref(), mode, kdf, chunk_size)?;
+
+    output
+        .write_all(chunks.header())
+        .map_err(RfcError::IoError)?;
+    let mut written = chunks.header().len();
+
+    // Chunks are sealed in parallel in batches, and written out in order
+    let batch_size = rayon::current_num_threads() * 4;
+    let mut batch: Vec<Vec<u8>> = Vec::with_capacity(batch_size);
+    let mut next = read_chunk(&mut input, chunk_size)?;
+    let mut index: u64 = 0;
+
+    loop {
+        let mut last = false;
+        while batch.len() < batch_size && !last {
+            let chunk = std::mem::take(&mut next);
+
+            // Read ahead, so that we know if the current chunk is the last one
+            if chunk.len() == chunk_size {
+                next = read_chunk(&mut input, chunk_size)?;
+            }
+
+            last = next.is_empty();
+            batch.push(chunk);
+        }
+
+        let sealed = batch
+            .par_iter()
+            .enumerate()
+            .map(|(i, chunk)| chunks.seal(index + i as u64, last && i == batch.len() - 1, chunk))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for sealed_chunk in sealed {
+            output.write_all(&sealed_chunk).map_err(RfcError::IoError)?;
+            written += sealed_chunk.len();
+        }
+
+        if last {
+            return Ok(written);
+        }
+
+        index += batch.len() as u64;
+        batch.clear();
+    }
+}
+
+/// Reads the next chunk of up to `chunk_size` bytes from `input`.
+fn read_chunk<R: Read>(input: R, chunk_size: usize) -> Result<Vec<u8>, RfcError> {
+    let mut chunk = vec![0u8; chunk_size];
+    let len = read_full(input, &mut chunk).map_err(RfcError::IoError)?;
+    chunk.truncate(len);
+
+    Ok(chunk)
+}
+
+/// SeekableReader decrypts a seekable rfc file on demand. Only chunks covering
+/// the bytes actually read are decrypted and authenticated.
+pub struct SeekableReader<R> {
+    inner: R,
+    chunks: Chunks,
+    /// Number of chunks in the file
+    count: u64,
+    len: u64,
+    pos: u64,
+    /// The most recently decrypted chunk and its index
+    chunk: Option<(u64, Vec<u8>)>,
+}
+
+impl<R> SeekableReader<R>
+where
+    R: Read + Seek,
+{
+    /// Parses the header from `inner` and derives the decryption key from `key`.
+    pub fn new<K: AsRef<[u8]>>(mut inner: R, key: K) -> Result<Self, RfcError> {
+        inner.seek(SeekFrom::Start(0)).map_err(RfcError::IoError)?;
+        let chunks = Chunks::open(&mut inner, key.as_ref())?;
+
+        let total = inner.seek(SeekFrom::End(0)).map_err(RfcError::IoError)?;
+        let sealed_len = total - chunks.header().len() as u64;
+        let sealed_chunk_size = chunks.sealed_chunk_size() as u64;
+
+        let count = sealed_len.div_ceil(sealed_chunk_size);
+        let last_sealed_len = sealed_len - count.saturating_sub(1) * sealed_chunk_size;
+        if count == 0 || last_sealed_len < TAG_SIZE as u64 {
+            return Err(RfcError::Truncated(String::from("incomplete last chunk")));
+        }
+
+        Ok(Self {
+            inner,
+            chunks,
+            count,
+            len: sealed_len - count * TAG_SIZE as u64,
+            pos: 0,
+            chunk: None,
+        })
+    }
+
+    /// Returns the plaintext length.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Decrypts chunk `index` into the chunk cache, if it is not already there.
+    fn load_chunk(&mut self, index: u64) -> Result<(), RfcError> {
+        if matches!(self.chunk, Some((cached, _)) if cached == index) {
+            return Ok(());
+        }
+
+        let sealed_chunk_size = self.chunks.sealed_chunk_size() as u64;
+        let offset = self.chunks.header().len() as u64 + index * sealed_chunk_size;
+        self.inner
+            .seek(SeekFrom::Start(offset))
+            .map_err(RfcError::IoError)?;
+
+        let mut sealed = vec![0u8; sealed_chunk_size as usize];
+        let n = read_full(&mut self.inner, &mut sealed).map_err(RfcError::IoError)?;
+        sealed.truncate(n);
+
+        let chunk = self
+            .chunks
+            .open_chunk(index, index + 1 == self.count, &sealed)?;
+
+        self.chunk = Some((index, chunk));
+        Ok(())
+    }
+}
+
+impl<R> Read for SeekableReader<R>
+where
+    R: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.len {
+            return Ok(0);
+        }
+
+        let chunk_size = self.chunks.chunk_size() as u64;
+        let index = self.pos / chunk_size;
+        self.load_chunk(index).map_err(io::Error::other)?;
+
+        let chunk = &self.chunk.as_ref().expect("no chunk loaded").1;
+        let offset = (self.pos % chunk_size) as usize;
+        let n = std::cmp::min(buf.len(), chunk.len() - offset);
+        buf[..n].copy_from_slice(&chunk[offset..offset + n]);
+
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R> Seek for SeekableReader<R>
+where
+    R: Read + Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+
+        self.pos = pos.ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position",
+        ))?;
+
+        Ok(self.pos)
+    }
+}
+
+/// Decrypts plaintext bytes `start..end` of the seekable rfc file `input` and writes them
+/// to `output`. `end` defaults to the end of the plaintext, and is clamped to it.
+pub fn decrypt_range<R, W, K>(
+    input: R,
+    mut output: W,
+    key: K,
+    start: u64,
+    end: Option<u64>,
+) -> Result<usize, RfcError>
+where
+    R: Read + Seek,
+    W: Write,
+    K: AsRef<[u8]>,
+{
+    let mut reader = SeekableReader::new(input, key)?;
+    let end = std::cmp::min(end.unwrap_or(reader.len()), reader.len());
+
+    reader
+        .seek(SeekFrom::Start(start))
+        .map_err(RfcError::IoError)?;
+
+    // Chunk errors are wrapped in io::Error by SeekableReader, so unwrap them for the caller
+    let written = io::copy(&mut reader.take(end.saturating_sub(start)), &mut output)
+        .map_err(RfcError::from_io)?;
+
+    Ok(written as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const KEY: &[u8] = b"this_is_my_key";
+
+    fn encrypt(plaintext: &[u8], mode: Mode, chunk_size: usize) -> Vec<u8> {
+        let mut ciphertext = Vec::new();
+        encrypt_seekable(
+            plaintext,
+            &mut ciphertext,
+            KEY,
+            mode,
+            KdfId::PBKDF2,
+            chunk_size,
+        )
+        .expect("encryption failed");
+
+        ciphertext
+    }
+
+    #[test]
+    fn test_seekable_roundtrip() {
+        let bytes = include_bytes!("./seekable.rs");
+        for mode in [Mode::AES128, Mode::AES256, Mode::CHACHA20] {
+            for len in [0, 1, 16, 17, 100, bytes.len()] {
+                let ciphertext = encrypt(&bytes[..len], mode, 16);
+                assert!(is_seekable(Cursor::new(&ciphertext)).unwrap());
+
+                let mut reader = SeekableReader::new(Cursor::new(&ciphertext), KEY).unwrap();
+                assert_eq!(reader.len(), len as u64);
+
+                let mut decrypted = Vec::new();
+                reader
+                    .read_to_end(&mut decrypted)
+                    .expect("decryption failed");
+                assert_eq!(&bytes[..len], decrypted);
+            }
+        }
+    }
+
+    #[test]
+    fn test_seekable_threads() {
+        let bytes = include_bytes!("./seekable.rs");
+        for threads in [1, 3] {
+            let ciphertext = crate::rfc::parallel::with_threads(Some(threads), || {
+                Ok(encrypt(bytes, Mode::AES256, 32))
+            })
+            .unwrap();
+
+            let mut decrypted = Vec::new();
+            decrypt_range(Cursor::new(&ciphertext), &mut decrypted, KEY, 0, None)
+                .expect("decryption failed");
+            assert_eq!(&bytes[..], decrypted);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_range() {
+        let bytes = include_bytes!("./seekable.rs");
+        let ciphertext = encrypt(bytes, Mode::AES256, 100);
+        let len = bytes.len() as u64;
+
+        let ranges = [(0, Some(0)), (0, Some(1)), (99, Some(101)), (250, None)];
+        let ranges = ranges
+            .into_iter()
+            .chain([(len - 10, Some(len + 10)), (len + 1, None)]);
+
+        for (start, end) in ranges {
+            let mut decrypted = Vec::new();
+            decrypt_range(Cursor::new(&ciphertext), &mut decrypted, KEY, start, end)
+                .expect("decryption failed");
+
+            let end = std::cmp::min(end.unwrap_or(len), len) as usize;
+            let start = std::cmp::min(start as usize, end);
+            assert_eq!(&bytes[start..end], decrypted);
+        }
+    }
+
+    #[test]
+    fn test_seek() {
+        let bytes = include_bytes!("./seekable.rs");
+        let ciphertext = encrypt(bytes, Mode::AES128, 64);
+        let mut reader = SeekableReader::new(Cursor::new(&ciphertext), KEY).unwrap();
+
+        let mut buf = [0u8; 10];
+        reader.seek(SeekFrom::End(-10)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&bytes[bytes.len() - 10..], buf);
+
+        reader.seek(SeekFrom::Start(60)).unwrap();
+        reader.seek(SeekFrom::Current(2)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&bytes[62..72], buf);
+
+        assert!(reader.seek(SeekFrom::Current(-100)).is_err());
+    }
+
+    #[test]
+    fn test_seekable_tampered() {
+        let bytes = include_bytes!("./seekable.rs");
+        let ciphertext = encrypt(bytes, Mode::AES256, 64);
+
+        let mut flipped = ciphertext.clone();
+        let last = flipped.len() - 1;
+        flipped[last] ^= 1;
+        let mut reader = SeekableReader::new(Cursor::new(&flipped), KEY).unwrap();
+        // Chunks before the tampered one are still readable
+        let mut buf = [0u8; 64];
+        reader.read_exact(&mut buf).unwrap();
+        assert!(reader.seek(SeekFrom::End(-1)).is_ok());
+        assert!(reader.read(&mut buf).is_err());
+
+        // Dropping the last chunk must not go unnoticed
+        let truncated = &ciphertext[..ciphertext.len() - (bytes.len() % 64 + TAG_SIZE)];
+        let mut reader = SeekableReader::new(Cursor::new(truncated), KEY).unwrap();
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+
+        // Chunk errors surface as RfcErrors from decrypt_range
+        let result = decrypt_range(Cursor::new(&flipped), Vec::new(), KEY, 0, None);
+        assert!(matches!(result, Err(RfcError::Tampered(_))));
+
+        // A wrong key is caught by the key check before any chunk is read
+        let result = SeekableReader::new(Cursor::new(&ciphertext), b"wrong_key");
+        assert!(matches!(result, Err(RfcError::WrongKey)));
+
+        let result = SeekableReader::new(Cursor::new(&ciphertext[..20]), KEY);
+        assert!(matches!(result, Err(RfcError::Truncated(_))));
+    }
+
+    #[test]
+    fn test_chunk_size_limit() {
+        assert!(Chunks::create(KEY, Mode::AES256, KdfId::PBKDF2, MAX_CHUNK_SIZE).is_ok());
+        assert!(Chunks::create(KEY, Mode::AES256, KdfId::PBKDF2, MAX_CHUNK_SIZE + 1).is_err());
+
+        // The chunk size is checked before anything is allocated from it
+        let mut ciphertext = encrypt(b"plaintext", Mode::AES256, 16);
+        ciphertext[11..15].copy_from_slice(&u32::MAX.to_le_bytes());
+        let result = SeekableReader::new(Cursor::new(&ciphertext), KEY);
+        assert!(matches!(result, Err(RfcError::Tampered(_))));
+    }
+
+    #[test]
+    fn test_chunk_cipher() {
+        let result = Chunks::create(KEY, Mode::NONE, KdfId::PBKDF2, 16);
+        assert!(matches!(result, Err(RfcError::UnknownCipher(0))));
+
+        let mut ciphertext = encrypt(b"plaintext", Mode::AES256, 16);
+        ciphertext[9] = 250;
+        let result = SeekableReader::new(Cursor::new(&ciphertext), KEY);
+        assert!(matches!(result, Err(RfcError::UnknownCipher(250))));
+    }
+}
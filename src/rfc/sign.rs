@@ -0,0 +1,158 @@
+use base64::{engine::general_purpose, Engine as _};
+use ed25519_dalek::{Signature, Signer, Verifier};
+// Re-exported for library users
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+use rand_core::OsRng;
+
+use super::error::RfcError;
+use super::header;
+
+/// Size of an Ed25519 signature
+pub const SIGNATURE_SIZE: usize = ed25519_dalek::SIGNATURE_LENGTH;
+
+const SECRET_KEY_LABEL: &str = "rfc-ed25519-secret";
+const PUBLIC_KEY_LABEL: &str = "rfc-ed25519-public";
+const SIGNATURE_LABEL: &str = "rfc-ed25519-signature";
+
+/// Ed25519 key used to sign files during encryption, or to verify them during decryption.
+#[derive(Clone)]
+pub enum Signing {
+    Sign(SigningKey),
+    Verify(VerifyingKey),
+}
+
+/// Generates a new random Ed25519 signing key.
+pub fn generate_key() -> SigningKey {
+    SigningKey::generate(&mut OsRng)
+}
+
+/// Encodes `key` for a secret key file.
+pub fn encode_secret_key(key: &SigningKey) -> String {
+    encode_labeled(SECRET_KEY_LABEL, key.as_bytes())
+}
+
+/// Encodes `key` for a public key file.
+pub fn encode_public_key(key: &VerifyingKey) -> String {
+    encode_labeled(PUBLIC_KEY_LABEL, key.as_bytes())
+}
+
+/// Encodes `signature` for a detached signature file.
+pub fn encode_signature(signature: &Signature) -> String {
+    encode_labeled(SIGNATURE_LABEL, &signature.to_bytes())
+}
+
+/// Parses a secret key file produced by `encode_secret_key`.
+pub fn decode_secret_key(text: &[u8]) -> Result<SigningKey, RfcError> {
+    decode_labeled(SECRET_KEY_LABEL, text, RfcError::InvalidKey)
+        .map(|bytes| SigningKey::from_bytes(&bytes))
+}
+
+/// Parses a public key file produced by `encode_public_key`.
+pub fn decode_public_key(text: &[u8]) -> Result<VerifyingKey, RfcError> {
+    VerifyingKey::from_bytes(&decode_labeled(
+        PUBLIC_KEY_LABEL,
+        text,
+        RfcError::InvalidKey,
+    )?)
+    .map_err(|err| RfcError::InvalidKey(format!("bad Ed25519 public key: {}", err)))
+}
+
+/// Parses a detached signature file produced by `encode_signature`.
+pub fn decode_signature(text: &[u8]) -> Result<Signature, RfcError> {
+    decode_labeled(SIGNATURE_LABEL, text, RfcError::BadSignature)
+        .map(|bytes| Signature::from_bytes(&bytes))
+}
+
+/// Returns the detached signature of `bytes`.
+pub fn sign(bytes: &[u8], key: &SigningKey) -> Signature {
+    key.sign(bytes)
+}
+
+/// Verifies the detached `signature` of `bytes`.
+pub fn verify(bytes: &[u8], signature: &Signature, key: &VerifyingKey) -> Result<(), RfcError> {
+    key.verify(bytes, signature)
+        .map_err(|_| RfcError::BadSignature(String::from("signature does not match")))
+}
+
+/// Signs an encrypted file, which must be marked as signed in its prefix, by appending
+/// the signature over the prefix, archive and tag.
+pub fn sign_embedded(mut file: Vec<u8>, key: &SigningKey) -> Vec<u8> {
+    let signature = sign(&file, key);
+    file.extend_from_slice(&signature.to_bytes());
+
+    file
+}
+
+/// Verifies the signature embedded in an encrypted file by `sign_embedded`.
+pub fn verify_embedded(file: &[u8], key: &VerifyingKey) -> Result<(), RfcError> {
+    let signature = header::parse_prefix(file)?
+        .signature
+        .ok_or(RfcError::BadSignature(String::from("input is not signed")))?;
+
+    let signed = &file[..file.len() - SIGNATURE_SIZE];
+    let signature =
+        Signature::from_slice(signature).map_err(|err| RfcError::BadSignature(err.to_string()))?;
+
+    verify(signed, &signature, key)
+}
+
+fn encode_labeled(label: &str, bytes: &[u8]) -> String {
+    format!("{} {}\n", label, general_purpose::STANDARD.encode(bytes))
+}
+
+/// Decodes `text` produced by `encode_labeled`, reporting malformed input with `error`.
+fn decode_labeled<const N: usize>(
+    label: &str,
+    text: &[u8],
+    error: fn(String) -> RfcError,
+) -> Result<[u8; N], RfcError> {
+    let text = std::str::from_utf8(text)
+        .map_err(|_| error(format!("expected {}, got binary data", label)))?;
+
+    let encoded = text
+        .trim()
+        .strip_prefix(label)
+        .ok_or_else(|| error(format!("expected {}", label)))?;
+
+    general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|err| error(format!("bad {}: {}", label, err)))?
+        .try_into()
+        .map_err(|_| error(format!("bad {}: wrong length", label)))
+}
+
+#[test]
+fn test_key_files() {
+    let key = generate_key();
+
+    let secret = encode_secret_key(&key);
+    assert_eq!(
+        decode_secret_key(secret.as_bytes()).unwrap().to_bytes(),
+        key.to_bytes()
+    );
+
+    let public = encode_public_key(&key.verifying_key());
+    assert_eq!(
+        decode_public_key(public.as_bytes()).unwrap(),
+        key.verifying_key()
+    );
+
+    // Secret and public key files must not be mixed up
+    assert!(decode_public_key(secret.as_bytes()).is_err());
+    assert!(decode_secret_key(public.as_bytes()).is_err());
+    assert!(decode_secret_key(b"rfc-ed25519-secret AAAA").is_err());
+}
+
+#[test]
+fn test_sign_verify() {
+    let key = generate_key();
+    let bytes = include_bytes!("./sign.rs");
+
+    let signature = sign(bytes, &key);
+    let encoded = encode_signature(&signature);
+    let signature = decode_signature(encoded.as_bytes()).unwrap();
+
+    assert!(verify(bytes, &signature, &key.verifying_key()).is_ok());
+    assert!(verify(&bytes[1..], &signature, &key.verifying_key()).is_err());
+    assert!(verify(bytes, &signature, &generate_key().verifying_key()).is_err());
+}
@@ -0,0 +1,616 @@
+//! `std::io` adapters for rfc streams, so that encryption composes with other streams
+//! without holding whole files in memory.
+//!
+//! Streams are in the seekable format: `EncryptWriter` seals plaintext in chunks as they
+//! fill up, and `DecryptReader` opens them in order, so only one chunk is buffered at a
+//! time. Each chunk is authenticated on its own, and the last one is sealed differently,
+//! so that truncation is detected when the reader reaches the end.
+//!
+//! Compression and encoding are layered around the chunks: plaintext is compressed into a
+//! single LZ4 frame before it is sealed, and the sealed stream is encoded as it is written.
+//! `Decryptor::decrypt` and `decrypt_buf` read streams with the same options, and plain,
+//! uncompressed streams are seekable files that `seekable::SeekableReader` reads as well.
+use lz4_flex::frame::{FrameDecoder, FrameEncoder};
+
+use std::io::{self, Cursor, Read, Write};
+
+use super::builder::{Decryptor, Encryptor};
+use super::encoding::{self, Decoder, Encoder, Encoding};
+use super::error::RfcError;
+use super::padding::Padding;
+use super::seekable::{read_full, Chunks, DEFAULT_CHUNK_SIZE};
+use super::sign::Signing;
+use super::{Mode, Options};
+
+/// Number of leading bytes of a stream that `Encoding::Auto` is detected from. Base64 is
+/// told from URL-safe Base64 by its alphabet, which is practically certain to show in this many.
+const DETECT_SAMPLE_SIZE: usize = 4096;
+
+/// EncryptWriter encrypts everything written to it into an rfc stream, and writes
+/// sealed chunks to the inner writer as they fill up.
+///
+/// The last chunk is only written by `finish`. A writer dropped without `finish` writes
+/// nothing more, and leaves truncated output that fails to decrypt.
+///
+/// ```
+/// use std::io::Write;
+/// use rfc::{Encoding, EncryptWriter, Encryptor};
+///
+/// let encryptor = Encryptor::new().compress(true).encoding(Encoding::B64).key("key");
+/// let mut writer = EncryptWriter::with_encryptor(Vec::new(), encryptor)?;
+/// writer.write_all(b"hello")?;
+/// let ciphertext = writer.finish()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct EncryptWriter<W: Write> {
+    plaintext: Compressor<W>,
+}
+
+/// Plaintext side of an `EncryptWriter`, which compresses into the chunks if set.
+enum Compressor<W: Write> {
+    None(ChunkWriter<Encoder<W>>),
+    /// Boxed, since the encoder buffers a whole LZ4 block
+    Lz4(Box<FrameEncoder<ChunkWriter<Encoder<W>>>>),
+}
+
+impl<W: Write> EncryptWriter<W> {
+    /// Returns a writer encrypting with `key` and the default `Encryptor` options.
+    pub fn new<K: AsRef<[u8]>>(inner: W, key: K) -> Result<Self, RfcError> {
+        Self::with_encryptor(inner, Encryptor::new().key(key))
+    }
+
+    /// Returns a writer encrypting with the cipher, KDF, key, compression and encoding of
+    /// `encryptor`, and writes the header to `inner`. Streams have no padding, metadata
+    /// or signature, and are only encoded with the encodings that stream, so those
+    /// options are rejected.
+    pub fn with_encryptor(inner: W, encryptor: Encryptor) -> Result<Self, RfcError> {
+        encryptor.check()?;
+        let options = encryptor.options()?;
+        check_layered_options(&options)?;
+
+        let mode = options.mode.expect("encryptors always have a cipher");
+        let chunks = Chunks::create(&options.key, mode, options.kdf, DEFAULT_CHUNK_SIZE)?;
+        let chunk_writer = ChunkWriter::new(Encoder::new(options.codec, inner)?, chunks)?;
+
+        let plaintext = match options.compress {
+            true => Compressor::Lz4(Box::new(FrameEncoder::new(chunk_writer))),
+            false => Compressor::None(chunk_writer),
+        };
+
+        Ok(Self { plaintext })
+    }
+
+    /// Ends the compression frame, seals and writes the last chunk, ends the encoding,
+    /// flushes the inner writer and returns it.
+    pub fn finish(self) -> Result<W, RfcError> {
+        let chunk_writer = match self.plaintext {
+            Compressor::None(chunk_writer) => chunk_writer,
+            Compressor::Lz4(encoder) => encoder
+                .finish()
+                .map_err(|err| RfcError::from_io(err.into()))?,
+        };
+
+        let mut inner = chunk_writer.finish()?.finish()?;
+        inner.flush().map_err(RfcError::IoError)?;
+
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        match &mut self.plaintext {
+            Compressor::None(chunk_writer) => chunk_writer.write(bytes),
+            Compressor::Lz4(encoder) => encoder.write(bytes),
+        }
+    }
+
+    /// Flushes the inner writer. The chunk being filled is not written before `finish`.
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.plaintext {
+            Compressor::None(chunk_writer) => chunk_writer.flush(),
+            Compressor::Lz4(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// ChunkWriter seals bytes written to it in chunks, and writes them to the inner writer.
+struct ChunkWriter<W: Write> {
+    inner: W,
+    chunks: Chunks,
+    /// Index of the chunk being filled
+    index: u64,
+    /// Plaintext of the chunk being filled. A full chunk is only sealed once more
+    /// plaintext is written, since the last chunk is sealed differently.
+    chunk: Vec<u8>,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    /// Returns a writer sealing with `chunks`, and writes their header to `inner`.
+    fn new(mut inner: W, chunks: Chunks) -> Result<Self, RfcError> {
+        inner
+            .write_all(chunks.header())
+            .map_err(RfcError::IoError)?;
+
+        Ok(Self {
+            inner,
+            chunks,
+            index: 0,
+            chunk: Vec::new(),
+        })
+    }
+
+    /// Seals and writes the last chunk, and returns the inner writer.
+    fn finish(mut self) -> Result<W, RfcError> {
+        self.write_chunk(true)?;
+
+        Ok(self.inner)
+    }
+
+    fn write_chunk(&mut self, last: bool) -> Result<(), RfcError> {
+        let sealed = self.chunks.seal(self.index, last, &self.chunk)?;
+        self.inner.write_all(&sealed).map_err(RfcError::IoError)?;
+
+        self.index += 1;
+        self.chunk.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ChunkWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        if bytes.is_empty() {
+            return Ok(0);
+        }
+        if self.chunk.len() == self.chunks.chunk_size() {
+            self.write_chunk(false).map_err(io::Error::other)?;
+        }
+
+        let n = std::cmp::min(bytes.len(), self.chunks.chunk_size() - self.chunk.len());
+        self.chunk.extend_from_slice(&bytes[..n]);
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Inner reader of a `DecryptReader`, after the bytes read to detect its encoding.
+type Input<R> = io::Chain<Cursor<Vec<u8>>, R>;
+
+/// DecryptReader yields the plaintext of an rfc stream read from the inner reader,
+/// one authenticated chunk at a time, decompressing it if set.
+///
+/// Errors are returned as `io::Error`s wrapping the `RfcError`, see `RfcError::from_io`.
+/// Plaintext of chunks already read has been authenticated, but a later chunk may still
+/// fail, e.g. if the input is truncated.
+pub struct DecryptReader<R: Read> {
+    plaintext: Decompressor<R>,
+}
+
+/// Plaintext side of a `DecryptReader`, which decompresses the chunks if set.
+enum Decompressor<R: Read> {
+    None(ChunkReader<Decoder<Input<R>>>),
+    Lz4(FrameDecoder<ChunkReader<Decoder<Input<R>>>>),
+}
+
+impl<R: Read> DecryptReader<R> {
+    /// Returns a reader decrypting with `key` and the default `Decryptor` options.
+    pub fn new<K: AsRef<[u8]>>(inner: R, key: K) -> Result<Self, RfcError> {
+        Self::with_decryptor(inner, Decryptor::new().key(key))
+    }
+
+    /// Returns a reader decrypting with the key, decompression and encoding of `decryptor`,
+    /// checking the cipher if it is set. The header is read from `inner`, so a wrong key
+    /// fails here. `Encoding::Auto` is detected from the first bytes of `inner`.
+    pub fn with_decryptor(mut inner: R, decryptor: Decryptor) -> Result<Self, RfcError> {
+        decryptor.check()?;
+        let options = decryptor.options()?;
+        check_layered_options(&options)?;
+
+        let (codec, sample) = match options.codec {
+            Encoding::Auto => {
+                let mut sample = vec![0u8; DETECT_SAMPLE_SIZE];
+                let n = read_full(&mut inner, &mut sample).map_err(RfcError::IoError)?;
+                sample.truncate(n);
+
+                (encoding::detect_encoding(&sample)?, sample)
+            }
+            codec => (codec, Vec::new()),
+        };
+        let input = Decoder::new(codec, Cursor::new(sample).chain(inner))?;
+
+        let chunk_reader = ChunkReader::new(input, &options.key, options.mode)?;
+        let plaintext = match options.compress {
+            true => Decompressor::Lz4(FrameDecoder::new(chunk_reader)),
+            false => Decompressor::None(chunk_reader),
+        };
+
+        Ok(Self { plaintext })
+    }
+
+    pub fn into_inner(self) -> R {
+        let chunk_reader = match self.plaintext {
+            Decompressor::None(chunk_reader) => chunk_reader,
+            Decompressor::Lz4(decoder) => decoder.into_inner(),
+        };
+
+        chunk_reader.inner.into_inner().into_inner().1
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.plaintext {
+            Decompressor::None(chunk_reader) => chunk_reader.read(buf),
+            Decompressor::Lz4(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+/// ChunkReader opens the chunks read from the inner reader in order.
+struct ChunkReader<R: Read> {
+    inner: R,
+    chunks: Chunks,
+    /// Index of the next chunk to open
+    index: u64,
+    /// The next sealed chunk, read ahead to tell whether it is the last one
+    sealed: Vec<u8>,
+    /// Plaintext of the most recently opened chunk
+    chunk: Cursor<Vec<u8>>,
+    done: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// Reads the header from `inner` and derives the key, checking the cipher against `mode` if set.
+    fn new(mut inner: R, key: &[u8], mode: Option<Mode>) -> Result<Self, RfcError> {
+        let chunks = Chunks::open(&mut inner, key)?;
+        match mode {
+            Some(mode) if mode != chunks.mode() => {
+                return Err(RfcError::CipherMismatch(
+                    chunks.mode().to_string(),
+                    mode.to_string(),
+                ))
+            }
+            _ => {}
+        }
+
+        let mut reader = Self {
+            inner,
+            chunks,
+            index: 0,
+            sealed: Vec::new(),
+            chunk: Cursor::new(Vec::new()),
+            done: false,
+        };
+        reader.sealed = reader.read_sealed()?;
+
+        Ok(reader)
+    }
+
+    fn read_sealed(&mut self) -> Result<Vec<u8>, RfcError> {
+        let mut sealed = vec![0u8; self.chunks.sealed_chunk_size()];
+        let n = read_full(&mut self.inner, &mut sealed).map_err(RfcError::from_io)?;
+        sealed.truncate(n);
+
+        Ok(sealed)
+    }
+
+    /// Opens the next chunk. It is the last one if it is short, or if no chunk follows it.
+    fn open_next(&mut self) -> Result<(), RfcError> {
+        let sealed = std::mem::take(&mut self.sealed);
+        if sealed.len() == self.chunks.sealed_chunk_size() {
+            self.sealed = self.read_sealed()?;
+        }
+
+        self.done = self.sealed.is_empty();
+        let chunk = self.chunks.open_chunk(self.index, self.done, &sealed)?;
+
+        self.index += 1;
+        self.chunk = Cursor::new(chunk);
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for ChunkReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.chunk.read(buf)?;
+            if n > 0 || buf.is_empty() || self.done {
+                return Ok(n);
+            }
+
+            self.open_next().map_err(io::Error::other)?;
+        }
+    }
+}
+
+/// Decrypts the decoded stream `bytes` for the core functions, writing the plaintext to
+/// `output` and decompressing it if `options.compress` is set.
+pub(super) fn decrypt_core<W: Write>(
+    options: Options,
+    bytes: &[u8],
+    mut output: W,
+) -> Result<usize, RfcError> {
+    if let Some(Signing::Verify(_)) = options.signing {
+        return Err(RfcError::BadSignature(String::from(
+            "rfc streams are not signed",
+        )));
+    }
+
+    let chunk_reader = ChunkReader::new(bytes, &options.key, options.mode)?;
+    let written = match options.compress {
+        true => io::copy(&mut FrameDecoder::new(chunk_reader), &mut output),
+        false => io::copy(&mut { chunk_reader }, &mut output),
+    }
+    .map_err(RfcError::from_io)?;
+
+    Ok(written as usize)
+}
+
+/// Rejects the options that the seekable format does not support, for the async adapters
+/// that do not layer compression and encodings around it.
+#[cfg(feature = "tokio")]
+pub(super) fn check_options(options: &Options) -> Result<(), RfcError> {
+    check_unsupported([
+        (options.compress, "compression"),
+        (
+            !matches!(options.codec, Encoding::Plain | Encoding::Auto),
+            "encodings",
+        ),
+    ])?;
+
+    check_layered_options(options)
+}
+
+/// Rejects the options that rfc streams do not support, other than the encodings that
+/// `Encoder` and `Decoder` reject.
+fn check_layered_options(options: &Options) -> Result<(), RfcError> {
+    check_unsupported([
+        (!matches!(options.padding, Padding::None), "padding"),
+        (options.metadata.is_some(), "metadata"),
+        (options.signing.is_some(), "signatures"),
+    ])
+}
+
+fn check_unsupported<const N: usize>(unsupported: [(bool, &str); N]) -> Result<(), RfcError> {
+    match unsupported.into_iter().find(|(set, _)| *set) {
+        Some((_, option)) => Err(RfcError::NotImplemented(format!(
+            "{} in rfc streams",
+            option
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecryptReader, EncryptWriter};
+    use crate::rfc::encoding::{
+        Encoding::{self, B58},
+        HexDecoder, HexEncoder,
+    };
+    use crate::rfc::seekable::{self, encrypt_seekable, DEFAULT_CHUNK_SIZE};
+    use crate::rfc::{Decryptor, Encryptor, KdfId, Mode, Padding, RfcError};
+
+    use std::io::{self, Cursor, Read, Write};
+
+    fn encrypt(plaintext: &[u8]) -> Vec<u8> {
+        let mut writer = EncryptWriter::new(Vec::new(), "key").unwrap();
+        io::copy(&mut &plaintext[..], &mut writer).unwrap();
+
+        writer.finish().unwrap()
+    }
+
+    fn decrypt(ciphertext: &[u8], key: &str) -> Result<Vec<u8>, RfcError> {
+        let mut decrypted = Vec::new();
+        DecryptReader::new(ciphertext, key)?
+            .read_to_end(&mut decrypted)
+            .map_err(RfcError::from_io)?;
+
+        Ok(decrypted)
+    }
+
+    #[test]
+    fn test_stream() {
+        let source = include_bytes!("./stream.rs");
+        let repeated = source.repeat(DEFAULT_CHUNK_SIZE / source.len() * 3);
+        let whole_chunks = vec![7u8; 2 * DEFAULT_CHUNK_SIZE];
+
+        for plaintext in [&b""[..], &source[..], &repeated, &whole_chunks] {
+            let ciphertext = encrypt(plaintext);
+            assert_eq!(decrypt(&ciphertext, "key").unwrap(), plaintext);
+
+            // Streams are seekable files, and the other way around
+            let mut decrypted = Vec::new();
+            seekable::decrypt_range(Cursor::new(&ciphertext), &mut decrypted, "key", 0, None)
+                .unwrap();
+            assert_eq!(decrypted, plaintext);
+
+            let mut ciphertext = Vec::new();
+            encrypt_seekable(
+                plaintext,
+                &mut ciphertext,
+                "key",
+                Mode::AES256,
+                KdfId::PBKDF2,
+                DEFAULT_CHUNK_SIZE,
+            )
+            .unwrap();
+            assert_eq!(decrypt(&ciphertext, "key").unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_stream_options() {
+        let plaintext = include_bytes!("./stream.rs");
+        let encryptor = Encryptor::new().cipher(Mode::AES128).key("key");
+
+        // Composed with other adapters, with small writes
+        let mut writer = HexEncoder::new(encryptor.writer(Vec::new()).unwrap());
+        for chunk in plaintext.chunks(7) {
+            writer.write_all(chunk).unwrap();
+        }
+        let ciphertext = writer.into_inner().finish().unwrap();
+
+        let decryptor = Decryptor::new()
+            .cipher(Mode::AES128)
+            .encoding(Encoding::Plain)
+            .key("key");
+        let mut reader = HexDecoder::new(decryptor.clone().reader(&ciphertext[..]).unwrap());
+
+        let mut decrypted = Vec::new();
+        reader.read_to_end(&mut decrypted).unwrap();
+        assert_eq!(plaintext.as_slice(), decrypted);
+
+        assert!(matches!(
+            decryptor.cipher(Mode::AES256).reader(&ciphertext[..]),
+            Err(RfcError::CipherMismatch(..))
+        ));
+    }
+
+    #[test]
+    fn test_stream_layers() {
+        let source = include_bytes!("./stream.rs");
+        let repeated = source.repeat(DEFAULT_CHUNK_SIZE / source.len() * 3);
+
+        for plaintext in [&b""[..], &source[..], &repeated] {
+            for codec in [
+                Encoding::Plain,
+                Encoding::Hex,
+                Encoding::B64,
+                Encoding::B64Url,
+            ] {
+                for compress in [false, true] {
+                    let encryptor = Encryptor::new()
+                        .compress(compress)
+                        .encoding(codec)
+                        .key("key");
+                    let mut writer = encryptor.writer(Vec::new()).unwrap();
+                    for chunk in plaintext.chunks(1000) {
+                        writer.write_all(chunk).unwrap();
+                    }
+                    let ciphertext = writer.finish().unwrap();
+
+                    if compress && !plaintext.is_empty() {
+                        assert!(ciphertext.len() < plaintext.len());
+                    }
+
+                    // Read back with the same options, or with the encoding detected
+                    for decoding in [codec, Encoding::Auto] {
+                        let decryptor = Decryptor::new()
+                            .compress(compress)
+                            .encoding(decoding)
+                            .key("key");
+
+                        let mut decrypted = Vec::new();
+                        decryptor
+                            .clone()
+                            .reader(&ciphertext[..])
+                            .unwrap()
+                            .read_to_end(&mut decrypted)
+                            .unwrap();
+                        assert_eq!(plaintext, decrypted, "{} {}", codec, compress);
+
+                        // The core functions read streams too
+                        assert_eq!(decryptor.decrypt_buf(&ciphertext[..]).unwrap(), plaintext);
+
+                        let mut decrypted = Vec::new();
+                        decryptor.decrypt(&ciphertext[..], &mut decrypted).unwrap();
+                        assert_eq!(plaintext, decrypted);
+
+                        let mut decrypted = Vec::new();
+                        decryptor
+                            .decrypt_slice(&ciphertext, &mut decrypted)
+                            .unwrap();
+                        assert_eq!(plaintext, decrypted);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream_errors() {
+        let plaintext = vec![7u8; 3 * DEFAULT_CHUNK_SIZE / 2];
+        let ciphertext = encrypt(&plaintext);
+
+        assert!(matches!(
+            decrypt(&ciphertext, "wrong key"),
+            Err(RfcError::WrongKey)
+        ));
+
+        // Truncation is detected, also at chunk boundaries
+        let header_len = ciphertext.len() - plaintext.len() - 2 * 16;
+        for len in [
+            header_len,
+            header_len + DEFAULT_CHUNK_SIZE + 16,
+            ciphertext.len() - 1,
+        ] {
+            assert!(matches!(
+                decrypt(&ciphertext[..len], "key"),
+                Err(RfcError::Truncated(_) | RfcError::Tampered(_))
+            ));
+        }
+
+        // Dropping a writer without finishing it writes no last chunk
+        let mut unfinished = Vec::new();
+        let mut writer = EncryptWriter::new(&mut unfinished, "key").unwrap();
+        writer.write_all(&plaintext).unwrap();
+        drop(writer);
+        assert_eq!(unfinished.len(), header_len + DEFAULT_CHUNK_SIZE + 16);
+        assert!(decrypt(&unfinished, "key").is_err());
+
+        assert!(EncryptWriter::with_encryptor(Vec::new(), Encryptor::new()).is_err());
+        assert!(DecryptReader::with_decryptor(&ciphertext[..], Decryptor::new()).is_err());
+        assert!(matches!(
+            EncryptWriter::with_encryptor(
+                Vec::new(),
+                Encryptor::new().padding(Padding::Padme).key("key")
+            ),
+            Err(RfcError::NotImplemented(_))
+        ));
+        assert!(matches!(
+            EncryptWriter::with_encryptor(Vec::new(), Encryptor::new().encoding(B58).key("key")),
+            Err(RfcError::NotImplemented(_))
+        ));
+        assert!(EncryptWriter::with_encryptor(
+            Vec::new(),
+            Encryptor::new().encoding(Encoding::Auto).key("key")
+        )
+        .is_err());
+        assert!(matches!(
+            Decryptor::new()
+                .encoding(Encoding::Z85)
+                .key("key")
+                .reader(&ciphertext[..]),
+            Err(RfcError::NotImplemented(_))
+        ));
+
+        // Compressed streams fail to decompress once tampered with, or without the key
+        let compressed = Encryptor::new()
+            .compress(true)
+            .encoding(Encoding::Hex)
+            .key("key");
+        let mut writer = compressed.writer(Vec::new()).unwrap();
+        writer.write_all(&plaintext).unwrap();
+        let mut ciphertext = writer.finish().unwrap();
+        let decryptor = Decryptor::new().compress(true).key("key");
+        assert!(matches!(
+            decryptor
+                .clone()
+                .key("wrong key")
+                .decrypt_buf(&ciphertext[..]),
+            Err(RfcError::WrongKey)
+        ));
+
+        let i = ciphertext.len() - 8;
+        ciphertext[i] = if ciphertext[i] == b'0' { b'1' } else { b'0' };
+        assert!(matches!(
+            decryptor.decrypt_buf(&ciphertext[..]),
+            Err(RfcError::Tampered(_))
+        ));
+    }
+}
@@ -1,8 +1,13 @@
 use rkyv::ser::serializers::AllocSerializer;
 use rkyv::validation::validators::DefaultValidator;
+use rkyv::AlignedVec;
 
 use super::error::RfcError;
 
+/// Largest byte vector `encode` archives. rkyv archives use 32-bit relative pointers, so the
+/// wrapped bytes and header must stay below 2 GiB; 1 MiB is left for the header.
+pub const MAX_ENCODED_BYTES: usize = i32::MAX as usize - 1024 * 1024;
+
 /// WrapperBytes wraps a byte vector with `H` in a tuple.
 /// This allows us to store metadata in addition to the ciphertext,
 /// like salt. This also allows ciphers to wrap their own metadata
@@ -19,7 +24,11 @@ use super::error::RfcError;
     serde::Deserialize,
 )]
 #[archive(check_bytes)]
-pub struct WrapperBytes<H>(pub H, pub Vec<u8>);
+pub struct WrapperBytes<H>(
+    pub H,
+    // The archived layout is the same, but bytes are serialized with a single copy
+    #[with(rkyv::with::CopyOptimize)] pub Vec<u8>,
+);
 
 /// Methods using `rkyv`.
 impl<'a, H> WrapperBytes<H>
@@ -28,15 +37,22 @@ where
     <H as rkyv::Archive>::Archived:
         rkyv::Deserialize<H, rkyv::Infallible> + rkyv::CheckBytes<DefaultValidator<'a>>,
 {
-    /// Encodes to bytes using `rkyv`.
-    pub fn encode(&'a self) -> Result<Vec<u8>, RfcError> {
-        rkyv::to_bytes(self)
-            .map(|v| v.to_vec())
-            .map_err(|err| RfcError::Deserialize(err.to_string()))
+    /// Encodes to bytes using `rkyv`. Bytes longer than `MAX_ENCODED_BYTES` are rejected,
+    /// since larger archives cannot be represented.
+    pub fn encode(&'a self) -> Result<AlignedVec, RfcError> {
+        if self.1.len() > MAX_ENCODED_BYTES {
+            return Err(RfcError::Serialize(format!(
+                "{} bytes exceed the archive limit of {} bytes, use the seekable format for larger inputs",
+                self.1.len(),
+                MAX_ENCODED_BYTES
+            )));
+        }
+
+        rkyv::to_bytes(self).map_err(|err| RfcError::Deserialize(err.to_string()))
     }
 
     /// Returns the archived form of Self from the given slice of bytes, with zero-copy.
-    pub fn decode_archived(bytes: &'a [u8]) -> Result<&ArchivedWrapperBytes<H>, RfcError> {
+    pub fn decode_archived(bytes: &'a [u8]) -> Result<&'a ArchivedWrapperBytes<H>, RfcError> {
         rkyv::check_archived_root::<Self>(bytes)
             .map_err(|err| RfcError::Deserialize(format!("failed to get archived form: {}", err)))
     }
@@ -51,6 +67,37 @@ where
     }
 }
 
+/// Bytes that are aligned for `decode_archived`, either borrowed or copied to an aligned buffer.
+pub enum AlignedBytes<'a> {
+    Borrowed(&'a [u8]),
+    Owned(AlignedVec),
+}
+
+impl std::ops::Deref for AlignedBytes<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Borrowed(bytes) => bytes,
+            Self::Owned(bytes) => bytes.as_slice(),
+        }
+    }
+}
+
+/// Returns `bytes` as is if it is aligned for `decode_archived`, or an aligned copy if not.
+/// Archives nested inside another archive's bytes, or read from a slice of a larger buffer,
+/// are often misaligned.
+pub fn aligned(bytes: &[u8]) -> AlignedBytes<'_> {
+    match bytes.as_ptr().align_offset(AlignedVec::ALIGNMENT) {
+        0 => AlignedBytes::Borrowed(bytes),
+        _ => {
+            let mut buf = AlignedVec::with_capacity(bytes.len());
+            buf.extend_from_slice(bytes);
+            AlignedBytes::Owned(buf)
+        }
+    }
+}
+
 /// Methods using `serde` traits.
 impl<'a, H> WrapperBytes<H>
 where
@@ -62,11 +109,8 @@ where
     }
 
     pub fn from_bincode(bytes: &'a [u8]) -> Result<Self, RfcError> {
-        bincode::deserialize(bytes.as_ref()).map_err(|err| {
-            RfcError::Deserialize(format!(
-                "failed to serialize to bincode: {}",
-                err.to_string()
-            ))
+        bincode::deserialize(bytes).map_err(|err| {
+            RfcError::Deserialize(format!("failed to serialize to bincode: {}", err))
         })
     }
 
@@ -96,3 +140,31 @@ fn test_decode_archived() {
     assert_eq!(h, archived.0.to_vec());
     assert_eq!(d, archived.1.to_vec());
 }
+
+#[test]
+fn test_encode_limit() {
+    // Zeroed allocations are not touched before the check, so this stays cheap
+    let over_limit = WrapperBytes::<usize>(0, vec![0u8; MAX_ENCODED_BYTES + 1]);
+    assert!(matches!(over_limit.encode(), Err(RfcError::Serialize(_))));
+}
+
+#[test]
+fn test_aligned() {
+    let w = WrapperBytes::<usize>(42, b"data_bytes".to_vec())
+        .encode()
+        .expect("failed to encode");
+
+    // Shift the archive by one byte so that it is misaligned
+    let mut misaligned = vec![0u8; w.len() + 1];
+    misaligned[1..].copy_from_slice(&w);
+    let bytes = &misaligned[1..];
+
+    let realigned = aligned(bytes);
+    assert!(matches!(realigned, AlignedBytes::Owned(_)));
+    assert_eq!(bytes, &*realigned);
+
+    let decoded = WrapperBytes::<usize>::decode(&realigned).expect("failed to decode");
+    assert_eq!(decoded.0, 42);
+
+    assert!(matches!(aligned(&realigned), AlignedBytes::Borrowed(_)));
+}
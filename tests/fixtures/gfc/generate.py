@@ -0,0 +1,99 @@
+#!/usr/bin/env python3
+"""Writes the gfc fixtures with an implementation independent of rfc's and of gfc.
+
+Requires the `cryptography` package. The salt and nonces are fixed, so that
+rfc's tests can reproduce the fixtures byte for byte. Run from this directory.
+"""
+import base64
+import hashlib
+import hmac
+import struct
+
+from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+from cryptography.hazmat.primitives.ciphers.aead import AESGCM, ChaCha20Poly1305
+
+PASSPHRASE = b"gfc test passphrase"
+PBKDF2_ROUNDS = 1000
+SALT = bytes(range(32))
+
+
+def gfc_key(passphrase, salt):
+    return hashlib.pbkdf2_hmac("sha256", passphrase, salt, PBKDF2_ROUNDS, 32)
+
+
+def hchacha20(key, nonce):
+    """HChaCha20 from draft-irtf-cfrg-xchacha, which derives XChaCha20's subkey."""
+
+    def rotl(v, c):
+        return ((v << c) & 0xFFFFFFFF) | (v >> (32 - c))
+
+    def quarter_round(s, a, b, c, d):
+        s[a] = (s[a] + s[b]) & 0xFFFFFFFF
+        s[d] = rotl(s[d] ^ s[a], 16)
+        s[c] = (s[c] + s[d]) & 0xFFFFFFFF
+        s[b] = rotl(s[b] ^ s[c], 12)
+        s[a] = (s[a] + s[b]) & 0xFFFFFFFF
+        s[d] = rotl(s[d] ^ s[a], 8)
+        s[c] = (s[c] + s[d]) & 0xFFFFFFFF
+        s[b] = rotl(s[b] ^ s[c], 7)
+
+    state = list(struct.unpack("<4I", b"expand 32-byte k"))
+    state += list(struct.unpack("<8I", key))
+    state += list(struct.unpack("<4I", nonce))
+
+    for _ in range(10):
+        quarter_round(state, 0, 4, 8, 12)
+        quarter_round(state, 1, 5, 9, 13)
+        quarter_round(state, 2, 6, 10, 14)
+        quarter_round(state, 3, 7, 11, 15)
+        quarter_round(state, 0, 5, 10, 15)
+        quarter_round(state, 1, 6, 11, 12)
+        quarter_round(state, 2, 7, 8, 13)
+        quarter_round(state, 3, 4, 9, 14)
+
+    return struct.pack("<8I", *(state[0:4] + state[12:16]))
+
+
+def aes_gcm(key, plaintext):
+    nonce = bytes(range(100, 112))
+    return nonce + AESGCM(key).encrypt(nonce, plaintext, None)
+
+
+def aes_ctr(key, plaintext):
+    iv = bytes(range(100, 116))
+    encryptor = Cipher(algorithms.AES(key), modes.CTR(iv)).encryptor()
+    ciphertext = encryptor.update(plaintext) + encryptor.finalize()
+    return iv + ciphertext + hmac.new(key, iv + ciphertext, hashlib.sha256).digest()
+
+
+def chacha20(key, plaintext):
+    nonce = bytes(range(100, 124))
+    subkey = hchacha20(key, nonce[:16])
+    sealed = ChaCha20Poly1305(subkey).encrypt(b"\0" * 4 + nonce[16:], plaintext, None)
+    return nonce + sealed
+
+
+def main():
+    # Test vector of draft-irtf-cfrg-xchacha section 2.2.1
+    assert hchacha20(
+        bytes(range(32)), bytes.fromhex("000000090000004a0000000031415927")
+    ).hex() == "82413b4227b27bfed30e42508a877d73a0f9e4d58a74a853c12ec41326d3ecdc"
+
+    with open("plaintext.txt", "rb") as f:
+        plaintext = f.read()
+    key = gfc_key(PASSPHRASE, SALT)
+
+    for name, encrypt in [("aes-gcm", aes_gcm), ("aes-ctr", aes_ctr), ("chacha20", chacha20)]:
+        output = encrypt(key, plaintext) + SALT
+        with open(name + ".bin", "wb") as f:
+            f.write(output)
+
+        if name == "aes-gcm":
+            with open(name + ".hex", "wb") as f:
+                f.write(output.hex().encode() + b"\n")
+            with open(name + ".b64", "wb") as f:
+                f.write(base64.b64encode(output) + b"\n")
+
+
+if __name__ == "__main__":
+    main()
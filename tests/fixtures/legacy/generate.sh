@@ -0,0 +1,13 @@
+#!/bin/sh
+# Writes the legacy fixtures with the rfc binary of the baseline commit b707c0a,
+# before files had a magic, version or MAC. The baseline needs the removed
+# array_chunks feature, so build it with `#![feature(array_chunks)]` dropped and
+# `array_chunks` in src/rfc/buf.rs replaced by `as_chunks`, which changes no
+# output. Usage: generate.sh PATH-TO-BASELINE-RFC. Run from this directory.
+set -eu
+
+rfc=$1
+
+"$rfc" -k key-file -f key -o aes256.bin plaintext.txt
+"$rfc" -k key-file -f key -c aes128 -z -o aes128-lz4.bin plaintext.txt
+"$rfc" -k key-file -f key -e b64 -o aes256.b64 plaintext.txt
@@ -0,0 +1,32 @@
+#!/bin/sh
+# Writes the openssl enc fixtures with the openssl command line tool.
+#
+# The salts are fixed with -S, so that rfc's tests can reproduce the fixtures
+# byte for byte. openssl does not write the Salted__ header when the salt is
+# given, so it is prepended here. Requires openssl and xxd. Run from this
+# directory.
+set -eu
+
+pass="pass:openssl test passphrase"
+
+# Usage: salted SALT OUTFILE ENC-OPTIONS...
+salted() {
+    salt=$1
+    out=$2
+    shift 2
+
+    {
+        printf 'Salted__'
+        echo "$salt" | xxd -r -p
+        openssl enc "$@" -pbkdf2 -S "$salt" -pass "$pass" -in plaintext.txt
+    } >"$out"
+}
+
+# openssl's defaults for -pbkdf2: SHA-256 and 10000 iterations
+salted 0001020304050607 aes-256-cbc.bin -aes-256-cbc
+
+salted 08090a0b0c0d0e0f aes-256-cbc-sha512.bin -aes-256-cbc -md sha512 -iter 1000
+openssl base64 -in aes-256-cbc-sha512.bin -out aes-256-cbc-sha512.b64
+rm aes-256-cbc-sha512.bin
+
+salted 1011121314151617 aes-128-cbc-sha1.bin -aes-128-cbc -md sha1 -iter 5000
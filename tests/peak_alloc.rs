@@ -0,0 +1,75 @@
+//! Measures the heap allocated while encrypting a memory-mapped file. This is its own test
+//! binary, so that the counting allocator sees no other tests.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rfc::mmap::map_file;
+use rfc::{Encryptor, Metadata, Padding};
+
+/// Counts the bytes currently allocated, and the most allocated at once since `reset`.
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(current, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Returns the bytes allocated now, and resets the peak to them.
+fn reset() -> usize {
+    let current = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(current, Ordering::SeqCst);
+    current
+}
+
+#[test]
+fn test_encrypt_slice_peak_alloc() {
+    const INPUT_SIZE: usize = 16 * 1024 * 1024;
+
+    let mut file = tempfile::tempfile().expect("failed to create tmp file");
+    let chunk: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    (0..INPUT_SIZE / chunk.len()).for_each(|_| file.write_all(&chunk).unwrap());
+    drop(chunk);
+    let map = map_file(&file).unwrap().expect("regular file not mapped");
+
+    let encryptor = Encryptor::new()
+        .key("key")
+        .padding(Padding::Padme)
+        .metadata(Metadata {
+            filename: Some(String::from("input")),
+            size: INPUT_SIZE as u64,
+            ..Default::default()
+        });
+
+    let baseline = reset();
+    let written = encryptor
+        .encrypt_slice(&map, std::io::sink())
+        .expect("encryption failed");
+    let peak = PEAK.load(Ordering::SeqCst) - baseline;
+
+    assert!(written > INPUT_SIZE);
+    // Only a segment of the plaintext is buffered, not the input or the output
+    assert!(
+        peak < INPUT_SIZE / 16,
+        "allocated {} bytes at once to encrypt {} bytes",
+        peak,
+        INPUT_SIZE
+    );
+}